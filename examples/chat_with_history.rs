@@ -32,11 +32,11 @@ impl Manager {
         while let Some(Ok(item)) = stream.next().await {
             let content_chunk = item.message.unwrap().content;
             content.push_str(&content_chunk);
-            out.write(content_chunk.as_bytes()).await?;
+            out.write_all(content_chunk.as_bytes()).await?;
             out.flush().await?;
         }
 
-        out.write(b"\n\n").await?;
+        out.write_all(b"\n\n").await?;
         out.flush().await?;
 
         self.update_history(input, &content).await;
@@ -75,7 +75,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let _ = reader.read_line(&mut input).await?;
         match input.trim() {
             "/bye" => break,
-            content => manager.chat(&content).await?,
+            content => manager.chat(content).await?,
         }
     }
 
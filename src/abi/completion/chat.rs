@@ -1,7 +1,8 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize, Serializer};
 
 use crate::{
-    abi::{Message, Options, Role},
+    abi::{KeepAlive, Message, Options, Role, Usage},
     action::OllamaRequest,
 };
 
@@ -31,10 +32,15 @@ pub struct ChatCompletionRequest<'a> {
     /// rather than a stream of objects.
     pub stream: bool,
 
+    /// If `true`, thinking models return their reasoning separately from the answer,
+    /// in the `thinking` field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+
     /// Controls how long the model will stay loaded into memory following the request
     /// (default: 5m).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub keep_alive: Option<i64>,
+    #[serde(skip_serializing_if = "KeepAlive::is_default")]
+    pub keep_alive: KeepAlive,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,7 +48,7 @@ pub struct ChatCompletionResponse {
     /// The model name.
     pub model: String,
 
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
 
     /// Eempty if the response was streamed, if not streamed,
     /// this will contain the full response.
@@ -52,29 +58,15 @@ pub struct ChatCompletionResponse {
 
     pub done: bool,
 
-    /// Time spent generating the response.
-    pub total_duration: Option<i64>,
-
-    /// Time spent in nanoseconds loading the model.
-    pub load_duration: Option<i64>,
-
-    /// Number of tokens in the prompt.
-    pub prompt_eval_count: Option<i64>,
-
-    /// Time in nanoseconds spent generating the response
-    pub prompt_eval_duration: Option<i64>,
-
-    /// Number of tokens in the response.
-    pub eval_count: Option<i64>,
-
-    /// Time in nanoseconds spent generating the response.
-    pub eval_duration: Option<i64>,
+    /// Token counts and timings, only present on the final response.
+    #[serde(flatten)]
+    pub usage: Usage,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ChatCompletionModelResponse {
     pub model: String,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     pub message: ModelResponseMessage,
     pub done_reason: String,
     pub done: bool,
@@ -112,7 +104,7 @@ impl<'a> ChatCompletionRequest<'a> {
         self = Self {
             model: self.model,
             messages: vec![],
-            keep_alive: Some(0),
+            keep_alive: KeepAlive::UnloadNow,
             ..Default::default()
         };
         self
@@ -133,7 +125,7 @@ impl<'a> Serialize for Tool<'a> {
     where
         S: Serializer,
     {
-        let schema_value: serde_json::Value = serde_json::from_str(&self.0)
+        let schema_value: serde_json::Value = serde_json::from_str(self.0)
             .map_err(|e| serde::ser::Error::custom(format!("invalid tool format: {e}")))?;
         schema_value.serialize(serializer)
     }
@@ -152,7 +144,7 @@ impl<'a> Serialize for Format<'a> {
     {
         match *self {
             Format::Json => serializer.serialize_str("json"),
-            Format::Schema(ref s) => {
+            Format::Schema(s) => {
                 let schema_value: serde_json::Value = serde_json::from_str(s)
                     .map_err(|e| serde::ser::Error::custom(format!("invalid JSON schema: {e}")))?;
 
@@ -1,8 +1,12 @@
 use std::fmt::Debug;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{abi::Options, action::OllamaRequest};
+use crate::{
+    abi::{Image, KeepAlive, Options, Usage},
+    action::OllamaRequest,
+};
 
 use super::chat::Format;
 
@@ -18,9 +22,9 @@ pub struct GenerateCompletionRequest<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub suffix: Option<&'a str>,
 
-    /// A list of base64-encoded images (for multimodal models such as `llava`).
+    /// A list of images (for multimodal models such as `llava`), base64-encoded before sending.
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub images: Vec<&'a str>,
+    pub images: Vec<Image>,
 
     /// The foramt to return a response in. Format can be `json` or a JSON schema.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -43,52 +47,47 @@ pub struct GenerateCompletionRequest<'a> {
     /// If `false` the response will be returned as a single response object, rather than a stream of objects
     pub stream: bool,
 
+    /// If `true`, thinking models return their reasoning separately from the answer,
+    /// in the `thinking` field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+
     /// If `true` no formatting will be applied to the prompt. You may choose to use the `raw`
     /// parameter if you are specifying a full templated prompt in your request to the API.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw: Option<bool>,
 
     /// Controls how long the model will stay loaded into memory following the request (default: 5m).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub keep_alive: Option<i64>,
+    #[serde(skip_serializing_if = "KeepAlive::is_default")]
+    pub keep_alive: KeepAlive,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerateCompletionResponse {
     /// The model name.
     pub model: String,
 
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
 
     /// Empty if the response was streamed, if not streamed,
     /// this will contain the full response.
     pub response: String,
 
+    /// The model's reasoning, for thinking models with `think` enabled.
+    #[serde(default)]
+    pub thinking: Option<String>,
+
     pub done: bool,
 
     pub done_reason: Option<String>,
 
-    /// Time spent generating the response.
-    pub total_duration: Option<i64>,
-
-    /// Time spent in nanoseconds loading the model.
-    pub load_duration: Option<i64>,
-
-    /// Number of tokens in the prompt.
-    pub prompt_eval_count: Option<i64>,
-
-    /// Time in nanoseconds spent generating the response
-    pub prompt_eval_duration: Option<i64>,
-
     /// An encoding of the conversation used in this response,
     /// this can be sent in the next request to keep a conversational memory.
     pub context: Option<Vec<i64>>,
 
-    /// Number of tokens in the response.
-    pub eval_count: Option<i64>,
-
-    /// Time in nanoseconds spent generating the response.
-    pub eval_duration: Option<i64>,
+    /// Token counts and timings, only present on the final response.
+    #[serde(flatten)]
+    pub usage: Usage,
 }
 
 impl<'a> GenerateCompletionRequest<'a> {
@@ -113,7 +112,7 @@ impl<'a> GenerateCompletionRequest<'a> {
     pub fn to_unload_model(mut self) -> Self {
         self = Self {
             model: self.model,
-            keep_alive: Some(0),
+            keep_alive: KeepAlive::UnloadNow,
             ..Default::default()
         };
         self
@@ -123,7 +122,7 @@ impl<'a> GenerateCompletionRequest<'a> {
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct GenerateCompletionModelResponse {
     pub model: String,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     pub response: String,
     pub done: bool,
     pub done_reason: String,
@@ -0,0 +1,528 @@
+use std::path::{Path, PathBuf};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::OllamaError;
+
+/// An image attached to a chat message or a generate request (for multimodal models such as `llava`).
+///
+/// Only base64-encoded images are sent to the server as they are, every other source is read,
+/// checked and encoded right before the request is sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Image {
+    /// A base64-encoded image, without any `data:` prefix.
+    Base64(String),
+
+    /// A path to an image file.
+    Path(PathBuf),
+
+    /// The raw bytes of an image file.
+    Bytes(Bytes),
+
+    /// A `data:` URI such as `data:image/png;base64,iVBORw0...`.
+    DataUri(String),
+
+    /// An `http` or `https` URL to download the image from.
+    Url(String),
+}
+
+impl Image {
+    #[inline]
+    pub fn base64(image: impl ToString) -> Self {
+        Image::Base64(image.to_string())
+    }
+
+    #[inline]
+    pub fn path(path: impl AsRef<Path>) -> Self {
+        Image::Path(path.as_ref().to_path_buf())
+    }
+
+    #[inline]
+    pub fn bytes(bytes: impl Into<Bytes>) -> Self {
+        Image::Bytes(bytes.into())
+    }
+
+    #[inline]
+    pub fn url(url: impl ToString) -> Self {
+        Image::Url(url.to_string())
+    }
+
+    /// Whether the image can be sent to the server without being resolved first.
+    #[inline]
+    pub fn is_resolved(&self) -> bool {
+        matches!(self, Image::Base64(_))
+    }
+
+    /// Read, check and base64-encode the image according to `options`.
+    pub async fn resolve(
+        &self,
+        cli: &reqwest::Client,
+        options: &ImageOptions,
+    ) -> Result<String, OllamaError> {
+        let bytes = match self {
+            Image::Base64(image) if options.is_default() => return Ok(image.to_string()),
+            Image::Base64(image) => decode_base64(image)?,
+            Image::Path(path) => read_file(path, options.max_download_bytes()).await?,
+            Image::Bytes(bytes) => bytes.to_vec(),
+            Image::DataUri(uri) => decode_data_uri(uri)?,
+            Image::Url(url) => download(cli, url, options.max_download_bytes()).await?,
+        };
+
+        let bytes = options.apply(bytes)?;
+        Ok(STANDARD.encode(bytes))
+    }
+}
+
+impl From<&str> for Image {
+    /// `data:` URIs, `http(s)` URLs and base64 are detected, anything else is read as a file
+    /// path, e.g. `photo.jpg`. A string that looks like base64 but names an existing file is
+    /// a path. Use [`Image::base64`] or [`Image::path`] to skip detection.
+    fn from(image: &str) -> Self {
+        if image.starts_with("data:") {
+            Image::DataUri(image.to_string())
+        } else if image.starts_with("http://") || image.starts_with("https://") {
+            Image::Url(image.to_string())
+        } else if is_base64(image) && !Path::new(image).exists() {
+            Image::Base64(image.to_string())
+        } else {
+            Image::path(image)
+        }
+    }
+}
+
+impl From<String> for Image {
+    fn from(image: String) -> Self {
+        Image::from(image.as_str())
+    }
+}
+
+impl From<&String> for Image {
+    fn from(image: &String) -> Self {
+        Image::from(image.as_str())
+    }
+}
+
+impl From<PathBuf> for Image {
+    fn from(path: PathBuf) -> Self {
+        Image::Path(path)
+    }
+}
+
+impl From<&Path> for Image {
+    fn from(path: &Path) -> Self {
+        Image::path(path)
+    }
+}
+
+impl From<Vec<u8>> for Image {
+    fn from(bytes: Vec<u8>) -> Self {
+        Image::Bytes(bytes.into())
+    }
+}
+
+impl From<Bytes> for Image {
+    fn from(bytes: Bytes) -> Self {
+        Image::Bytes(bytes)
+    }
+}
+
+impl Serialize for Image {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Image::Base64(image) => serializer.serialize_str(image),
+            _ => Err(serde::ser::Error::custom(
+                "image must be resolved before it is serialized",
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Image {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Image::Base64)
+    }
+}
+
+/// The most bytes an image is downloaded with, as it is held in memory.
+pub const MAX_DOWNLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Limits applied to images before they are sent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageOptions {
+    /// Maximum size of an image in bytes, after downscaling.
+    pub max_bytes: Option<usize>,
+
+    /// Maximum width and height of an image in pixels. Larger images are
+    /// downscaled, preserving their aspect ratio.
+    #[cfg(feature = "image")]
+    pub max_dimension: Option<u32>,
+}
+
+impl ImageOptions {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// The most bytes an image may be downloaded with: `max_bytes` unless the image may be
+    /// downscaled afterwards, and never more than [`MAX_DOWNLOAD_BYTES`].
+    fn max_download_bytes(&self) -> usize {
+        #[cfg(feature = "image")]
+        if self.max_dimension.is_some() {
+            return MAX_DOWNLOAD_BYTES;
+        }
+        self.max_bytes.map_or(MAX_DOWNLOAD_BYTES, |max_bytes| {
+            max_bytes.min(MAX_DOWNLOAD_BYTES)
+        })
+    }
+
+    fn apply(&self, bytes: Vec<u8>) -> Result<Vec<u8>, OllamaError> {
+        let mime = sniff_mime(&bytes)
+            .ok_or_else(|| OllamaError::ImageError("unsupported image format".to_string()))?;
+
+        #[cfg(feature = "image")]
+        let bytes = match self.max_dimension {
+            Some(max_dimension) => downscale(bytes, mime, max_dimension)?,
+            None => bytes,
+        };
+        #[cfg(not(feature = "image"))]
+        let _ = mime;
+
+        match self.max_bytes {
+            Some(max_bytes) if bytes.len() > max_bytes => Err(OllamaError::ImageError(format!(
+                "image is {} bytes, exceeding the limit of {max_bytes} bytes",
+                bytes.len()
+            ))),
+            _ => Ok(bytes),
+        }
+    }
+}
+
+/// Detect the MIME type of an image from its magic bytes.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if bytes.starts_with(b"BM") {
+        Some("image/bmp")
+    } else {
+        None
+    }
+}
+
+/// Resolve every image that has not been base64-encoded yet.
+pub(crate) async fn resolve_images(
+    cli: &reqwest::Client,
+    images: &mut [Image],
+    options: &ImageOptions,
+) -> Result<(), OllamaError> {
+    for image in images.iter_mut() {
+        if image.is_resolved() && options.is_default() {
+            continue;
+        }
+        *image = Image::Base64(image.resolve(cli, options).await?);
+    }
+    Ok(())
+}
+
+/// Whether `image` is made of base64 characters only, padded to a multiple of 4, ignoring
+/// whitespace. Checked without decoding, as images are large.
+fn is_base64(image: &str) -> bool {
+    let chars = image.bytes().filter(|b| !b.is_ascii_whitespace());
+    let (mut len, mut padding) = (0, 0);
+    for b in chars {
+        match b {
+            b'=' => padding += 1,
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' if padding == 0 => {}
+            _ => return false,
+        }
+        len += 1;
+    }
+    len > 0 && len % 4 == 0 && padding <= 2
+}
+
+/// Read an image file, refusing files larger than `max_bytes` before reading them.
+async fn read_file(path: &Path, max_bytes: usize) -> Result<Vec<u8>, OllamaError> {
+    let failed = |e: std::io::Error| {
+        OllamaError::ImageError(format!("failed to read {}: {e}", path.display()))
+    };
+
+    let len = tokio::fs::metadata(path).await.map_err(failed)?.len();
+    if len > max_bytes as u64 {
+        return Err(OllamaError::ImageError(format!(
+            "{} is {len} bytes, exceeding the limit of {max_bytes} bytes",
+            path.display()
+        )));
+    }
+    tokio::fs::read(path).await.map_err(failed)
+}
+
+fn decode_base64(image: &str) -> Result<Vec<u8>, OllamaError> {
+    STANDARD
+        .decode(image.trim())
+        .map_err(|e| OllamaError::ImageError(format!("invalid base64 image: {e}")))
+}
+
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, OllamaError> {
+    let invalid = || OllamaError::ImageError("invalid data URI".to_string());
+
+    let (header, payload) = uri
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or_else(invalid)?;
+
+    if !header.ends_with(";base64") {
+        return Err(OllamaError::ImageError(
+            "only base64-encoded data URIs are supported".to_string(),
+        ));
+    }
+    decode_base64(payload)
+}
+
+async fn download(
+    cli: &reqwest::Client,
+    url: &str,
+    max_bytes: usize,
+) -> Result<Vec<u8>, OllamaError> {
+    let mut response = cli
+        .get(url)
+        .send()
+        .await
+        .map_err(OllamaError::RequestError)?;
+
+    if !response.status().is_success() {
+        return Err(OllamaError::ImageError(format!(
+            "failed to download {url}: {}",
+            response.status()
+        )));
+    }
+
+    let too_large = || {
+        OllamaError::ImageError(format!(
+            "image at {url} exceeds the download limit of {max_bytes} bytes"
+        ))
+    };
+    if response
+        .content_length()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    // The body is read chunk by chunk, as the content length may be missing or wrong.
+    let mut bytes = vec![];
+    while let Some(chunk) = response.chunk().await.map_err(OllamaError::DecodingError)? {
+        if bytes.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+#[cfg(feature = "image")]
+fn downscale(bytes: Vec<u8>, mime: &str, max_dimension: u32) -> Result<Vec<u8>, OllamaError> {
+    use image::{ImageFormat, imageops::FilterType};
+
+    let format = ImageFormat::from_mime_type(mime)
+        .ok_or_else(|| OllamaError::ImageError(format!("unsupported image format: {mime}")))?;
+    let decoded = image::load_from_memory_with_format(&bytes, format)
+        .map_err(|e| OllamaError::ImageError(format!("failed to decode image: {e}")))?;
+
+    if decoded.width() <= max_dimension && decoded.height() <= max_dimension {
+        return Ok(bytes);
+    }
+
+    // GIF and BMP gain nothing from being kept in their own format.
+    let format = match format {
+        ImageFormat::Jpeg => ImageFormat::Jpeg,
+        _ => ImageFormat::Png,
+    };
+    let resized = decoded.resize(max_dimension, max_dimension, FilterType::Triangle);
+    let resized = match format {
+        ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(resized.to_rgb8()),
+        _ => resized,
+    };
+
+    let mut out = std::io::Cursor::new(vec![]);
+    resized
+        .write_to(&mut out, format)
+        .map_err(|e| OllamaError::ImageError(format!("failed to encode image: {e}")))?;
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+
+    use super::{Image, ImageOptions, is_base64, sniff_mime};
+
+    const PNG_1X1: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn image_from_str_should_detect_source() {
+        assert_eq!(Image::from(PNG_1X1), Image::Base64(PNG_1X1.to_string()));
+        assert!(matches!(
+            Image::from("https://example.com/cat.png"),
+            Image::Url(_)
+        ));
+        assert!(matches!(
+            Image::from("data:image/png;base64,AAAA"),
+            Image::DataUri(_)
+        ));
+        assert_eq!(Image::from("photo.jpg"), Image::path("photo.jpg"));
+        assert_eq!(Image::from("AAAA\nAA=="), Image::base64("AAAA\nAA=="));
+        assert_eq!(Image::from("AAA=AA=="), Image::path("AAA=AA=="));
+        assert_eq!(Image::from("AAAAA"), Image::path("AAAAA"));
+    }
+
+    #[test]
+    fn image_from_str_should_prefer_existing_paths() {
+        // A path made of base64 characters only, e.g. `/tmp/ollamaimageAA`.
+        let dir = std::env::temp_dir();
+        let Some(path) = (0..4)
+            .map(|n| dir.join(format!("ollamaimage{}", "A".repeat(n))))
+            .find(|path| is_base64(path.to_str().unwrap()))
+        else {
+            return;
+        };
+        let image = path.to_str().unwrap();
+        assert_eq!(Image::from(image), Image::base64(image));
+
+        std::fs::write(&path, b"").unwrap();
+        let detected = Image::from(image);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(detected, Image::path(&path));
+    }
+
+    #[test]
+    fn sniff_mime_should_work() {
+        let png = STANDARD.decode(PNG_1X1).unwrap();
+        assert_eq!(sniff_mime(&png), Some("image/png"));
+        assert_eq!(sniff_mime(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("image/jpeg"));
+        assert_eq!(sniff_mime(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_mime(b"%PDF-1.7"), None);
+    }
+
+    #[test]
+    fn unresolved_image_should_not_serialize() {
+        assert_eq!(
+            serde_json::to_string(&Image::base64(PNG_1X1)).unwrap(),
+            format!("\"{PNG_1X1}\"")
+        );
+        assert!(serde_json::to_string(&Image::bytes(vec![1, 2, 3])).is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_should_encode_bytes_data_uris_and_files() {
+        let cli = reqwest::Client::new();
+        let options = ImageOptions::default();
+        let png = STANDARD.decode(PNG_1X1).unwrap();
+
+        let resolved = Image::bytes(png.clone()).resolve(&cli, &options).await;
+        assert_eq!(resolved.unwrap(), PNG_1X1);
+
+        let uri = format!("data:image/png;base64,{PNG_1X1}");
+        let resolved = Image::from(uri).resolve(&cli, &options).await;
+        assert_eq!(resolved.unwrap(), PNG_1X1);
+
+        let path = std::env::temp_dir().join("ollama-native-resolve-image.png");
+        std::fs::write(&path, &png).unwrap();
+        let resolved = Image::path(&path).resolve(&cli, &options).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resolved.unwrap(), PNG_1X1);
+
+        let err = Image::bytes(b"not an image".to_vec())
+            .resolve(&cli, &options)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "image error: unsupported image format");
+    }
+
+    #[tokio::test]
+    async fn resolve_should_enforce_max_bytes() {
+        let cli = reqwest::Client::new();
+        let options = ImageOptions {
+            max_bytes: Some(16),
+            ..Default::default()
+        };
+        let err = Image::base64(PNG_1X1)
+            .resolve(&cli, &options)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeding the limit of 16 bytes"));
+
+        let path = std::env::temp_dir().join("ollama-native-max-bytes.png");
+        std::fs::write(&path, STANDARD.decode(PNG_1X1).unwrap()).unwrap();
+        let err = Image::path(&path).resolve(&cli, &options).await;
+        std::fs::remove_file(&path).unwrap();
+        let expected = format!(
+            "{} is 70 bytes, exceeding the limit of 16 bytes",
+            path.display()
+        );
+        assert!(err.unwrap_err().to_string().contains(&expected));
+    }
+
+    #[tokio::test]
+    async fn downloads_should_stop_at_the_limit() {
+        use axum::{Router, body::Body, routing::get};
+        use futures::{StreamExt, stream};
+
+        // An endless body, which only a limit enforced while streaming can stop.
+        let endless = || async {
+            let chunk = bytes::Bytes::from(vec![0u8; 1024]);
+            Body::from_stream(stream::repeat(chunk).map(Ok::<_, std::io::Error>))
+        };
+        let router = Router::new().route("/endless.png", get(endless));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/endless.png", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let options = ImageOptions {
+            max_bytes: Some(4096),
+            ..Default::default()
+        };
+        let err = Image::url(&url)
+            .resolve(&reqwest::Client::new(), &options)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("exceeds the download limit of 4096 bytes")
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn resolve_should_downscale_large_images() {
+        let mut png = std::io::Cursor::new(vec![]);
+        image::DynamicImage::new_rgb8(64, 32)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+
+        let options = ImageOptions {
+            max_dimension: Some(16),
+            ..Default::default()
+        };
+        let resolved = Image::bytes(png.into_inner())
+            .resolve(&reqwest::Client::new(), &options)
+            .await
+            .unwrap();
+
+        let decoded = image::load_from_memory(&STANDARD.decode(resolved).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 8));
+    }
+}
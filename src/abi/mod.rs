@@ -1,12 +1,19 @@
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
 pub mod completion;
+pub mod image;
+pub mod usage;
 pub mod version;
 
+pub use image::{Image, ImageOptions};
+pub use usage::Usage;
+
 #[cfg(feature = "model")]
 pub mod model;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     /// The role of the message, either `system`, `user`, `assistant`, or `tool`.
     pub role: Role,
@@ -14,9 +21,13 @@ pub struct Message {
     /// The content of the message.
     pub content: String,
 
+    /// The model's reasoning, for thinking models with `think` enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
+
     /// A list of images to include in the message (for multimodal models such as `llava`).
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub images: Option<Vec<String>>,
+    pub images: Option<Vec<Image>>,
 
     /// A list of tools in JSON that the model wants to use.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -38,6 +49,7 @@ impl Message {
         Self {
             role,
             content: content.to_string(),
+            thinking: None,
             images: None,
             tool_calls: None,
         }
@@ -58,24 +70,39 @@ impl Message {
         Self::new(Role::Assistant, content)
     }
 
+    /// The result of a tool call, sent back to the model.
     #[inline]
-    pub fn images(mut self, images: Vec<impl ToString>) -> Self {
+    pub fn tool(content: &str) -> Self {
+        Self::new(Role::Tool, content)
+    }
+
+    /// Attach images: base64 strings, `data:` URIs, `http(s)` URLs, paths or raw bytes.
+    #[inline]
+    pub fn images(mut self, images: Vec<impl Into<Image>>) -> Self {
         let mut cur_images = self.images.unwrap_or_default();
         images
             .into_iter()
-            .for_each(|img| cur_images.push(img.to_string()));
+            .for_each(|img| cur_images.push(img.into()));
         self.images = Some(cur_images);
         self
     }
 
+    /// Attach an image: a base64 string, a `data:` URI, an `http(s)` URL, a path or raw bytes.
     #[inline]
-    pub fn image(mut self, image: &str) -> Self {
+    pub fn image(mut self, image: impl Into<Image>) -> Self {
         let mut cur_images = self.images.unwrap_or_default();
-        cur_images.push(image.to_string());
+        cur_images.push(image.into());
         self.images = Some(cur_images);
         self
     }
 
+    /// The reasoning that led to this message, e.g. when replaying an assistant turn of a thinking model.
+    #[inline]
+    pub fn thinking(mut self, thinking: &str) -> Self {
+        self.thinking = Some(thinking.to_string());
+        self
+    }
+
     #[inline]
     pub fn tool_calls(mut self, tool_calls: Vec<serde_json::Value>) -> Self {
         let mut cur_tool_calls = self.tool_calls.unwrap_or_default();
@@ -95,7 +122,7 @@ impl Message {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Options {
     /// Enable Mirostat sampling for controlling perplexity.
     /// (default: 0, 0 = disabled, 1 = Mirostat, 2 = Mirostat 2.0).
@@ -245,9 +272,156 @@ impl Options {
     }
 }
 
+/// Controls how long a model stays loaded into memory following a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeepAlive {
+    /// Use the server default (5m unless `OLLAMA_KEEP_ALIVE` is set).
+    #[default]
+    Default,
+
+    /// Keep the model loaded for the given duration after the request.
+    Duration(Duration),
+
+    /// Keep the model loaded until the server is stopped.
+    Forever,
+
+    /// Unload the model immediately after the request.
+    UnloadNow,
+}
+
+impl KeepAlive {
+    pub fn is_default(&self) -> bool {
+        self == &Self::Default
+    }
+}
+
+impl From<Duration> for KeepAlive {
+    fn from(duration: Duration) -> Self {
+        if duration.is_zero() {
+            KeepAlive::UnloadNow
+        } else {
+            KeepAlive::Duration(duration)
+        }
+    }
+}
+
+/// Seconds, as the `keep_alive` parameter used to be given. Any negative value keeps the
+/// model loaded forever.
+impl From<i64> for KeepAlive {
+    fn from(secs: i64) -> Self {
+        match u64::try_from(secs) {
+            Ok(secs) => Duration::from_secs(secs).into(),
+            Err(_) => KeepAlive::Forever,
+        }
+    }
+}
+
+impl Serialize for KeepAlive {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            KeepAlive::Default => serializer.serialize_none(),
+            KeepAlive::Forever => serializer.serialize_i64(-1),
+            KeepAlive::UnloadNow => serializer.serialize_i64(0),
+            // Durations are sent as Go duration strings, which the server parses without loss.
+            KeepAlive::Duration(d) if d.subsec_nanos() == 0 => {
+                serializer.serialize_str(&format!("{}s", d.as_secs()))
+            }
+            KeepAlive::Duration(d) if d.subsec_nanos() % 1_000_000 == 0 => {
+                serializer.serialize_str(&format!("{}ms", d.as_millis()))
+            }
+            KeepAlive::Duration(d) => serializer.serialize_str(&format!("{}ns", d.as_nanos())),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeepAlive {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Numbers are seconds, strings are Go durations such as `10m` or `1h30m`.
+        // Any negative value keeps the model loaded forever.
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            None => Ok(KeepAlive::Default),
+            Some(serde_json::Value::Number(n)) => {
+                let secs = n
+                    .as_f64()
+                    .ok_or_else(|| de::Error::custom(format!("invalid keep_alive: {n}")))?;
+                KeepAlive::from_secs_f64(secs).map_err(de::Error::custom)
+            }
+            Some(serde_json::Value::String(s)) => match s.trim().parse::<f64>() {
+                Ok(secs) => KeepAlive::from_secs_f64(secs).map_err(de::Error::custom),
+                Err(_) => parse_go_duration(&s).map_err(de::Error::custom),
+            },
+            Some(other) => Err(de::Error::custom(format!("invalid keep_alive: {other}"))),
+        }
+    }
+}
+
+impl KeepAlive {
+    fn from_secs_f64(secs: f64) -> Result<Self, String> {
+        if secs < 0.0 {
+            Ok(KeepAlive::Forever)
+        } else {
+            Duration::try_from_secs_f64(secs)
+                .map(Into::into)
+                .map_err(|_| format!("invalid keep_alive: {secs}"))
+        }
+    }
+}
+
+/// Parses a Go duration string, e.g. `300ms`, `-1.5h` or `2h45m`.
+fn parse_go_duration(s: &str) -> Result<KeepAlive, String> {
+    let invalid = || format!("invalid keep_alive duration: {s:?}");
+
+    let s = s.trim();
+    let (negative, mut rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut nanos = 0f64;
+    while !rest.is_empty() {
+        let value_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(invalid)?;
+        let value: f64 = rest[..value_len].parse().map_err(|_| invalid())?;
+        rest = &rest[value_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ns" => 1.0,
+            "us" | "µs" => 1e3,
+            "ms" => 1e6,
+            "s" => 1e9,
+            "m" => 60.0 * 1e9,
+            "h" => 3600.0 * 1e9,
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+        nanos += value * scale;
+    }
+
+    if negative && nanos > 0.0 {
+        Ok(KeepAlive::Forever)
+    } else {
+        Ok(Duration::from_nanos(nanos as u64).into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Options;
+    use std::time::Duration;
+
+    use super::{KeepAlive, Options};
 
     #[test]
     fn is_default_should_work() {
@@ -256,4 +430,49 @@ mod tests {
         p.mirostat = Some(1);
         assert!(!p.is_default());
     }
+
+    #[test]
+    fn keep_alive_should_serialize() {
+        let cases = [
+            (KeepAlive::Forever, "-1"),
+            (KeepAlive::UnloadNow, "0"),
+            (Duration::from_secs(600).into(), r#""600s""#),
+            (Duration::from_millis(1500).into(), r#""1500ms""#),
+            (Duration::from_secs(0).into(), "0"),
+            (300.into(), r#""300s""#),
+            (0.into(), "0"),
+            ((-1).into(), "-1"),
+        ];
+        for (keep_alive, expected) in cases {
+            assert_eq!(serde_json::to_string(&keep_alive).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn keep_alive_should_deserialize() {
+        let cases = [
+            ("-1", KeepAlive::Forever),
+            (r#""-1""#, KeepAlive::Forever),
+            (r#""-1m""#, KeepAlive::Forever),
+            ("0", KeepAlive::UnloadNow),
+            (r#""0""#, KeepAlive::UnloadNow),
+            ("300", KeepAlive::Duration(Duration::from_secs(300))),
+            (r#""10m""#, KeepAlive::Duration(Duration::from_secs(600))),
+            (r#""1h30m""#, KeepAlive::Duration(Duration::from_secs(5400))),
+            (
+                r#""1.5s""#,
+                KeepAlive::Duration(Duration::from_millis(1500)),
+            ),
+            ("null", KeepAlive::Default),
+        ];
+        for (json, expected) in cases {
+            assert_eq!(serde_json::from_str::<KeepAlive>(json).unwrap(), expected);
+        }
+
+        assert!(serde_json::from_str::<KeepAlive>(r#""10 minutes""#).is_err());
+        assert!(serde_json::from_str::<KeepAlive>(r#""inf""#).is_err());
+        assert!(serde_json::from_str::<KeepAlive>(r#""NaN""#).is_err());
+        assert!(serde_json::from_str::<KeepAlive>("1e300").is_err());
+        assert!(serde_json::from_str::<KeepAlive>(r#""m""#).is_err());
+    }
 }
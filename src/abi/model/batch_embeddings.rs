@@ -0,0 +1,38 @@
+use serde::Serialize;
+
+/// A single embedding produced by a batch embedding job.
+#[cfg(feature = "model")]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchEmbedding {
+    /// Position of the input this embedding was generated for.
+    pub index: usize,
+    pub embedding: Vec<f64>,
+    /// Progress of the job once this embedding has been yielded.
+    pub progress: BatchProgress,
+}
+
+/// Progress of a batch embedding job.
+#[cfg(feature = "model")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BatchProgress {
+    /// Number of embeddings yielded so far.
+    pub completed: usize,
+
+    /// Number of batches whose embeddings have all been yielded.
+    pub batches_completed: usize,
+
+    /// Total number of inputs, `None` if they come from a stream of unknown length.
+    pub total: Option<usize>,
+}
+
+#[cfg(feature = "model")]
+impl BatchProgress {
+    /// Fraction of the inputs embedded so far, between 0.0 and 1.0.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some(self.completed as f64 / total as f64),
+            None => None,
+        }
+    }
+}
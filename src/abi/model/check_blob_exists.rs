@@ -10,6 +10,6 @@ pub struct CheckBlobExistsRequest<'a> {
 
 impl<'a> OllamaRequest for CheckBlobExistsRequest<'a> {
     fn path(&self) -> String {
-        format!("/api/blobs/{}", self.digest.to_string())
+        format!("/api/blobs/{}", self.digest)
     }
 }
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{abi::Options, action::OllamaRequest};
+use crate::{
+    abi::{KeepAlive, Options, Usage},
+    action::OllamaRequest,
+};
 
 #[cfg(feature = "model")]
 #[derive(Debug, Clone, Default, Serialize)]
@@ -24,8 +27,8 @@ pub struct GenerateEmbeddingsRequest<'a> {
 
     /// Controls how long the model will stay loaded into memory following the request
     /// (default: 5m).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub keep_alive: Option<i64>,
+    #[serde(skip_serializing_if = "KeepAlive::is_default")]
+    pub keep_alive: KeepAlive,
 }
 
 #[cfg(feature = "model")]
@@ -33,9 +36,11 @@ pub struct GenerateEmbeddingsRequest<'a> {
 pub struct GenerateEmbeddingsResponse {
     pub model: String,
     pub embeddings: Vec<Vec<f64>>,
-    pub total_duration: Option<i64>,
-    pub load_duration: Option<i64>,
-    pub prompt_eval_count: Option<i64>,
+
+    /// Token counts and timings, only `total_duration`, `load_duration` and
+    /// `prompt_eval_count` are reported for embeddings.
+    #[serde(flatten)]
+    pub usage: Usage,
 }
 
 impl<'a> OllamaRequest for GenerateEmbeddingsRequest<'a> {
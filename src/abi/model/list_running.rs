@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::action::OllamaRequest;
@@ -15,14 +16,17 @@ pub struct ListRunningModelsResponse {
 }
 
 #[cfg(feature = "model")]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListRunningModelsInfo {
     pub name: String,
     pub model: String,
     pub size: i64,
     pub digest: String,
     pub details: ModelInfoDetail,
-    pub expires_at: String,
+    /// The time at which the model will be unloaded from memory.
+    pub expires_at: DateTime<Utc>,
+
+    /// Size of the model loaded into VRAM, in bytes.
     pub size_vram: i64,
 }
 
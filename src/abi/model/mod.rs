@@ -1,5 +1,6 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+pub mod batch_embeddings;
 pub mod check_blob_exists;
 pub mod copy;
 pub mod create;
@@ -7,13 +8,15 @@ pub mod delete;
 pub mod generate_embeddings;
 pub mod list_local;
 pub mod list_running;
+pub mod name;
 pub mod pull;
 pub mod push;
 pub mod push_blob;
 pub mod show_info;
+pub mod watch_running;
 
 #[cfg(feature = "model")]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfoDetail {
     pub format: String,
     pub family: String,
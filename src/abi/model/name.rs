@@ -0,0 +1,146 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::OllamaError;
+
+pub const DEFAULT_HOST: &str = "registry.ollama.ai";
+pub const DEFAULT_NAMESPACE: &str = "library";
+pub const DEFAULT_TAG: &str = "latest";
+
+/// A fully qualified model name, `host/namespace/model:tag`, with the parts left out of
+/// a short name such as `llama3.1` filled with their defaults.
+#[cfg(feature = "model")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModelName {
+    pub host: String,
+    pub namespace: String,
+    pub model: String,
+    pub tag: String,
+}
+
+#[cfg(feature = "model")]
+impl ModelName {
+    pub fn new(host: &str, namespace: &str, model: &str, tag: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            namespace: namespace.to_string(),
+            model: model.to_string(),
+            tag: tag.to_string(),
+        }
+    }
+
+    /// The shortest name the model can be referred to by, as listed by `/api/tags`,
+    /// e.g. `llama3.1:8b` or `jmorgan/mixtral:latest`.
+    pub fn shortest(&self) -> String {
+        match (
+            self.host == DEFAULT_HOST,
+            self.namespace == DEFAULT_NAMESPACE,
+        ) {
+            (true, true) => format!("{}:{}", self.model, self.tag),
+            (true, false) => format!("{}/{}:{}", self.namespace, self.model, self.tag),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// The name `/api/tags` and `/api/ps` list a model by, e.g. `llama3.1:latest` for
+/// `llama3.1`. Names that do not parse are kept as they are.
+#[cfg(any(feature = "pool", feature = "scheduler"))]
+pub(crate) fn normalize(name: &str) -> String {
+    name.parse::<ModelName>()
+        .map_or_else(|_| name.to_string(), |name| name.shortest())
+}
+
+#[cfg(feature = "model")]
+impl FromStr for ModelName {
+    type Err = OllamaError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let invalid = || OllamaError::InvalidFormat(format!("invalid model name: {name:?}"));
+
+        // Pinned names such as `model@sha256:...` are looked up by name.
+        let name = name.split_once('@').map_or(name, |(name, _)| name);
+        let (path, tag) = match name.rsplit_once(':') {
+            Some((path, tag)) if !tag.contains('/') => (path, tag),
+            _ => (name, DEFAULT_TAG),
+        };
+
+        let parts: Vec<&str> = path.split('/').collect();
+        let (host, namespace, model) = match parts.as_slice() {
+            [model] => (DEFAULT_HOST, DEFAULT_NAMESPACE, *model),
+            [namespace, model] => (DEFAULT_HOST, *namespace, *model),
+            [host, namespace, model] => (*host, *namespace, *model),
+            _ => return Err(invalid()),
+        };
+        // Each part is a directory in the model store, so none may leave it.
+        if [host, namespace, model, tag]
+            .iter()
+            .any(|p| p.is_empty() || *p == "." || *p == ".." || p.contains('\\'))
+        {
+            return Err(invalid());
+        }
+        Ok(Self::new(host, namespace, model, tag))
+    }
+}
+
+#[cfg(feature = "model")]
+impl fmt::Display for ModelName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}:{}",
+            self.host, self.namespace, self.model, self.tag
+        )
+    }
+}
+
+#[cfg(feature = "model")]
+impl Serialize for ModelName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.shortest())
+    }
+}
+
+#[cfg(feature = "model")]
+impl<'de> Deserialize<'de> for ModelName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelName;
+
+    #[test]
+    fn model_name_should_fill_defaults() {
+        let name: ModelName = "llama3.1".parse().unwrap();
+        assert_eq!(
+            name.to_string(),
+            "registry.ollama.ai/library/llama3.1:latest"
+        );
+        assert_eq!(name.shortest(), "llama3.1:latest");
+
+        let name: ModelName = "jmorgan/mixtral:8x7b".parse().unwrap();
+        assert_eq!(name.namespace, "jmorgan");
+        assert_eq!(name.shortest(), "jmorgan/mixtral:8x7b");
+
+        let name: ModelName = "localhost:5000/me/model:v1".parse().unwrap();
+        assert_eq!(name.host, "localhost:5000");
+        assert_eq!(name.tag, "v1");
+        assert_eq!(name.shortest(), "localhost:5000/me/model:v1");
+
+        let name: ModelName = "localhost:5000/me/model".parse().unwrap();
+        assert_eq!(name.tag, "latest");
+
+        assert!("a/b/c/d".parse::<ModelName>().is_err());
+        assert!("model:".parse::<ModelName>().is_err());
+        assert!("../../model".parse::<ModelName>().is_err());
+        assert!("model:..".parse::<ModelName>().is_err());
+        assert!("./model".parse::<ModelName>().is_err());
+        assert!("..\\..\\model".parse::<ModelName>().is_err());
+    }
+}
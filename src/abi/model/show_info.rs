@@ -28,6 +28,28 @@ pub struct ShowModelInformationResponse {
     pub model_info: HashMap<String, serde_json::Value>,
 }
 
+#[cfg(feature = "model")]
+impl ShowModelInformationResponse {
+    /// The architecture of the model, e.g. `llama`, read from `general.architecture`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.model_info.get("general.architecture")?.as_str()
+    }
+
+    /// The maximum context length the model was trained with, in tokens, read from
+    /// `<architecture>.context_length`.
+    pub fn context_length(&self) -> Option<u64> {
+        let key = format!("{}.context_length", self.architecture()?);
+        self.model_info.get(&key)?.as_u64()
+    }
+
+    /// The number of dimensions of the embeddings produced by the model, read from
+    /// `<architecture>.embedding_length`.
+    pub fn embedding_length(&self) -> Option<u64> {
+        let key = format!("{}.embedding_length", self.architecture()?);
+        self.model_info.get(&key)?.as_u64()
+    }
+}
+
 impl<'a> OllamaRequest for ShowModelInformationRequest<'a> {
     fn path(&self) -> String {
         "/api/show".to_string()
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::list_running::ListRunningModelsInfo;
+
+/// An event emitted by the running model watcher when the output of `/api/ps` changes
+/// between two polls.
+#[cfg(feature = "model")]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunningModelEvent {
+    /// A model has been loaded into memory.
+    Loaded { model: ListRunningModelsInfo },
+
+    /// A model has been unloaded from memory.
+    Unloaded { model: ListRunningModelsInfo },
+
+    /// The VRAM footprint of a loaded model has changed.
+    VramChanged {
+        model: ListRunningModelsInfo,
+        previous_size_vram: i64,
+    },
+
+    /// A loaded model will be unloaded within the configured expiry threshold.
+    /// Emitted once per `expires_at`, so refreshing the keep alive re-arms it.
+    ExpiringSoon {
+        model: ListRunningModelsInfo,
+        remaining: Duration,
+    },
+}
+
+impl RunningModelEvent {
+    /// The model this event is about.
+    pub fn model(&self) -> &ListRunningModelsInfo {
+        match self {
+            RunningModelEvent::Loaded { model }
+            | RunningModelEvent::Unloaded { model }
+            | RunningModelEvent::VramChanged { model, .. }
+            | RunningModelEvent::ExpiringSoon { model, .. } => model,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Token counts and timings reported by the server once a request is done.
+/// Every field is `None` on intermediate streaming chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Time spent generating the response.
+    #[serde(default, with = "nanos", skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<Duration>,
+
+    /// Time spent loading the model.
+    #[serde(default, with = "nanos", skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<Duration>,
+
+    /// Number of tokens in the prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<i64>,
+
+    /// Time spent evaluating the prompt.
+    #[serde(default, with = "nanos", skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_duration: Option<Duration>,
+
+    /// Number of tokens in the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<i64>,
+
+    /// Time spent generating the response tokens.
+    #[serde(default, with = "nanos", skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<Duration>,
+}
+
+impl Usage {
+    /// Prompt evaluation throughput in tokens per second.
+    pub fn prompt_tokens_per_second(&self) -> Option<f64> {
+        tokens_per_second(self.prompt_eval_count?, self.prompt_eval_duration?)
+    }
+
+    /// Generation throughput in tokens per second.
+    pub fn generation_tokens_per_second(&self) -> Option<f64> {
+        tokens_per_second(self.eval_count?, self.eval_duration?)
+    }
+
+    /// Time until the first response token was produced, as measured by the server:
+    /// loading the model plus evaluating the prompt.
+    pub fn time_to_first_token(&self) -> Option<Duration> {
+        let prompt_eval = self.prompt_eval_duration?;
+        Some(self.load_duration.unwrap_or_default() + prompt_eval)
+    }
+
+    /// Number of prompt and response tokens.
+    pub fn total_tokens(&self) -> Option<i64> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, eval) => Some(prompt.unwrap_or_default() + eval.unwrap_or_default()),
+        }
+    }
+}
+
+fn tokens_per_second(count: i64, duration: Duration) -> Option<f64> {
+    if duration.is_zero() {
+        return None;
+    }
+    Some(count as f64 / duration.as_secs_f64())
+}
+
+/// (De)serializes an optional [`Duration`] as integer nanoseconds.
+mod nanos {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(d) => serializer.serialize_u64(d.as_nanos() as u64),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let nanos = Option::<i64>::deserialize(deserializer)?;
+        Ok(nanos.map(|n| Duration::from_nanos(n.max(0) as u64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{DateTime, Utc};
+
+    use super::Usage;
+    use crate::abi::completion::ChatCompletionResponse;
+
+    #[test]
+    fn usage_should_convert_units() {
+        let usage: Usage = serde_json::from_str(
+            r#"{
+                "total_duration": 4883583458,
+                "load_duration": 1334875,
+                "prompt_eval_count": 26,
+                "prompt_eval_duration": 342546000,
+                "eval_count": 282,
+                "eval_duration": 4535599000
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(usage.total_duration, Some(Duration::from_nanos(4883583458)));
+        assert_eq!(
+            usage.time_to_first_token(),
+            Some(Duration::from_nanos(1334875 + 342546000))
+        );
+        assert_eq!(usage.total_tokens(), Some(308));
+
+        let prompt_tps = usage.prompt_tokens_per_second().unwrap();
+        assert!((prompt_tps - 26.0 / 0.342546).abs() < 1e-6);
+        let generation_tps = usage.generation_tokens_per_second().unwrap();
+        assert!((generation_tps - 282.0 / 4.535599).abs() < 1e-6);
+
+        let serialized = serde_json::to_value(usage).unwrap();
+        assert_eq!(serialized["eval_duration"], 4535599000u64);
+    }
+
+    #[test]
+    fn usage_should_be_empty_for_streaming_chunks() {
+        let usage: Usage = serde_json::from_str("{}").unwrap();
+        assert_eq!(usage, Usage::default());
+        assert_eq!(usage.generation_tokens_per_second(), None);
+        assert_eq!(usage.time_to_first_token(), None);
+        assert_eq!(usage.total_tokens(), None);
+        assert_eq!(serde_json::to_string(&usage).unwrap(), "{}");
+    }
+
+    #[test]
+    fn chat_completion_response_should_parse_usage_and_created_at() {
+        let resp: ChatCompletionResponse = serde_json::from_str(
+            r#"{
+                "model": "llama3.2",
+                "created_at": "2023-12-12T14:13:43.416799Z",
+                "message": {"role": "assistant", "content": "Hello! How are you today?"},
+                "done": true,
+                "total_duration": 5191566416,
+                "load_duration": 2154458,
+                "prompt_eval_count": 26,
+                "prompt_eval_duration": 383809000,
+                "eval_count": 298,
+                "eval_duration": 4799921000
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            resp.created_at,
+            "2023-12-12T14:13:43.416799Z"
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        );
+        assert_eq!(resp.usage.eval_count, Some(298));
+        assert_eq!(
+            resp.usage.eval_duration,
+            Some(Duration::from_nanos(4799921000))
+        );
+    }
+}
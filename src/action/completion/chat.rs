@@ -6,8 +6,9 @@ use reqwest::{
     header::{CONTENT_TYPE, HeaderMap, HeaderValue},
 };
 
+use crate::abi::image::resolve_images;
 use crate::abi::{
-    Message,
+    ImageOptions, KeepAlive, Message, Options,
     completion::chat::{
         ChatCompletionModelResponse, ChatCompletionRequest, ChatCompletionResponse, Format, Tool,
     },
@@ -18,15 +19,34 @@ use crate::{action::OllamaClient, error::OllamaServerError};
 
 #[cfg(feature = "stream")]
 use {
-    crate::action::{IntoStream, OllamaStream},
-    async_stream::stream,
+    crate::action::{IntoStream, OllamaStream, decode_ndjson},
     async_trait::async_trait,
-    tokio_stream::StreamExt,
 };
 
+#[cfg(feature = "cache")]
+use {
+    crate::action::model::list_local::resolve_digest,
+    crate::cache::{ResponseCache, ResponseKey, cached},
+    std::sync::Arc,
+};
+
+#[cfg(feature = "cache")]
+#[cfg(feature = "stream")]
+use crate::cache::cached_stream;
+
+#[cfg(feature = "scheduler")]
+use crate::scheduler::Priority;
+
 pub struct ChatAction<'a, R> {
     request: ChatCompletionRequest<'a>,
     ollama: OllamaClient,
+    image_options: ImageOptions,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<dyn ResponseCache>>,
+    #[cfg(feature = "cache")]
+    model_digest: Option<&'a str>,
+    #[cfg(feature = "scheduler")]
+    priority: Priority,
     _resp: PhantomData<R>,
 }
 
@@ -35,6 +55,13 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
         Self {
             ollama,
             request: ChatCompletionRequest::new(model),
+            image_options: ImageOptions::default(),
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "cache")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: Priority::default(),
             _resp: PhantomData::<ChatCompletionResponse>,
         }
     }
@@ -47,6 +74,13 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
         ChatAction {
             ollama: self.ollama,
             request: self.request.to_load_model(),
+            image_options: ImageOptions::default(),
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "cache")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: self.priority,
             _resp: PhantomData::<ChatCompletionModelResponse>,
         }
     }
@@ -57,6 +91,13 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
         ChatAction {
             ollama: self.ollama,
             request: self.request.to_unload_model(),
+            image_options: ImageOptions::default(),
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "cache")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: self.priority,
             _resp: PhantomData::<ChatCompletionModelResponse>,
         }
     }
@@ -93,6 +134,21 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
         self
     }
 
+    /// Reject message images larger than `max_bytes` after downscaling.
+    #[inline]
+    pub fn max_image_bytes(mut self, max_bytes: usize) -> Self {
+        self.image_options.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Downscale message images whose width or height exceeds `max_dimension` pixels.
+    #[cfg(feature = "image")]
+    #[inline]
+    pub fn max_image_dimension(mut self, max_dimension: u32) -> Self {
+        self.image_options.max_dimension = Some(max_dimension);
+        self
+    }
+
     /// Tool in JSON for the model to use if supported.
     #[inline]
     pub fn tool(mut self, tool: &'a str) -> Self {
@@ -103,7 +159,7 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
     /// List of tools in JSON for the model to use if supported.
     #[inline]
     pub fn tools(mut self, tools: Vec<&'a str>) -> Self {
-        self.request.tools = tools.into_iter().map(|t| Tool::new(t)).collect();
+        self.request.tools = tools.into_iter().map(Tool::new).collect();
         self
     }
 
@@ -118,10 +174,54 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
         self
     }
 
+    /// Let thinking models return their reasoning separately from the answer.
+    /// Set to `false` to disable thinking on models that think by default.
+    #[inline]
+    pub fn think(mut self, think: bool) -> Self {
+        self.request.think = Some(think);
+        self
+    }
+
+    /// Serve the response from the cache if an identical request was answered before,
+    /// storing it otherwise. Entries are keyed by model digest and request, the digest being
+    /// looked up with `/api/tags` unless set with `model_digest`. See [`crate::cache`].
+    #[cfg(feature = "cache")]
+    #[inline]
+    pub fn cache(mut self, cache: Arc<dyn ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The digest of the model used for cache keys, skipping the `/api/tags` lookup.
+    #[cfg(feature = "cache")]
+    #[inline]
+    pub fn model_digest(mut self, model_digest: &'a str) -> Self {
+        self.model_digest = Some(model_digest);
+        self
+    }
+
+    /// The priority of the request in the scheduler of the client, if any
+    /// (default: `Priority::Interactive`).
+    #[cfg(feature = "scheduler")]
+    #[inline]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     /// Controls how long the model will stay loaded into memory following the request (default: 5m).
+    /// Accepts seconds as an `i64` (negative keeps the model loaded forever), a
+    /// [`Duration`][`std::time::Duration`] or a [`KeepAlive`] such as `KeepAlive::Forever`.
     #[inline]
-    pub fn keep_alive(mut self, keep_alive: i64) -> Self {
-        self.request.keep_alive = Some(keep_alive);
+    pub fn keep_alive(mut self, keep_alive: impl Into<KeepAlive>) -> Self {
+        self.request.keep_alive = keep_alive.into();
+        self
+    }
+
+    /// Additional model parameters, replacing any set so far.
+    #[inline]
+    pub fn options(mut self, options: Options) -> Self {
+        self.request.options = options;
         self
     }
 
@@ -241,28 +341,100 @@ impl<'a> ChatAction<'a, ChatCompletionResponse> {
     }
 }
 
+impl<'a, R> ChatAction<'a, R> {
+    async fn resolve_images(&mut self) -> Result<(), OllamaError> {
+        for message in self.request.messages.iter_mut() {
+            if let Some(images) = message.images.as_mut() {
+                resolve_images(&self.ollama.cli, images, &self.image_options).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ChatAction<'a, ChatCompletionResponse> {
+    async fn send(&self) -> Result<ChatCompletionResponse, OllamaError> {
+        #[cfg(feature = "scheduler")]
+        let _permit = self
+            .ollama
+            .acquire(self.request.model, self.priority)
+            .await?;
+
+        #[cfg(feature = "openai")]
+        if crate::openai::enabled(&self.ollama) {
+            return crate::openai::chat(&self.ollama, &self.request).await;
+        }
+
+        let headers = if self.request.format.is_some() {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            Some(headers)
+        } else {
+            None
+        };
+
+        let reqwest_resp = self.ollama.post(&self.request, headers).await?;
+        match reqwest_resp.status() {
+            StatusCode::OK => parse_response(reqwest_resp).await,
+            _code => {
+                let error: OllamaServerError = parse_response(reqwest_resp).await?;
+                Err(OllamaError::OllamaServerError(error.error))
+            }
+        }
+    }
+
+    #[cfg(feature = "stream")]
+    async fn send_stream(&self) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError> {
+        #[cfg(feature = "scheduler")]
+        let permit = self
+            .ollama
+            .acquire(self.request.model, self.priority)
+            .await?;
+
+        let stream = self.open_stream().await?;
+
+        #[cfg(feature = "scheduler")]
+        let stream = permit.hold(stream);
+        Ok(stream)
+    }
+
+    #[cfg(feature = "stream")]
+    async fn open_stream(&self) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError> {
+        #[cfg(feature = "openai")]
+        if crate::openai::enabled(&self.ollama) {
+            return crate::openai::chat_stream(&self.ollama, &self.request).await;
+        }
+
+        let reqwest_resp = self.ollama.post(&self.request, None).await?;
+        let s = decode_ndjson(reqwest_resp);
+        Ok(Box::pin(s))
+    }
+
+    #[cfg(feature = "cache")]
+    async fn cache_key(&self) -> Result<ResponseKey, OllamaError> {
+        let digest = match self.model_digest {
+            Some(digest) => digest.to_string(),
+            None => resolve_digest(&self.ollama, self.request.model).await?,
+        };
+        ResponseKey::chat(&digest, &self.request)
+    }
+}
+
 impl<'a> IntoFuture for ChatAction<'a, ChatCompletionResponse> {
     type Output = Result<ChatCompletionResponse, OllamaError>;
     type IntoFuture = BoxFuture<'a, Self::Output>;
 
-    fn into_future(self) -> Self::IntoFuture {
+    fn into_future(mut self) -> Self::IntoFuture {
         Box::pin(async move {
-            let headers = if let Some(_) = self.request.format {
-                let mut headers = HeaderMap::new();
-                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-                Some(headers)
-            } else {
-                None
-            };
-
-            let reqwest_resp = self.ollama.post(&self.request, headers).await?;
-            match reqwest_resp.status() {
-                StatusCode::OK => parse_response(reqwest_resp).await,
-                _code => {
-                    let error: OllamaServerError = parse_response(reqwest_resp).await?;
-                    Err(OllamaError::OllamaServerError(error.error))
-                }
+            self.resolve_images().await?;
+
+            #[cfg(feature = "cache")]
+            if let Some(cache) = &self.cache {
+                let key = self.cache_key().await?;
+                return cached(cache.as_ref(), &key, self.send()).await;
             }
+
+            self.send().await
         })
     }
 }
@@ -290,20 +462,14 @@ impl<'a> IntoFuture for ChatAction<'a, ChatCompletionModelResponse> {
 impl<'a> IntoStream<ChatCompletionResponse> for ChatAction<'a, ChatCompletionResponse> {
     async fn stream(mut self) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError> {
         self.request.stream = true;
+        self.resolve_images().await?;
 
-        let mut reqwest_stream = self.ollama.post(&self.request, None).await?.bytes_stream();
+        #[cfg(feature = "cache")]
+        if let Some(cache) = self.cache.clone() {
+            let key = self.cache_key().await?;
+            return cached_stream(cache, key, self.send_stream()).await;
+        }
 
-        let s = stream! {
-            while let Some(item) = reqwest_stream.next().await {
-                match item {
-                    Ok(chunk) => match serde_json::from_slice(&chunk) {
-                        Ok(r) => yield Ok(r),
-                        Err(e) => yield Err(OllamaError::StreamDecodingError(e.to_string())),
-                    }
-                    Err(e) => yield Err(OllamaError::DecodingError(e))
-                }
-            };
-        };
-        Ok(Box::pin(s))
+        self.send_stream().await
     }
 }
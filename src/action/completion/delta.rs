@@ -0,0 +1,175 @@
+use async_stream::stream;
+use tokio_stream::StreamExt;
+
+use crate::{
+    abi::{
+        Usage,
+        completion::{ChatCompletionResponse, GenerateCompletionResponse},
+    },
+    action::OllamaStream,
+};
+
+/// A piece of a streamed chat or generate response, with the reasoning of thinking
+/// models separated from the answer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delta {
+    /// A chunk of the model's reasoning.
+    Thinking(String),
+
+    /// A chunk of the answer.
+    Content(String),
+
+    /// Tool calls requested by the model.
+    ToolCalls(Vec<serde_json::Value>),
+
+    /// The final chunk of the stream.
+    Done {
+        done_reason: Option<String>,
+        usage: Usage,
+    },
+}
+
+/// Converts a response stream into a stream of [`Delta`]s, skipping empty chunks.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::action::{IntoStream, completion::delta::{Delta, DeltaStream}};
+///
+/// let mut deltas = ollama
+///     .chat("qwen3")
+///     .user_message("How many r's are in strawberry?")
+///     .think(true)
+///     .stream()
+///     .await?
+///     .deltas();
+///
+/// while let Some(delta) = deltas.next().await {
+///     match delta? {
+///         Delta::Thinking(thinking) => eprint!("{thinking}"),
+///         Delta::Content(content) => print!("{content}"),
+///         _ => {}
+///     }
+/// }
+/// ```
+pub trait DeltaStream {
+    fn deltas(self) -> OllamaStream<Delta>;
+}
+
+impl<R: IntoDeltas + Send + 'static> DeltaStream for OllamaStream<R> {
+    fn deltas(mut self) -> OllamaStream<Delta> {
+        let s = stream! {
+            while let Some(item) = self.next().await {
+                match item {
+                    Ok(resp) => for delta in resp.into_deltas() {
+                        yield Ok(delta);
+                    },
+                    Err(e) => yield Err(e),
+                }
+            }
+        };
+        Box::pin(s)
+    }
+}
+
+/// Splits a single streamed response into its deltas.
+pub trait IntoDeltas {
+    fn into_deltas(self) -> Vec<Delta>;
+}
+
+impl IntoDeltas for ChatCompletionResponse {
+    fn into_deltas(self) -> Vec<Delta> {
+        let mut deltas = vec![];
+        if let Some(message) = self.message {
+            push_text(&mut deltas, message.thinking, Delta::Thinking);
+            push_text(&mut deltas, Some(message.content), Delta::Content);
+            if let Some(tool_calls) = message.tool_calls.filter(|tc| !tc.is_empty()) {
+                deltas.push(Delta::ToolCalls(tool_calls));
+            }
+        }
+        if self.done {
+            deltas.push(Delta::Done {
+                done_reason: self.done_reason,
+                usage: self.usage,
+            });
+        }
+        deltas
+    }
+}
+
+impl IntoDeltas for GenerateCompletionResponse {
+    fn into_deltas(self) -> Vec<Delta> {
+        let mut deltas = vec![];
+        push_text(&mut deltas, self.thinking, Delta::Thinking);
+        push_text(&mut deltas, Some(self.response), Delta::Content);
+        if self.done {
+            deltas.push(Delta::Done {
+                done_reason: self.done_reason,
+                usage: self.usage,
+            });
+        }
+        deltas
+    }
+}
+
+fn push_text(deltas: &mut Vec<Delta>, text: Option<String>, delta: fn(String) -> Delta) {
+    if let Some(text) = text.filter(|t| !t.is_empty()) {
+        deltas.push(delta(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::{Delta, DeltaStream};
+    use crate::abi::completion::{ChatCompletionResponse, GenerateCompletionResponse};
+    use crate::action::OllamaStream;
+
+    fn chunks<R: serde::de::DeserializeOwned + Send + 'static>(chunks: &[&str]) -> OllamaStream<R> {
+        let parsed: Vec<_> = chunks
+            .iter()
+            .map(|c| Ok(serde_json::from_str::<R>(c).unwrap()))
+            .collect();
+        Box::pin(tokio_stream::iter(parsed))
+    }
+
+    #[tokio::test]
+    async fn chat_deltas_should_separate_thinking() {
+        let stream: OllamaStream<ChatCompletionResponse> = chunks(&[
+            r#"{"model":"qwen3","created_at":"2025-05-29T09:35:56.836222Z","message":{"role":"assistant","content":"","thinking":"Let me count"},"done":false}"#,
+            r#"{"model":"qwen3","created_at":"2025-05-29T09:35:56.836222Z","message":{"role":"assistant","content":"","thinking":" the letters."},"done":false}"#,
+            r#"{"model":"qwen3","created_at":"2025-05-29T09:35:56.836222Z","message":{"role":"assistant","content":"There are 3."},"done":false}"#,
+            r#"{"model":"qwen3","created_at":"2025-05-29T09:35:56.836222Z","message":{"role":"assistant","content":""},"done_reason":"stop","done":true,"eval_count":12,"eval_duration":1000000}"#,
+        ]);
+
+        let deltas: Vec<Delta> = stream.deltas().map(|d| d.unwrap()).collect().await;
+        assert_eq!(deltas[0], Delta::Thinking("Let me count".to_string()));
+        assert_eq!(deltas[1], Delta::Thinking(" the letters.".to_string()));
+        assert_eq!(deltas[2], Delta::Content("There are 3.".to_string()));
+        match &deltas[3] {
+            Delta::Done { done_reason, usage } => {
+                assert_eq!(done_reason.as_deref(), Some("stop"));
+                assert_eq!(usage.eval_count, Some(12));
+            }
+            other => panic!("unexpected delta: {other:?}"),
+        }
+        assert_eq!(deltas.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn generate_deltas_should_separate_thinking() {
+        let stream: OllamaStream<GenerateCompletionResponse> = chunks(&[
+            r#"{"model":"qwen3","created_at":"2025-05-29T09:35:56.836222Z","response":"","thinking":"Hmm.","done":false}"#,
+            r#"{"model":"qwen3","created_at":"2025-05-29T09:35:56.836222Z","response":"Blue.","done":false}"#,
+        ]);
+
+        let deltas: Vec<Delta> = stream.deltas().map(|d| d.unwrap()).collect().await;
+        assert_eq!(
+            deltas,
+            vec![
+                Delta::Thinking("Hmm.".to_string()),
+                Delta::Content("Blue.".to_string())
+            ]
+        );
+    }
+}
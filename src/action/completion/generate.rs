@@ -8,12 +8,24 @@ use reqwest::{
 
 #[cfg(feature = "stream")]
 use {
-    crate::action::{IntoStream, OllamaStream},
-    async_stream::stream,
+    crate::action::{IntoStream, OllamaStream, decode_ndjson},
     async_trait::async_trait,
-    tokio_stream::StreamExt,
 };
 
+#[cfg(feature = "cache")]
+use {
+    crate::action::model::list_local::resolve_digest,
+    crate::cache::{ResponseCache, ResponseKey, cached},
+    std::sync::Arc,
+};
+
+#[cfg(feature = "cache")]
+#[cfg(feature = "stream")]
+use crate::cache::cached_stream;
+
+#[cfg(feature = "scheduler")]
+use crate::scheduler::Priority;
+
 use crate::{
     abi::completion::{
         chat::Format,
@@ -21,6 +33,7 @@ use crate::{
             GenerateCompletionModelResponse, GenerateCompletionRequest, GenerateCompletionResponse,
         },
     },
+    abi::{Image, ImageOptions, KeepAlive, image::resolve_images},
     action::{OllamaClient, parse_response},
     error::{OllamaError, OllamaServerError},
 };
@@ -28,6 +41,13 @@ use crate::{
 pub struct GenerateAction<'a, R> {
     ollama: OllamaClient,
     request: GenerateCompletionRequest<'a>,
+    image_options: ImageOptions,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<dyn ResponseCache>>,
+    #[cfg(feature = "cache")]
+    model_digest: Option<&'a str>,
+    #[cfg(feature = "scheduler")]
+    priority: Priority,
     _resp: PhantomData<R>,
 }
 
@@ -36,6 +56,13 @@ impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
         Self {
             ollama,
             request: GenerateCompletionRequest::new(model),
+            image_options: ImageOptions::default(),
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "cache")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: Priority::default(),
             _resp: PhantomData::<GenerateCompletionResponse>,
         }
     }
@@ -55,6 +82,13 @@ impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
         GenerateAction {
             ollama: self.ollama,
             request: self.request.to_load_model(),
+            image_options: ImageOptions::default(),
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "cache")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: self.priority,
             _resp: PhantomData::<GenerateCompletionModelResponse>,
         }
     }
@@ -65,6 +99,13 @@ impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
         GenerateAction {
             ollama: self.ollama,
             request: self.request.to_unload_model(),
+            image_options: ImageOptions::default(),
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "cache")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: self.priority,
             _resp: PhantomData::<GenerateCompletionModelResponse>,
         }
     }
@@ -76,18 +117,36 @@ impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
         self
     }
 
-    /// A list of base64-encoded images (for multimodal models such as `llava`).
+    /// A list of images (for multimodal models such as `llava`): base64 strings, `data:` URIs,
+    /// `http(s)` URLs, paths or raw bytes.
     #[inline]
-    pub fn images(mut self, images: Vec<&'a str>) -> Self {
+    pub fn images(mut self, images: Vec<impl Into<Image>>) -> Self {
         images
             .into_iter()
-            .for_each(|img| self.request.images.push(img));
+            .for_each(|img| self.request.images.push(img.into()));
+        self
+    }
+
+    /// An image (for multimodal models such as `llava`): a base64 string, a `data:` URI,
+    /// an `http(s)` URL, a path or raw bytes.
+    #[inline]
+    pub fn image(mut self, image: impl Into<Image>) -> Self {
+        self.request.images.push(image.into());
+        self
+    }
+
+    /// Reject images larger than `max_bytes` after downscaling.
+    #[inline]
+    pub fn max_image_bytes(mut self, max_bytes: usize) -> Self {
+        self.image_options.max_bytes = Some(max_bytes);
         self
     }
 
+    /// Downscale images whose width or height exceeds `max_dimension` pixels.
+    #[cfg(feature = "image")]
     #[inline]
-    pub fn image(mut self, image: &'a str) -> Self {
-        self.request.images.push(image);
+    pub fn max_image_dimension(mut self, max_dimension: u32) -> Self {
+        self.image_options.max_dimension = Some(max_dimension);
         self
     }
 
@@ -127,10 +186,47 @@ impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
         self
     }
 
+    /// Let thinking models return their reasoning separately from the answer.
+    /// Set to `false` to disable thinking on models that think by default.
+    #[inline]
+    pub fn think(mut self, think: bool) -> Self {
+        self.request.think = Some(think);
+        self
+    }
+
+    /// Serve the response from the cache if an identical request was answered before,
+    /// storing it otherwise. Entries are keyed by model digest and request, the digest being
+    /// looked up with `/api/tags` unless set with `model_digest`. See [`crate::cache`].
+    #[cfg(feature = "cache")]
+    #[inline]
+    pub fn cache(mut self, cache: Arc<dyn ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The digest of the model used for cache keys, skipping the `/api/tags` lookup.
+    #[cfg(feature = "cache")]
+    #[inline]
+    pub fn model_digest(mut self, model_digest: &'a str) -> Self {
+        self.model_digest = Some(model_digest);
+        self
+    }
+
+    /// The priority of the request in the scheduler of the client, if any
+    /// (default: `Priority::Interactive`).
+    #[cfg(feature = "scheduler")]
+    #[inline]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     /// Controls how long the model will stay loaded into memory following the request (default: 5m).
+    /// Accepts seconds as an `i64` (negative keeps the model loaded forever), a
+    /// [`Duration`][`std::time::Duration`] or a [`KeepAlive`] such as `KeepAlive::Forever`.
     #[inline]
-    pub fn keep_alive(mut self, keep_alive: i64) -> Self {
-        self.request.keep_alive = Some(keep_alive);
+    pub fn keep_alive(mut self, keep_alive: impl Into<KeepAlive>) -> Self {
+        self.request.keep_alive = keep_alive.into();
         self
     }
 
@@ -250,28 +346,98 @@ impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
     }
 }
 
+impl<'a> GenerateAction<'a, GenerateCompletionResponse> {
+    async fn resolve_images(&mut self) -> Result<(), OllamaError> {
+        resolve_images(
+            &self.ollama.cli,
+            &mut self.request.images,
+            &self.image_options,
+        )
+        .await
+    }
+
+    async fn send(&self) -> Result<GenerateCompletionResponse, OllamaError> {
+        #[cfg(feature = "scheduler")]
+        let _permit = self
+            .ollama
+            .acquire(self.request.model, self.priority)
+            .await?;
+
+        #[cfg(feature = "openai")]
+        if crate::openai::enabled(&self.ollama) {
+            return crate::openai::generate(&self.ollama, &self.request).await;
+        }
+
+        let headers = if self.request.format.is_some() {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            Some(headers)
+        } else {
+            None
+        };
+
+        let reqwest_resp = self.ollama.post(&self.request, headers).await?;
+        match reqwest_resp.status() {
+            StatusCode::OK => parse_response(reqwest_resp).await,
+            _code => {
+                let error: OllamaServerError = parse_response(reqwest_resp).await?;
+                Err(OllamaError::OllamaServerError(error.error))
+            }
+        }
+    }
+
+    #[cfg(feature = "stream")]
+    async fn send_stream(&self) -> Result<OllamaStream<GenerateCompletionResponse>, OllamaError> {
+        #[cfg(feature = "scheduler")]
+        let permit = self
+            .ollama
+            .acquire(self.request.model, self.priority)
+            .await?;
+
+        let stream = self.open_stream().await?;
+
+        #[cfg(feature = "scheduler")]
+        let stream = permit.hold(stream);
+        Ok(stream)
+    }
+
+    #[cfg(feature = "stream")]
+    async fn open_stream(&self) -> Result<OllamaStream<GenerateCompletionResponse>, OllamaError> {
+        #[cfg(feature = "openai")]
+        if crate::openai::enabled(&self.ollama) {
+            return crate::openai::generate_stream(&self.ollama, &self.request).await;
+        }
+
+        let reqwest_resp = self.ollama.post(&self.request, None).await?;
+        let s = decode_ndjson(reqwest_resp);
+        Ok(Box::pin(s))
+    }
+
+    #[cfg(feature = "cache")]
+    async fn cache_key(&self) -> Result<ResponseKey, OllamaError> {
+        let digest = match self.model_digest {
+            Some(digest) => digest.to_string(),
+            None => resolve_digest(&self.ollama, self.request.model).await?,
+        };
+        ResponseKey::generate(&digest, &self.request)
+    }
+}
+
 impl<'a> IntoFuture for GenerateAction<'a, GenerateCompletionResponse> {
     type Output = Result<GenerateCompletionResponse, OllamaError>;
     type IntoFuture = BoxFuture<'a, Self::Output>;
 
-    fn into_future(self) -> Self::IntoFuture {
+    fn into_future(mut self) -> Self::IntoFuture {
         Box::pin(async move {
-            let headers = if let Some(_) = self.request.format {
-                let mut headers = HeaderMap::new();
-                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-                Some(headers)
-            } else {
-                None
-            };
-
-            let reqwest_resp = self.ollama.post(&self.request, headers).await?;
-            match reqwest_resp.status() {
-                StatusCode::OK => parse_response(reqwest_resp).await,
-                _code => {
-                    let error: OllamaServerError = parse_response(reqwest_resp).await?;
-                    Err(OllamaError::OllamaServerError(error.error))
-                }
+            self.resolve_images().await?;
+
+            #[cfg(feature = "cache")]
+            if let Some(cache) = &self.cache {
+                let key = self.cache_key().await?;
+                return cached(cache.as_ref(), &key, self.send()).await;
             }
+
+            self.send().await
         })
     }
 }
@@ -299,19 +465,14 @@ impl<'a> IntoFuture for GenerateAction<'a, GenerateCompletionModelResponse> {
 impl<'a> IntoStream<GenerateCompletionResponse> for GenerateAction<'a, GenerateCompletionResponse> {
     async fn stream(mut self) -> Result<OllamaStream<GenerateCompletionResponse>, OllamaError> {
         self.request.stream = true;
-        let mut reqwest_stream = self.ollama.post(&self.request, None).await?.bytes_stream();
-
-        let s = stream! {
-            while let Some(item) = reqwest_stream.next().await {
-                match item {
-                    Ok(chunk) => match serde_json::from_slice(&chunk) {
-                        Ok(r) => yield Ok(r),
-                        Err(e) => yield Err(OllamaError::StreamDecodingError(e.to_string())),
-                    }
-                    Err(e) => yield Err(OllamaError::DecodingError(e))
-                }
-            };
-        };
-        Ok(Box::pin(s))
+        self.resolve_images().await?;
+
+        #[cfg(feature = "cache")]
+        if let Some(cache) = self.cache.clone() {
+            let key = self.cache_key().await?;
+            return cached_stream(cache, key, self.send_stream()).await;
+        }
+
+        self.send_stream().await
     }
 }
@@ -1,2 +1,5 @@
 pub mod chat;
 pub mod generate;
+
+#[cfg(feature = "stream")]
+pub mod delta;
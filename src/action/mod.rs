@@ -4,25 +4,86 @@ pub mod version;
 #[cfg(feature = "model")]
 pub mod model;
 
-use reqwest::header::HeaderMap;
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+pub(crate) mod observe;
+
+use bytes::Bytes;
+use reqwest::{Method, header::HeaderMap};
 use serde::{Serialize, de::DeserializeOwned};
 
 use crate::config::OllamaConfig;
 use crate::error::OllamaError;
 
+#[cfg(feature = "model")]
+use {
+    std::path::Path,
+    tokio_util::codec::{BytesCodec, FramedRead},
+};
+
 #[cfg(feature = "stream")]
-use {async_trait::async_trait, futures::Stream, std::pin::Pin};
+use {
+    async_stream::stream, async_trait::async_trait, futures::Stream, std::pin::Pin,
+    tokio_stream::StreamExt,
+};
+
+#[cfg(feature = "cassette")]
+use crate::cassette::Cassette;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::ClientMetrics;
+
+#[cfg(feature = "pool")]
+use crate::pool::HostPool;
+
+#[cfg(feature = "scheduler")]
+use crate::scheduler::{Permit, Priority, Scheduler};
+
+#[cfg(any(
+    feature = "cassette",
+    feature = "metrics",
+    feature = "pool",
+    feature = "scheduler"
+))]
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct OllamaClient {
     pub cli: reqwest::Client,
     pub config: OllamaConfig,
+
+    /// Records or replays the requests sent through the client.
+    #[cfg(feature = "cassette")]
+    pub cassette: Option<Arc<Cassette>>,
+
+    /// Counts and times the requests sent through the client.
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<Arc<ClientMetrics>>,
+
+    /// Routes the requests sent through the client to one of several hosts, see
+    /// [`crate::pool`].
+    #[cfg(feature = "pool")]
+    pub pool: Option<Arc<HostPool>>,
+
+    /// Queues chat, generate and embedding requests, see [`crate::scheduler`].
+    #[cfg(feature = "scheduler")]
+    pub scheduler: Option<Arc<Scheduler>>,
 }
 
 impl OllamaClient {
     pub fn new(config: OllamaConfig) -> Self {
         let cli = reqwest::Client::new();
-        Self { cli, config }
+        Self {
+            cli,
+            config,
+            #[cfg(feature = "cassette")]
+            cassette: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "pool")]
+            pool: None,
+            #[cfg(feature = "scheduler")]
+            scheduler: None,
+        }
     }
 
     pub fn url(&self) -> String {
@@ -34,38 +95,185 @@ impl OllamaClient {
         request: &impl OllamaRequest,
         headers: Option<HeaderMap>,
     ) -> Result<reqwest::Response, OllamaError> {
-        let serialized =
-            serde_json::to_vec(&request).map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
-
-        let url = format!("{}{}", self.config.url, request.path());
-        let response = self
-            .cli
-            .post(url)
-            .headers(headers.unwrap_or_default())
-            .body(serialized)
-            .send()
+        let serialized = serde_json::to_vec(&request)
+            .map(Bytes::from)
+            .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+
+        let headers = headers.unwrap_or_default();
+        let path = request.path();
+        let build = |url: &str| {
+            self.cli
+                .post(format!("{url}{path}"))
+                .headers(headers.clone())
+                .body(serialized.clone())
+        };
+        self.exchange(Method::POST, path.clone(), Some(&serialized), build)
             .await
-            .map_err(|e| OllamaError::RequestError(e))?;
-        Ok(response)
     }
 
     pub async fn get(
         &self,
         request: &impl OllamaRequest,
     ) -> Result<reqwest::Response, OllamaError> {
-        let url = format!("{}{}", self.config.url, request.path());
-        let response = self
-            .cli
-            .get(url)
-            .send()
+        let path = request.path();
+        let build = |url: &str| self.cli.get(format!("{url}{path}"));
+        self.exchange(Method::GET, path.clone(), None, build).await
+    }
+
+    pub async fn delete(
+        &self,
+        request: &impl OllamaRequest,
+    ) -> Result<reqwest::Response, OllamaError> {
+        let serialized = serde_json::to_vec(&request)
+            .map(Bytes::from)
+            .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+
+        let path = request.path();
+        let build = |url: &str| {
+            self.cli
+                .delete(format!("{url}{path}"))
+                .body(serialized.clone())
+        };
+        self.exchange(Method::DELETE, path.clone(), Some(&serialized), build)
+            .await
+    }
+
+    pub async fn head(
+        &self,
+        request: &impl OllamaRequest,
+    ) -> Result<reqwest::Response, OllamaError> {
+        let path = request.path();
+        let build = |url: &str| self.cli.head(format!("{url}{path}"));
+        self.exchange(Method::HEAD, path.clone(), None, build).await
+    }
+
+    /// Post the content of `file` to the path of `request`. The file is streamed from disk,
+    /// so it is neither recorded by a cassette nor matched on replay.
+    #[cfg(feature = "model")]
+    pub async fn upload(
+        &self,
+        request: &impl OllamaRequest,
+        file: &Path,
+    ) -> Result<reqwest::Response, OllamaError> {
+        // The file is opened again for each attempt, but a missing one fails here.
+        tokio::fs::metadata(file)
             .await
-            .map_err(|e| OllamaError::RequestError(e))?;
-        Ok(response)
+            .map_err(OllamaError::FileError)?;
+
+        let path = request.path();
+        let build = |url: &str| self.cli.post(format!("{url}{path}")).body(file_body(file));
+        self.exchange(Method::POST, path.clone(), None, build).await
     }
+
+    /// Wait for the scheduler, if any, to let a request to `model` run.
+    #[cfg(feature = "scheduler")]
+    pub(crate) async fn acquire(
+        &self,
+        model: &str,
+        priority: Priority,
+    ) -> Result<Permit, OllamaError> {
+        match &self.scheduler {
+            Some(scheduler) => scheduler.acquire(model, priority).await,
+            None => Ok(Permit::default()),
+        }
+    }
+
+    /// Send the request built by `request` from the host URL through the pool, the cassette,
+    /// the metrics and the tracing span, if enabled.
+    #[cfg_attr(
+        not(any(feature = "cassette", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    async fn exchange(
+        &self,
+        method: Method,
+        path: String,
+        body: Option<&Bytes>,
+        request: impl Fn(&str) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, OllamaError> {
+        #[cfg(feature = "tracing")]
+        let span = crate::telemetry::RequestSpan::new(&self.config, &method, &path, body);
+
+        // The span reports the host of the pool that answered, not the configured one.
+        #[cfg(all(feature = "pool", feature = "tracing"))]
+        let answered = span.server_recorder();
+        #[cfg(all(feature = "pool", not(feature = "tracing")))]
+        let answered = |_: &str| {};
+
+        let send = async {
+            #[cfg(feature = "pool")]
+            if let Some(pool) = &self.pool {
+                let body = body.map(|b| &b[..]);
+                return pool.send(&path, body, request, &answered).await;
+            }
+            request(&self.config.url)
+                .send()
+                .await
+                .map_err(OllamaError::RequestError)
+        };
+
+        #[cfg(feature = "cassette")]
+        let send = async {
+            match &self.cassette {
+                Some(cassette) => {
+                    let body = body.map(|b| &b[..]);
+                    cassette.exchange(&method, &path, body, send).await
+                }
+                None => send.await,
+            }
+        };
+
+        #[cfg(feature = "metrics")]
+        let send = async {
+            match &self.metrics {
+                Some(metrics) => metrics.instrument(&path, body, send).await,
+                None => send.await,
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        return span.instrument(send).await;
+
+        #[cfg(not(feature = "tracing"))]
+        send.await
+    }
+}
+
+/// A body streaming the content of `path`. A file that cannot be opened fails the request
+/// once the body is read.
+#[cfg(feature = "model")]
+fn file_body(path: &Path) -> reqwest::Body {
+    match std::fs::File::open(path) {
+        Ok(file) => {
+            let file = tokio::fs::File::from_std(file);
+            reqwest::Body::wrap_stream(FramedRead::new(file, BytesCodec::new()))
+        }
+        Err(e) => reqwest::Body::wrap_stream(futures::stream::once(async { Err::<Bytes, _>(e) })),
+    }
+}
+
+/// A response with the status and headers given and another body.
+#[cfg(any(
+    feature = "cassette",
+    feature = "tracing",
+    feature = "metrics",
+    feature = "pool"
+))]
+pub(crate) fn build_response(
+    status: u16,
+    headers: HeaderMap,
+    body: reqwest::Body,
+) -> Result<reqwest::Response, OllamaError> {
+    let mut response = http::Response::builder()
+        .status(status)
+        .body(body)
+        .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+    *response.headers_mut() = headers;
+    Ok(reqwest::Response::from(response))
 }
 
 #[cfg(feature = "stream")]
-pub type OllamaStream<T> = Pin<Box<dyn Stream<Item = Result<T, OllamaError>>>>;
+pub type OllamaStream<T> = Pin<Box<dyn Stream<Item = Result<T, OllamaError>> + Send>>;
 
 #[cfg(feature = "stream")]
 #[async_trait]
@@ -80,8 +288,89 @@ pub trait OllamaRequest: Serialize + Send + Sync {
 pub(crate) async fn parse_response<T: DeserializeOwned>(
     response: reqwest::Response,
 ) -> Result<T, OllamaError> {
-    response
-        .json()
-        .await
-        .map_err(|e| OllamaError::DecodingError(e))
+    response.json().await.map_err(OllamaError::DecodingError)
+}
+
+/// Decode a newline-delimited JSON response body. Lines may be split across network chunks
+/// or share one, so they are buffered until complete.
+#[cfg(feature = "stream")]
+pub(crate) fn decode_ndjson<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> impl Stream<Item = Result<T, OllamaError>> {
+    let mut bytes = response.bytes_stream();
+    stream! {
+        let mut buffer: Vec<u8> = vec![];
+        while let Some(item) = bytes.next().await {
+            match item {
+                Ok(chunk) => buffer.extend_from_slice(&chunk),
+                Err(e) => {
+                    yield Err(OllamaError::DecodingError(e));
+                    continue;
+                }
+            }
+
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                yield serde_json::from_slice(&line)
+                    .map_err(|e| OllamaError::StreamDecodingError(e.to_string()));
+            }
+        }
+
+        if !buffer.iter().all(u8::is_ascii_whitespace) {
+            yield serde_json::from_slice(&buffer)
+                .map_err(|e| OllamaError::StreamDecodingError(e.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    use tokio_stream::StreamExt;
+
+    use crate::Ollama;
+    use crate::action::IntoStream;
+
+    #[tokio::test]
+    async fn streams_should_decode_lines_split_across_chunks() {
+        let line = |response: &str, done: bool| {
+            format!(
+                r#"{{"model":"llama3.1:8b","created_at":"2025-01-01T00:00:00Z","response":"{response}","done":{done}}}"#
+            ) + "\n"
+        };
+        let first = line("Hel", false);
+        let (head, tail) = first.split_at(first.len() / 2);
+        let chunks = [
+            head.to_string(),
+            tail.to_string() + &line("lo", false),
+            line("!", true),
+        ];
+
+        // Answer with one HTTP chunk at a time, so each arrives in its own read.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let _ = socket.read(&mut [0; 4096]).unwrap();
+            let head = "HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\n\
+                transfer-encoding: chunked\r\n\r\n";
+            socket.write_all(head.as_bytes()).unwrap();
+            for chunk in chunks {
+                write!(socket, "{:x}\r\n{chunk}\r\n", chunk.len()).unwrap();
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            socket.write_all(b"0\r\n\r\n").unwrap();
+        });
+
+        let ollama = Ollama::new(&url);
+        let stream = ollama.generate("llama3.1:8b").prompt("hi").stream().await;
+        let responses: Vec<_> = stream.unwrap().collect().await;
+        let responses: Vec<_> = responses.into_iter().map(|r| r.unwrap().response).collect();
+        assert_eq!(responses, ["Hel", "lo", "!"]);
+    }
 }
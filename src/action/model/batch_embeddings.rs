@@ -0,0 +1,472 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_stream::stream;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use reqwest::StatusCode;
+
+use crate::{
+    abi::{
+        KeepAlive, Options,
+        model::{
+            batch_embeddings::{BatchEmbedding, BatchProgress},
+            generate_embeddings::{GenerateEmbeddingsRequest, GenerateEmbeddingsResponse},
+        },
+    },
+    action::{IntoStream, OllamaClient, OllamaStream, parse_response},
+    error::{OllamaError, OllamaServerError},
+    tokens::estimate_tokens,
+};
+
+const DEFAULT_BATCH_SIZE: usize = 64;
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_RETRIES: usize = 3;
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+type Inputs = Pin<Box<dyn Stream<Item = String> + Send>>;
+
+pub struct BatchEmbeddingsAction {
+    ollama: OllamaClient,
+    inputs: Inputs,
+    total: Option<usize>,
+    limits: BatchLimits,
+    concurrency: usize,
+    params: BatchParams,
+}
+
+/// Everything needed to send a batch, shared between the concurrent requests.
+#[derive(Debug, Clone)]
+struct BatchParams {
+    model: String,
+    truncate: Option<bool>,
+    options: Options,
+    keep_alive: KeepAlive,
+    retries: usize,
+    retry_backoff: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BatchLimits {
+    batch_size: usize,
+    max_batch_tokens: Option<usize>,
+}
+
+/// A run of consecutive inputs sent in a single `/api/embed` request.
+#[derive(Debug, Default, PartialEq)]
+struct Batch {
+    /// Index of the first input of the batch.
+    start: usize,
+    texts: Vec<String>,
+}
+
+impl BatchEmbeddingsAction {
+    pub fn new(ollama: OllamaClient, model: &str) -> Self {
+        Self {
+            ollama,
+            inputs: Box::pin(futures::stream::empty()),
+            total: Some(0),
+            limits: BatchLimits {
+                batch_size: DEFAULT_BATCH_SIZE,
+                max_batch_tokens: None,
+            },
+            concurrency: DEFAULT_CONCURRENCY,
+            params: BatchParams {
+                model: model.to_string(),
+                truncate: None,
+                options: Options::default(),
+                keep_alive: KeepAlive::default(),
+                retries: DEFAULT_RETRIES,
+                retry_backoff: DEFAULT_RETRY_BACKOFF,
+            },
+        }
+    }
+
+    /// Texts to generate embeddings for, appended after any previously added inputs.
+    #[inline]
+    pub fn inputs<I>(self, inputs: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String> + 'static,
+        I::IntoIter: Send + 'static,
+    {
+        self.input_stream(futures::stream::iter(inputs.into_iter().map(Into::into)))
+    }
+
+    /// A stream of texts to generate embeddings for, appended after any previously added inputs.
+    /// The stream is consumed lazily, only as far ahead as the running batches require.
+    #[inline]
+    pub fn input_stream(mut self, inputs: impl Stream<Item = String> + Send + 'static) -> Self {
+        self.total = match (self.total, inputs.size_hint()) {
+            (Some(total), (lower, Some(upper))) if lower == upper => Some(total + lower),
+            _ => None,
+        };
+        self.inputs = Box::pin(self.inputs.chain(inputs));
+        self
+    }
+
+    /// Maximum number of inputs per request (default: 64).
+    #[inline]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.limits.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Maximum number of estimated tokens per request, counting 4 characters per token.
+    /// An input longer than the limit is sent in a batch of its own.
+    #[inline]
+    pub fn max_batch_tokens(mut self, max_batch_tokens: usize) -> Self {
+        self.limits.max_batch_tokens = Some(max_batch_tokens);
+        self
+    }
+
+    /// Number of requests in flight at the same time (default: 4).
+    #[inline]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How many times a failed batch is retried before giving up (default: 3). Only
+    /// transport errors and 5xx responses are retried, a 4xx fails the batch at once.
+    #[inline]
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.params.retries = retries;
+        self
+    }
+
+    /// Delay before the first retry of a failed batch, doubled on every further attempt
+    /// up to 30 seconds (default: 500ms).
+    #[inline]
+    pub fn retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.params.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Truncates the end of each input to fit within context length.
+    /// Returns error if `false` and context length is exceeded. Defaults to `true`.
+    #[inline]
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        if !truncate {
+            self.params.truncate = Some(false);
+        }
+        self
+    }
+
+    /// Controls how long the model will stay loaded into memory following each request (default: 5m).
+    /// Accepts seconds as an `i64` (negative keeps the model loaded forever), a [`Duration`] or
+    /// a [`KeepAlive`] such as `KeepAlive::Forever`.
+    #[inline]
+    pub fn keep_alive(mut self, keep_alive: impl Into<KeepAlive>) -> Self {
+        self.params.keep_alive = keep_alive.into();
+        self
+    }
+
+    /// Sets the size of the context window used to generate the next token.
+    /// (Default: 2048).
+    #[inline]
+    pub fn num_ctx(mut self, num_ctx: i64) -> Self {
+        self.params.options.num_ctx(num_ctx);
+        self
+    }
+}
+
+#[async_trait]
+impl IntoStream<BatchEmbedding> for BatchEmbeddingsAction {
+    async fn stream(self) -> Result<OllamaStream<BatchEmbedding>, OllamaError> {
+        let ollama = self.ollama;
+        let params = Arc::new(self.params);
+        let mut results = Box::pin(into_batches(self.inputs, self.limits))
+            .map(move |batch| embed_batch(ollama.clone(), params.clone(), batch))
+            .buffered(self.concurrency);
+
+        let s = stream! {
+            let mut progress = BatchProgress {
+                total: self.total,
+                ..Default::default()
+            };
+
+            while let Some(result) = results.next().await {
+                match result {
+                    Ok((start, embeddings)) => {
+                        let count = embeddings.len();
+                        for (offset, embedding) in embeddings.into_iter().enumerate() {
+                            progress.completed += 1;
+                            if offset + 1 == count {
+                                progress.batches_completed += 1;
+                            }
+                            yield Ok(BatchEmbedding { index: start + offset, embedding, progress });
+                        }
+                    }
+                    // Stop at the first batch that keeps failing so no index is silently skipped.
+                    Err(e) => {
+                        yield Err(e);
+                        break;
+                    }
+                }
+            }
+        };
+
+        Ok(Box::pin(s))
+    }
+}
+
+fn into_batches(mut inputs: Inputs, limits: BatchLimits) -> impl Stream<Item = Batch> + Send {
+    stream! {
+        let mut batch = Batch::default();
+        let mut tokens = 0;
+        let mut next = 0;
+
+        while let Some(text) = inputs.next().await {
+            let text_tokens = estimate_tokens(&text);
+            let is_full = batch.texts.len() >= limits.batch_size
+                || limits
+                    .max_batch_tokens
+                    .is_some_and(|max| tokens + text_tokens > max);
+
+            if !batch.texts.is_empty() && is_full {
+                let full = std::mem::take(&mut batch);
+                tokens = 0;
+                yield full;
+            }
+
+            if batch.texts.is_empty() {
+                batch.start = next;
+            }
+            batch.texts.push(text);
+            tokens += text_tokens;
+            next += 1;
+        }
+
+        if !batch.texts.is_empty() {
+            yield batch;
+        }
+    }
+}
+
+/// Embed a batch, retrying with exponential backoff. Returns the batch start index along
+/// with one embedding per input.
+async fn embed_batch(
+    ollama: OllamaClient,
+    params: Arc<BatchParams>,
+    batch: Batch,
+) -> Result<(usize, Vec<Vec<f64>>), OllamaError> {
+    let mut backoff = params.retry_backoff;
+    let mut attempt = 0;
+    loop {
+        match send_batch(&ollama, &params, &batch.texts).await {
+            Ok(embeddings) => return Ok((batch.start, embeddings)),
+            Err(failure) if failure.retryable && attempt < params.retries => {
+                attempt += 1;
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2).min(MAX_RETRY_BACKOFF);
+            }
+            Err(failure) => return Err(failure.error),
+        }
+    }
+}
+
+/// A failed attempt at sending a batch.
+struct Failure {
+    error: OllamaError,
+
+    /// Whether sending the batch again may succeed: the request did not reach the server,
+    /// or the server answered with a 5xx.
+    retryable: bool,
+}
+
+impl From<OllamaError> for Failure {
+    fn from(error: OllamaError) -> Self {
+        let retryable = matches!(error, OllamaError::RequestError(_));
+        Self { error, retryable }
+    }
+}
+
+async fn send_batch(
+    ollama: &OllamaClient,
+    params: &BatchParams,
+    texts: &[String],
+) -> Result<Vec<Vec<f64>>, Failure> {
+    let request = GenerateEmbeddingsRequest {
+        model: &params.model,
+        input: texts.iter().map(String::as_str).collect(),
+        truncate: params.truncate,
+        options: params.options.clone(),
+        keep_alive: params.keep_alive,
+    };
+
+    let reqwest_resp = ollama.post(&request, None).await?;
+    let response: GenerateEmbeddingsResponse = match reqwest_resp.status() {
+        StatusCode::OK => parse_response(reqwest_resp).await?,
+        code => {
+            let error = parse_response::<OllamaServerError>(reqwest_resp)
+                .await
+                .map_or_else(|e| e, |error| OllamaError::OllamaServerError(error.error));
+            return Err(Failure {
+                error,
+                retryable: code.is_server_error(),
+            });
+        }
+    };
+
+    if response.embeddings.len() != texts.len() {
+        return Err(OllamaError::InvalidFormat(format!(
+            "got {} embeddings for {} inputs",
+            response.embeddings.len(),
+            texts.len()
+        ))
+        .into());
+    }
+    Ok(response.embeddings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use super::{Batch, BatchLimits, into_batches};
+    use crate::{action::IntoStream, mock::MockOllama};
+
+    async fn batches(inputs: Vec<&'static str>, limits: BatchLimits) -> Vec<Batch> {
+        let inputs = futures::stream::iter(inputs.into_iter().map(String::from));
+        into_batches(Box::pin(inputs), limits).collect().await
+    }
+
+    fn batch(start: usize, texts: &[&str]) -> Batch {
+        Batch {
+            start,
+            texts: texts.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn into_batches_should_respect_limits() {
+        let limits = BatchLimits {
+            batch_size: 2,
+            max_batch_tokens: None,
+        };
+        let result = batches(vec!["a", "b", "c", "d", "e"], limits).await;
+        assert_eq!(
+            result,
+            vec![
+                batch(0, &["a", "b"]),
+                batch(2, &["c", "d"]),
+                batch(4, &["e"])
+            ]
+        );
+
+        // "aaaaaaaa" is 2 tokens, the 12 character input exceeds the limit on its own.
+        let limits = BatchLimits {
+            batch_size: 10,
+            max_batch_tokens: Some(2),
+        };
+        let result = batches(vec!["a", "a", "aaaaaaaaaaaa", "aaaaaaaa", "a"], limits).await;
+        assert_eq!(
+            result,
+            vec![
+                batch(0, &["a", "a"]),
+                batch(2, &["aaaaaaaaaaaa"]),
+                batch(3, &["aaaaaaaa"]),
+                batch(4, &["a"]),
+            ]
+        );
+
+        assert!(batches(vec![], limits).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn batch_embeddings_should_keep_order() {
+        let mock = MockOllama::start().await;
+        let inputs: Vec<String> = (1..=20).map(|i| "x".repeat(i)).collect();
+
+        let results: Vec<_> = mock
+            .ollama()
+            .batch_embeddings("all-minilm")
+            .inputs(inputs)
+            .batch_size(3)
+            .concurrency(4)
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(mock.requests("/api/embed").len(), 7);
+        for (i, result) in results.iter().enumerate() {
+            let item = result.as_ref().unwrap();
+            assert_eq!(item.index, i);
+            assert_eq!(item.embedding[0], (i + 1) as f64);
+            assert_eq!(item.progress.completed, i + 1);
+            assert_eq!(item.progress.total, Some(20));
+        }
+        let last = results.last().unwrap().as_ref().unwrap();
+        assert_eq!(last.progress.batches_completed, 7);
+        assert_eq!(last.progress.fraction(), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn batch_embeddings_should_retry() {
+        let mock = MockOllama::start().await;
+        mock.fail_next(2);
+
+        let results: Vec<_> = mock
+            .ollama()
+            .batch_embeddings("all-minilm")
+            .inputs(["a", "bb"])
+            .retry_backoff(Duration::from_millis(1))
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(mock.requests("/api/embed").len(), 3);
+
+        // Give up once the retries are exhausted.
+        mock.fail_next(2);
+        let results: Vec<_> = mock
+            .ollama()
+            .batch_embeddings("all-minilm")
+            .inputs(["a", "bb"])
+            .batch_size(1)
+            .concurrency(1)
+            .retries(1)
+            .retry_backoff(Duration::from_millis(1))
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].as_ref().unwrap_err().to_string(),
+            "ollama error: mock failure"
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_embeddings_should_not_retry_client_errors() {
+        let mock = MockOllama::start().await;
+
+        let results: Vec<_> = mock
+            .ollama()
+            .batch_embeddings("")
+            .inputs(["a"])
+            .retry_backoff(Duration::from_millis(1))
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            results[0].as_ref().unwrap_err().to_string(),
+            "ollama error: model is required"
+        );
+        assert_eq!(mock.requests("/api/embed").len(), 1);
+    }
+}
@@ -3,7 +3,7 @@ use reqwest::StatusCode;
 
 use crate::{
     abi::model::check_blob_exists::CheckBlobExistsRequest,
-    action::{OllamaClient, OllamaRequest, parse_response},
+    action::{OllamaClient, parse_response},
     error::{OllamaError, OllamaServerError},
 };
 
@@ -26,12 +26,7 @@ impl<'a> IntoFuture for CheckBlobExistsAction<'a> {
 
     fn into_future(self) -> Self::IntoFuture {
         Box::pin(async move {
-            let url = format!("{}{}", self.ollama.url(), self.request.path());
-            let reqwest_resp = reqwest::Client::new()
-                .head(url)
-                .send()
-                .await
-                .map_err(|e| OllamaError::RequestError(e))?;
+            let reqwest_resp = self.ollama.head(&self.request).await?;
 
             match reqwest_resp.status() {
                 StatusCode::OK => Ok(()),
@@ -303,7 +303,7 @@ impl<'a> IntoStream<CreateModelResponse> for CreateModelAction<'a> {
                         Ok(r) => for c in r {
                             yield Ok(c)
                         },
-                        Err(e) => yield Err(e.into()),
+                        Err(e) => yield Err(e),
                     }
                     Err(e) => yield Err(OllamaError::DecodingError(e))
                 }
@@ -317,7 +317,7 @@ impl<'a> IntoStream<CreateModelResponse> for CreateModelAction<'a> {
 
 #[cfg(feature = "stream")]
 fn parse_chunks(chunks: &[u8]) -> Result<Vec<CreateModelResponse>, OllamaError> {
-    let chunks = std::str::from_utf8(&chunks).map_err(|e| {
+    let chunks = std::str::from_utf8(chunks).map_err(|e| {
         OllamaError::StreamDecodingError(format!("failed to parse chunk to utf8: {e}"))
     })?;
 
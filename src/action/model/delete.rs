@@ -3,7 +3,7 @@ use reqwest::StatusCode;
 
 use crate::{
     abi::model::delete::DeleteModelRequest,
-    action::{OllamaClient, OllamaRequest, parse_response},
+    action::{OllamaClient, parse_response},
     error::{OllamaError, OllamaServerError},
 };
 
@@ -26,17 +26,7 @@ impl<'a> IntoFuture for DeleteModelAction<'a> {
 
     fn into_future(self) -> Self::IntoFuture {
         Box::pin(async move {
-            let url = format!("{}{}", self.ollama.url(), self.request.path());
-
-            let serialized = serde_json::to_vec(&self.request)
-                .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
-
-            let reqwest_resp = reqwest::Client::new()
-                .delete(url)
-                .body(serialized)
-                .send()
-                .await
-                .map_err(|e| OllamaError::RequestError(e))?;
+            let reqwest_resp = self.ollama.delete(&self.request).await?;
 
             match reqwest_resp.status() {
                 StatusCode::OK => Ok(()),
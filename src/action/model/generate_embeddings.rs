@@ -2,14 +2,33 @@ use futures::future::BoxFuture;
 use reqwest::StatusCode;
 
 use crate::{
-    abi::model::generate_embeddings::{GenerateEmbeddingsRequest, GenerateEmbeddingsResponse},
+    abi::{
+        KeepAlive,
+        model::generate_embeddings::{GenerateEmbeddingsRequest, GenerateEmbeddingsResponse},
+    },
     action::{OllamaClient, parse_response},
     error::{OllamaError, OllamaServerError},
 };
 
+#[cfg(feature = "scheduler")]
+use crate::scheduler::Priority;
+
+#[cfg(feature = "embeddings")]
+use crate::{
+    abi::Usage,
+    action::model::list_local::resolve_digest,
+    embeddings::{CacheKey, EmbeddingCache},
+};
+
 pub struct GenerateEmbeddingsAction<'a> {
     ollama: OllamaClient,
     request: GenerateEmbeddingsRequest<'a>,
+    #[cfg(feature = "embeddings")]
+    cache: Option<&'a dyn EmbeddingCache>,
+    #[cfg(feature = "embeddings")]
+    model_digest: Option<&'a str>,
+    #[cfg(feature = "scheduler")]
+    priority: Priority,
 }
 
 impl<'a> GenerateEmbeddingsAction<'a> {
@@ -19,7 +38,16 @@ impl<'a> GenerateEmbeddingsAction<'a> {
             ..Default::default()
         };
 
-        Self { ollama, request }
+        Self {
+            ollama,
+            request,
+            #[cfg(feature = "embeddings")]
+            cache: None,
+            #[cfg(feature = "embeddings")]
+            model_digest: None,
+            #[cfg(feature = "scheduler")]
+            priority: Priority::default(),
+        }
     }
 
     /// Text to generate embeddings for.
@@ -42,16 +70,46 @@ impl<'a> GenerateEmbeddingsAction<'a> {
     /// Returns error if `false` and context length is exceeded. Defaults to `true`.
     #[inline]
     pub fn truncate(mut self, truncate: bool) -> Self {
-        if truncate == false {
+        if !truncate {
             self.request.truncate = Some(false);
         }
         self
     }
 
+    /// The priority of the request in the scheduler of the client, if any
+    /// (default: `Priority::Interactive`).
+    #[cfg(feature = "scheduler")]
+    #[inline]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     /// Controls how long the model will stay loaded into memory following the request (default: 5m).
+    /// Accepts seconds as an `i64` (negative keeps the model loaded forever), a
+    /// [`Duration`][`std::time::Duration`] or a [`KeepAlive`] such as `KeepAlive::Forever`.
+    #[inline]
+    pub fn keep_alive(mut self, keep_alive: impl Into<KeepAlive>) -> Self {
+        self.request.keep_alive = keep_alive.into();
+        self
+    }
+
+    /// Look up embeddings in the cache first and only send the inputs that miss, storing
+    /// their embeddings afterwards. Entries are keyed by model digest, truncate flag, options
+    /// and input text, the digest being looked up with `/api/tags` unless set with `model_digest`.
+    /// Failing to store an embedding does not fail the request.
+    #[cfg(feature = "embeddings")]
+    #[inline]
+    pub fn cache(mut self, cache: &'a dyn EmbeddingCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The digest of the model used for cache keys, skipping the `/api/tags` lookup.
+    #[cfg(feature = "embeddings")]
     #[inline]
-    pub fn keep_alive(mut self, keep_alive: i64) -> Self {
-        self.request.keep_alive = Some(keep_alive);
+    pub fn model_digest(mut self, model_digest: &'a str) -> Self {
+        self.model_digest = Some(model_digest);
         self
     }
 
@@ -177,14 +235,215 @@ impl<'a> IntoFuture for GenerateEmbeddingsAction<'a> {
 
     fn into_future(self) -> Self::IntoFuture {
         Box::pin(async move {
-            let reqwest_resp = self.ollama.post(&self.request, None).await?;
-            match reqwest_resp.status() {
-                StatusCode::OK => parse_response(reqwest_resp).await,
-                _code => {
-                    let error: OllamaServerError = parse_response(reqwest_resp).await?;
-                    Err(OllamaError::OllamaServerError(error.error))
-                }
+            #[cfg(feature = "embeddings")]
+            if let Some(cache) = self.cache {
+                return self.embed_cached(cache).await;
             }
+
+            self.embed(&self.request).await
         })
     }
 }
+
+impl GenerateEmbeddingsAction<'_> {
+    async fn embed(
+        &self,
+        request: &GenerateEmbeddingsRequest<'_>,
+    ) -> Result<GenerateEmbeddingsResponse, OllamaError> {
+        let ollama = &self.ollama;
+
+        #[cfg(feature = "scheduler")]
+        let _permit = ollama.acquire(request.model, self.priority).await?;
+
+        #[cfg(feature = "openai")]
+        if crate::openai::enabled(ollama) {
+            return crate::openai::embed(ollama, request).await;
+        }
+
+        let reqwest_resp = ollama.post(request, None).await?;
+        match reqwest_resp.status() {
+            StatusCode::OK => parse_response(reqwest_resp).await,
+            _code => {
+                let error: OllamaServerError = parse_response(reqwest_resp).await?;
+                Err(OllamaError::OllamaServerError(error.error))
+            }
+        }
+    }
+
+    /// Serve the inputs from the cache, sending only the misses in a single request.
+    /// If every input hits, the returned usage is empty.
+    #[cfg(feature = "embeddings")]
+    async fn embed_cached(
+        &self,
+        cache: &dyn EmbeddingCache,
+    ) -> Result<GenerateEmbeddingsResponse, OllamaError> {
+        let request = &self.request;
+        let digest = match self.model_digest {
+            Some(digest) => digest.to_string(),
+            None => resolve_digest(&self.ollama, request.model).await?,
+        };
+
+        let keys = request
+            .input
+            .iter()
+            .map(|text| CacheKey::new(&digest, request.truncate, &request.options, text))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut embeddings = Vec::with_capacity(keys.len());
+        let mut misses = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            // An entry that cannot be read is a miss, it is recomputed and overwritten.
+            let hit = cache.get(key).await.unwrap_or(None);
+            if hit.is_none() {
+                misses.push(i);
+            }
+            embeddings.push(hit);
+        }
+
+        let mut model = request.model.to_string();
+        let mut usage = Usage::default();
+        if !misses.is_empty() {
+            let request = GenerateEmbeddingsRequest {
+                input: misses.iter().map(|&i| request.input[i]).collect(),
+                ..request.clone()
+            };
+
+            let response = self.embed(&request).await?;
+            if response.embeddings.len() != misses.len() {
+                return Err(OllamaError::InvalidFormat(format!(
+                    "got {} embeddings for {} inputs",
+                    response.embeddings.len(),
+                    misses.len()
+                )));
+            }
+
+            for (&i, embedding) in misses.iter().zip(response.embeddings) {
+                // The embedding is good even if storing it is not.
+                let _ = cache.put(&keys[i], &embedding).await;
+                embeddings[i] = Some(embedding);
+            }
+            model = response.model;
+            usage = response.usage;
+        }
+
+        Ok(GenerateEmbeddingsResponse {
+            model,
+            embeddings: embeddings.into_iter().flatten().collect(),
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "embeddings")]
+mod tests {
+    use async_trait::async_trait;
+
+    use crate::{
+        embeddings::{CacheKey, EmbeddingCache, MemoryCache},
+        error::OllamaError,
+        mock::MockOllama,
+    };
+
+    /// A cache that fails every read and write.
+    struct BrokenCache;
+
+    #[async_trait]
+    impl EmbeddingCache for BrokenCache {
+        async fn get(&self, _key: &CacheKey) -> Result<Option<Vec<f64>>, OllamaError> {
+            Err(OllamaError::InvalidFormat("corrupt entry".to_string()))
+        }
+
+        async fn put(&self, _key: &CacheKey, _embedding: &[f64]) -> Result<(), OllamaError> {
+            Err(OllamaError::FileError(
+                std::io::ErrorKind::ReadOnlyFilesystem.into(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_should_only_send_misses() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let cache = MemoryCache::new(100);
+
+        let first = ollama
+            .generate_embeddings("all-minilm")
+            .inputs(vec!["a", "bb"])
+            .cache(&cache)
+            .await
+            .unwrap();
+        assert_eq!(first.embeddings.len(), 2);
+        assert_eq!(cache.len(), 2);
+
+        let second = ollama
+            .generate_embeddings("all-minilm")
+            .inputs(vec!["ccc", "a", "dddd", "bb"])
+            .cache(&cache)
+            .await
+            .unwrap();
+        let lengths: Vec<f64> = second.embeddings.iter().map(|e| e[0]).collect();
+        assert_eq!(lengths, vec![3.0, 1.0, 4.0, 2.0]);
+
+        let requests = mock.requests("/api/embed");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["input"], serde_json::json!(["ccc", "dddd"]));
+
+        // Every input hits, nothing is sent.
+        ollama
+            .generate_embeddings("all-minilm:latest")
+            .inputs(vec!["dddd", "a"])
+            .cache(&cache)
+            .await
+            .unwrap();
+        assert_eq!(mock.requests("/api/embed").len(), 2);
+
+        // A different truncate flag is a different entry.
+        ollama
+            .generate_embeddings("all-minilm")
+            .input("a")
+            .truncate(false)
+            .cache(&cache)
+            .await
+            .unwrap();
+        assert_eq!(mock.requests("/api/embed").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_should_resolve_model_digest() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let cache = MemoryCache::new(100);
+
+        let err = ollama
+            .generate_embeddings("missing")
+            .input("a")
+            .cache(&cache)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OllamaError::ModelDoesNotExist));
+
+        ollama
+            .generate_embeddings("missing")
+            .input("a")
+            .cache(&cache)
+            .model_digest("sha256:0000")
+            .await
+            .unwrap();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cache_failures_should_not_fail_requests() {
+        let mock = MockOllama::start().await;
+        let response = mock
+            .ollama()
+            .generate_embeddings("all-minilm")
+            .inputs(vec!["a", "bb"])
+            .cache(&BrokenCache)
+            .model_digest("sha256:0000")
+            .await
+            .unwrap();
+        assert_eq!(response.embeddings.len(), 2);
+    }
+}
@@ -31,6 +31,11 @@ impl<'a> IntoFuture for ListLocalModelAction<'a> {
 
     fn into_future(self) -> Self::IntoFuture {
         Box::pin(async move {
+            #[cfg(feature = "openai")]
+            if crate::openai::enabled(&self.ollama) {
+                return crate::openai::list_models(&self.ollama).await;
+            }
+
             let reqwest_resp = self.ollama.get(&self.request).await?;
             match reqwest_resp.status() {
                 StatusCode::OK => parse_response(reqwest_resp).await,
@@ -42,3 +47,26 @@ impl<'a> IntoFuture for ListLocalModelAction<'a> {
         })
     }
 }
+
+/// The digest of a local model, matching `model` either exactly or with the `latest` tag.
+/// Servers that do not report digests, such as the OpenAI-compatible API, key by name.
+#[cfg(any(feature = "embeddings", feature = "cache"))]
+pub(crate) async fn resolve_digest(
+    ollama: &OllamaClient,
+    model: &str,
+) -> Result<String, OllamaError> {
+    let latest = format!("{model}:latest");
+    ListLocalModelAction::new(ollama.clone())
+        .await?
+        .models
+        .into_iter()
+        .find(|m| m.name == model || m.name == latest)
+        .map(|m| {
+            if m.digest.is_empty() {
+                m.name
+            } else {
+                m.digest
+            }
+        })
+        .ok_or(OllamaError::ModelDoesNotExist)
+}
@@ -9,3 +9,8 @@ pub mod pull;
 pub mod push;
 pub mod push_blob;
 pub mod show_info;
+
+#[cfg(feature = "stream")]
+pub mod batch_embeddings;
+#[cfg(feature = "stream")]
+pub mod watch_running;
@@ -74,7 +74,7 @@ impl<'a> IntoStream<PullModelStreamingResponse> for PullModelAction<'a> {
                         Ok(r) => for c in r {
                             yield Ok(c)
                         },
-                        Err(e) => yield Err(e.into()),
+                        Err(e) => yield Err(e),
                     }
                     Err(e) => yield Err(OllamaError::DecodingError(e))
                 }
@@ -88,7 +88,7 @@ impl<'a> IntoStream<PullModelStreamingResponse> for PullModelAction<'a> {
 
 #[cfg(feature = "stream")]
 fn parse_chunks(chunks: &[u8]) -> Result<Vec<PullModelStreamingResponse>, OllamaError> {
-    let chunks = std::str::from_utf8(&chunks).map_err(|e| {
+    let chunks = std::str::from_utf8(chunks).map_err(|e| {
         OllamaError::StreamDecodingError(format!("failed to parse chunk to utf8: {e}"))
     })?;
 
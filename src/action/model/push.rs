@@ -72,7 +72,7 @@ impl<'a> IntoStream<PushModelStreamingResponse> for PushModelAction<'a> {
                         Ok(chunk) => for c in chunk {
                             yield Ok(c)
                         },
-                        Err(e) => yield Err(e.into()),
+                        Err(e) => yield Err(e),
                     }
                     Err(e) => yield Err(OllamaError::DecodingError(e))
                 }
@@ -86,7 +86,7 @@ impl<'a> IntoStream<PushModelStreamingResponse> for PushModelAction<'a> {
 
 #[cfg(feature = "stream")]
 fn parse_chunks(chunks: &[u8]) -> Result<Vec<PushModelStreamingResponse>, OllamaError> {
-    let chunks = std::str::from_utf8(&chunks).map_err(|e| {
+    let chunks = std::str::from_utf8(chunks).map_err(|e| {
         OllamaError::StreamDecodingError(format!("failed to parse chunk to utf8: {e}"))
     })?;
 
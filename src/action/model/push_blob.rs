@@ -1,10 +1,11 @@
+use std::path::Path;
+
 use futures::future::BoxFuture;
 use reqwest::StatusCode;
-use tokio_util::codec::{BytesCodec, FramedRead};
 
 use crate::{
     abi::model::push_blob::PushBlobRequest,
-    action::{OllamaClient, OllamaRequest, parse_response},
+    action::{OllamaClient, parse_response},
     error::{OllamaError, OllamaServerError},
 };
 
@@ -26,9 +27,8 @@ impl<'a> IntoFuture for PushBlobAction<'a> {
 
     fn into_future(self) -> Self::IntoFuture {
         Box::pin(async move {
-            let url = format!("{}{}", self.ollama.url(), self.request.path());
-            let file_path = &self.request.file;
-            let reqwest_resp = upload_file(self.ollama, &url, file_path).await?;
+            let file = Path::new(self.request.file);
+            let reqwest_resp = self.ollama.upload(&self.request, file).await?;
             match reqwest_resp.status() {
                 StatusCode::CREATED => Ok(()),
                 StatusCode::BAD_REQUEST => Err(OllamaError::UnexpectedDigest),
@@ -40,25 +40,3 @@ impl<'a> IntoFuture for PushBlobAction<'a> {
         })
     }
 }
-
-async fn upload_file(
-    ollama: OllamaClient,
-    url: &str,
-    file_path: &str,
-) -> Result<reqwest::Response, OllamaError> {
-    let file = tokio::fs::File::open(file_path)
-        .await
-        .map_err(|e| OllamaError::FileError(e))?;
-
-    let stream = FramedRead::new(file, BytesCodec::new());
-    let body = reqwest::Body::wrap_stream(stream);
-
-    let response = ollama
-        .cli
-        .post(url)
-        .body(body)
-        .send()
-        .await
-        .map_err(|e| OllamaError::RequestError(e))?;
-    Ok(response)
-}
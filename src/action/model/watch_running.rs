@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_stream::stream;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    abi::model::{list_running::ListRunningModelsInfo, watch_running::RunningModelEvent},
+    action::{
+        IntoStream, OllamaClient, OllamaStream, model::list_running::ListRunningModelsAction,
+    },
+    error::OllamaError,
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+const DEFAULT_EXPIRY_THRESHOLD: Duration = Duration::from_secs(30);
+
+pub struct WatchRunningModelsAction {
+    ollama: OllamaClient,
+    interval: Duration,
+    expiry_threshold: Duration,
+}
+
+impl WatchRunningModelsAction {
+    pub fn new(ollama: OllamaClient) -> Self {
+        Self {
+            ollama,
+            interval: DEFAULT_INTERVAL,
+            expiry_threshold: DEFAULT_EXPIRY_THRESHOLD,
+        }
+    }
+
+    /// How often `/api/ps` is polled (default: 5s, minimum: 100ms).
+    #[inline]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(MIN_INTERVAL);
+        self
+    }
+
+    /// How long before `expires_at` an `ExpiringSoon` event is emitted (default: 30s).
+    #[inline]
+    pub fn expiry_threshold(mut self, expiry_threshold: Duration) -> Self {
+        self.expiry_threshold = expiry_threshold;
+        self
+    }
+}
+
+#[async_trait]
+impl IntoStream<RunningModelEvent> for WatchRunningModelsAction {
+    async fn stream(self) -> Result<OllamaStream<RunningModelEvent>, OllamaError> {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let s = stream! {
+            let mut state = WatchState::default();
+            loop {
+                ticker.tick().await;
+                match ListRunningModelsAction::new(self.ollama.clone()).await {
+                    Ok(resp) => {
+                        for event in state.update(resp.models, Utc::now(), self.expiry_threshold) {
+                            yield Ok(event);
+                        }
+                    }
+                    // Keep watching, the server may come back on the next poll.
+                    Err(e) => yield Err(e),
+                }
+            }
+        };
+
+        Ok(Box::pin(s))
+    }
+}
+
+/// The last observed `/api/ps` snapshot, keyed by model name.
+#[derive(Debug, Default)]
+struct WatchState {
+    models: HashMap<String, ListRunningModelsInfo>,
+    /// The `expires_at` for which an `ExpiringSoon` event has already been emitted.
+    warned: HashMap<String, DateTime<Utc>>,
+}
+
+impl WatchState {
+    fn update(
+        &mut self,
+        current: Vec<ListRunningModelsInfo>,
+        now: DateTime<Utc>,
+        expiry_threshold: Duration,
+    ) -> Vec<RunningModelEvent> {
+        let mut events = vec![];
+        let mut previous = std::mem::take(&mut self.models);
+
+        for model in current {
+            match previous.remove(&model.name) {
+                Some(prev) if prev.digest != model.digest => {
+                    self.warned.remove(&prev.name);
+                    events.push(RunningModelEvent::Unloaded { model: prev });
+                    events.push(RunningModelEvent::Loaded {
+                        model: model.clone(),
+                    });
+                }
+                Some(prev) if prev.size_vram != model.size_vram => {
+                    events.push(RunningModelEvent::VramChanged {
+                        model: model.clone(),
+                        previous_size_vram: prev.size_vram,
+                    });
+                }
+                Some(_) => {}
+                None => events.push(RunningModelEvent::Loaded {
+                    model: model.clone(),
+                }),
+            }
+
+            if let Ok(remaining) = (model.expires_at - now).to_std() {
+                let already_warned = self.warned.get(&model.name) == Some(&model.expires_at);
+                if remaining <= expiry_threshold && !already_warned {
+                    self.warned.insert(model.name.clone(), model.expires_at);
+                    events.push(RunningModelEvent::ExpiringSoon {
+                        model: model.clone(),
+                        remaining,
+                    });
+                }
+            }
+
+            self.models.insert(model.name.clone(), model);
+        }
+
+        let mut unloaded: Vec<ListRunningModelsInfo> = previous.into_values().collect();
+        unloaded.sort_by(|a, b| a.name.cmp(&b.name));
+        for model in unloaded {
+            self.warned.remove(&model.name);
+            events.push(RunningModelEvent::Unloaded { model });
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use tokio_stream::StreamExt;
+
+    use super::{MIN_INTERVAL, WatchState};
+    use crate::Ollama;
+    use crate::abi::model::{
+        list_running::ListRunningModelsInfo, watch_running::RunningModelEvent,
+    };
+    use crate::action::IntoStream;
+
+    fn model(
+        name: &str,
+        digest: &str,
+        size_vram: i64,
+        expires_at: DateTime<Utc>,
+    ) -> ListRunningModelsInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "model": name,
+            "size": 5137025024i64,
+            "digest": digest,
+            "details": {
+                "format": "gguf",
+                "family": "llama",
+                "families": ["llama"],
+                "parameter_size": "8.0B",
+                "quantization_level": "Q4_0"
+            },
+            "expires_at": expires_at,
+            "size_vram": size_vram
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn expires_at_should_be_parsed() {
+        let info: ListRunningModelsInfo = serde_json::from_str(
+            r#"{"name":"mistral:latest","model":"mistral:latest","size":5137025024,"digest":"2ae6f6dd7a3dd734790bbbf58b8909a606e0e7e97e94b7604e0aa7ae4490e6d8","details":{"parent_model":"","format":"gguf","family":"llama","families":["llama"],"parameter_size":"7.2B","quantization_level":"Q4_0"},"expires_at":"2024-06-04T14:38:31.83753-07:00","size_vram":5137025024}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            info.expires_at,
+            "2024-06-04T21:38:31.83753Z"
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn watch_state_should_emit_load_vram_and_unload_events() {
+        let now = Utc::now();
+        let later = now + TimeDelta::minutes(5);
+        let threshold = Duration::from_secs(30);
+        let mut state = WatchState::default();
+
+        let events = state.update(vec![model("llama3.1:8b", "a", 100, later)], now, threshold);
+        assert!(matches!(
+            events.as_slice(),
+            [RunningModelEvent::Loaded { .. }]
+        ));
+
+        let events = state.update(vec![model("llama3.1:8b", "a", 100, later)], now, threshold);
+        assert!(events.is_empty());
+
+        let events = state.update(vec![model("llama3.1:8b", "a", 200, later)], now, threshold);
+        match events.as_slice() {
+            [
+                RunningModelEvent::VramChanged {
+                    model,
+                    previous_size_vram,
+                },
+            ] => {
+                assert_eq!(model.size_vram, 200);
+                assert_eq!(*previous_size_vram, 100);
+            }
+            other => panic!("unexpected events: {other:?}"),
+        }
+
+        let events = state.update(vec![model("llama3.1:8b", "b", 200, later)], now, threshold);
+        assert!(matches!(
+            events.as_slice(),
+            [
+                RunningModelEvent::Unloaded { .. },
+                RunningModelEvent::Loaded { .. }
+            ]
+        ));
+
+        let events = state.update(vec![], now, threshold);
+        match events.as_slice() {
+            [RunningModelEvent::Unloaded { model }] => assert_eq!(model.digest, "b"),
+            other => panic!("unexpected events: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_state_should_emit_expiring_soon_once_per_expiry() {
+        let now = Utc::now();
+        let soon = now + TimeDelta::seconds(10);
+        let threshold = Duration::from_secs(30);
+        let mut state = WatchState::default();
+
+        let events = state.update(vec![model("llama3.1:8b", "a", 100, soon)], now, threshold);
+        assert!(matches!(
+            events.as_slice(),
+            [
+                RunningModelEvent::Loaded { .. },
+                RunningModelEvent::ExpiringSoon { .. }
+            ]
+        ));
+
+        let events = state.update(vec![model("llama3.1:8b", "a", 100, soon)], now, threshold);
+        assert!(events.is_empty());
+
+        // The keep alive has been refreshed, so the warning is re-armed.
+        let refreshed = soon + TimeDelta::seconds(5);
+        let events = state.update(
+            vec![model("llama3.1:8b", "a", 100, refreshed)],
+            now,
+            threshold,
+        );
+        match events.as_slice() {
+            [RunningModelEvent::ExpiringSoon { remaining, .. }] => {
+                assert_eq!(*remaining, Duration::from_secs(15))
+            }
+            other => panic!("unexpected events: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn zero_interval_should_be_clamped() {
+        let action = Ollama::new("http://127.0.0.1:1")
+            .watch_running_models()
+            .interval(Duration::ZERO);
+        assert_eq!(action.interval, MIN_INTERVAL);
+
+        // Nothing is listening, so the first poll reports an error instead of panicking.
+        let mut stream = action.stream().await.unwrap();
+        assert!(stream.next().await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Gathers what response bodies say about the model, the tokens and the timings as they are
+//! read, for the tracing spans and the metrics.
+
+use std::time::{Duration, Instant};
+
+use async_stream::stream;
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::action::build_response;
+use crate::error::OllamaError;
+
+/// The stats of a response. They are gathered from all chunks, as native streams report
+/// them in the last chunk and OpenAI-compatible ones report the finish reason and the usage
+/// in different chunks. Durations reported by Ollama are in seconds.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseStats {
+    pub model: Option<String>,
+    pub id: Option<String>,
+    pub finish_reasons: Vec<String>,
+    pub input_tokens: Option<i64>,
+    pub output_tokens: Option<i64>,
+    pub total_duration: Option<f64>,
+    pub load_duration: Option<f64>,
+    pub prompt_eval_duration: Option<f64>,
+    pub eval_duration: Option<f64>,
+
+    /// From sending the request to receiving the first chunk of the body.
+    pub first_chunk: Option<Duration>,
+
+    /// From sending the request to reading the body in full, or dropping it.
+    pub elapsed: Duration,
+
+    /// The type of the error that interrupted the body, see [`reqwest_error_type`].
+    pub error: Option<&'static str>,
+}
+
+impl ResponseStats {
+    /// The generation speed reported by Ollama.
+    #[cfg(feature = "metrics")]
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let tokens = self.output_tokens? as f64;
+        self.eval_duration
+            .filter(|seconds| *seconds > 0.0)
+            .map(|seconds| tokens / seconds)
+    }
+
+    /// Observe an NDJSON line, a server-sent event line or a whole JSON body, which Ollama
+    /// always sends on a single line.
+    fn observe_line(&mut self, line: &[u8]) {
+        let line = line.trim_ascii();
+        let line = line.strip_prefix(b"data:").unwrap_or(line).trim_ascii();
+        if line.is_empty() || line == b"[DONE]" {
+            return;
+        }
+        if let Ok(value) = serde_json::from_slice::<Value>(line) {
+            self.observe(&value);
+        }
+    }
+
+    fn observe(&mut self, value: &Value) {
+        let text = |value: &Value| value.as_str().map(str::to_string);
+        self.model = text(&value["model"]).or(self.model.take());
+        self.id = text(&value["id"]).or(self.id.take());
+
+        let reasons = value["choices"].as_array().into_iter().flatten();
+        let reasons = reasons.map(|choice| &choice["finish_reason"]);
+        for reason in std::iter::once(&value["done_reason"]).chain(reasons) {
+            if let Some(reason) = reason.as_str() {
+                self.finish_reasons.push(reason.to_string());
+            }
+        }
+
+        let count = |native: &str, openai: &str| {
+            value[native]
+                .as_i64()
+                .or_else(|| value["usage"][openai].as_i64())
+        };
+        self.input_tokens = count("prompt_eval_count", "prompt_tokens").or(self.input_tokens);
+        self.output_tokens = count("eval_count", "completion_tokens").or(self.output_tokens);
+
+        let seconds = |field: &str| value[field].as_i64().map(|nanos| nanos as f64 / 1e9);
+        self.total_duration = seconds("total_duration").or(self.total_duration);
+        self.load_duration = seconds("load_duration").or(self.load_duration);
+        self.prompt_eval_duration = seconds("prompt_eval_duration").or(self.prompt_eval_duration);
+        self.eval_duration = seconds("eval_duration").or(self.eval_duration);
+    }
+}
+
+/// Calls `done` with the stats once the body is dropped.
+struct Observer<F: FnOnce(ResponseStats)> {
+    stats: ResponseStats,
+    started: Instant,
+    done: Option<F>,
+}
+
+impl<F: FnOnce(ResponseStats)> Drop for Observer<F> {
+    fn drop(&mut self) {
+        if let Some(done) = self.done.take() {
+            self.stats.elapsed = self.started.elapsed();
+            done(std::mem::take(&mut self.stats));
+        }
+    }
+}
+
+/// Pass the response through, gathering the stats of its body. `done` is called when the
+/// body is dropped rather than when it ends, as readers may stop before its end, e.g. at
+/// `data: [DONE]`.
+pub(crate) fn observe(
+    response: reqwest::Response,
+    started: Instant,
+    done: impl FnOnce(ResponseStats) + Send + 'static,
+) -> reqwest::Response {
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+
+    let mut bytes = response.bytes_stream();
+    // Created outside the stream, so that a body dropped without being read is reported too.
+    let observer = Observer {
+        stats: ResponseStats::default(),
+        started,
+        done: Some(done),
+    };
+    let body = stream! {
+        let mut observer = observer;
+        let mut buffer: Vec<u8> = vec![];
+        while let Some(item) = bytes.next().await {
+            let chunk = match item {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    observer.stats.error = Some(reqwest_error_type(&e));
+                    yield Err(e);
+                    return;
+                }
+            };
+
+            if observer.stats.first_chunk.is_none() && !chunk.is_empty() {
+                observer.stats.first_chunk = Some(started.elapsed());
+            }
+
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                observer.stats.observe_line(&line);
+            }
+            yield Ok(chunk);
+        }
+
+        observer.stats.observe_line(&buffer);
+    };
+
+    // The status and headers are those of a response, so they are valid.
+    build_response(status, headers, reqwest::Body::wrap_stream(body)).unwrap()
+}
+
+/// A short, low-cardinality description of why a request failed.
+pub(crate) fn error_type(error: &OllamaError) -> &'static str {
+    match error {
+        OllamaError::RequestError(e) => reqwest_error_type(e),
+        _ => "_OTHER",
+    }
+}
+
+pub(crate) fn reqwest_error_type(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "timeout"
+    } else if error.is_connect() {
+        "connect"
+    } else if error.is_body() || error.is_decode() {
+        "body"
+    } else {
+        "request"
+    }
+}
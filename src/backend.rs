@@ -0,0 +1,368 @@
+//! Backend-agnostic chat and embedding traits, so that application code can be written
+//! against [`ChatModel`] and [`EmbeddingModel`] rather than the request builders, and run
+//! against [`OllamaModel`] in production and [`FakeModel`] in tests.
+//!
+//! # Example
+//! ```rust,ignore
+//! use ollama_native::backend::{ChatModel, FakeModel, OllamaModel};
+//!
+//! async fn greet(model: &dyn ChatModel) -> Result<String, OllamaError> {
+//!     let response = model.chat(&[Message::user("Hello!")], &Options::default()).await?;
+//!     Ok(response.message.map(|m| m.content).unwrap_or_default())
+//! }
+//!
+//! greet(&OllamaModel::new(ollama, "llama3.1:8b")).await?;
+//! greet(&FakeModel::new().respond("Hi!")).await?;
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::DateTime;
+
+use crate::{
+    ChatCompletionResponse, GenerateEmbeddingsResponse, Ollama,
+    abi::{Message, Options, Role, Usage},
+    action::{IntoStream, OllamaStream},
+    error::OllamaError,
+};
+
+const DEFAULT_FAKE_DIMENSIONS: usize = 16;
+
+/// A model that answers chats.
+#[async_trait]
+pub trait ChatModel: Send + Sync {
+    /// The next message of the chat.
+    async fn chat(
+        &self,
+        messages: &[Message],
+        options: &Options,
+    ) -> Result<ChatCompletionResponse, OllamaError>;
+
+    /// The next message of the chat as a stream of chunks, the last one being `done`.
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        options: &Options,
+    ) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError>;
+}
+
+/// A model that embeds texts.
+#[async_trait]
+pub trait EmbeddingModel: Send + Sync {
+    /// One embedding per text, in order.
+    async fn embed(&self, texts: &[&str]) -> Result<GenerateEmbeddingsResponse, OllamaError>;
+}
+
+/// An [`Ollama`] client bound to a model name.
+#[derive(Clone)]
+pub struct OllamaModel {
+    ollama: Ollama,
+    model: String,
+}
+
+impl OllamaModel {
+    pub fn new(ollama: Ollama, model: impl ToString) -> Self {
+        Self {
+            ollama,
+            model: model.to_string(),
+        }
+    }
+
+    /// The name of the model requests are sent to.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[async_trait]
+impl ChatModel for OllamaModel {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        options: &Options,
+    ) -> Result<ChatCompletionResponse, OllamaError> {
+        self.ollama
+            .chat(&self.model)
+            .messages(messages.to_vec())
+            .options(options.clone())
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        options: &Options,
+    ) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError> {
+        self.ollama
+            .chat(&self.model)
+            .messages(messages.to_vec())
+            .options(options.clone())
+            .stream()
+            .await
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for OllamaModel {
+    async fn embed(&self, texts: &[&str]) -> Result<GenerateEmbeddingsResponse, OllamaError> {
+        self.ollama
+            .generate_embeddings(&self.model)
+            .inputs(texts.to_vec())
+            .await
+    }
+}
+
+/// A deterministic model for tests.
+///
+/// Chats are answered with the scripted responses in order, then with `echo: <last user
+/// message>`. Streams send one word per chunk. Token counts are word counts and every
+/// response is created at the Unix epoch.
+///
+/// Embeddings are normalized bags of hashed, lowercased words, so texts sharing words are
+/// closer to each other and the same text always gets the same embedding.
+pub struct FakeModel {
+    name: String,
+    dimensions: usize,
+    responses: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<Vec<Message>>>,
+}
+
+impl Default for FakeModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeModel {
+    pub fn new() -> Self {
+        Self {
+            name: "fake".to_string(),
+            dimensions: DEFAULT_FAKE_DIMENSIONS,
+            responses: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(vec![]),
+        }
+    }
+
+    /// The model name reported in responses (default: `fake`).
+    #[inline]
+    pub fn name(mut self, name: impl ToString) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// The number of dimensions of the embeddings (default: 16).
+    #[inline]
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions.max(1);
+        self
+    }
+
+    /// Queue a response for the next chat that has no response queued yet.
+    #[inline]
+    pub fn respond(self, response: impl ToString) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .push_back(response.to_string());
+        self
+    }
+
+    /// The messages of every chat so far, in order.
+    pub fn requests(&self) -> Vec<Vec<Message>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn answer(&self, messages: &[Message]) -> (String, Usage) {
+        self.requests.lock().unwrap().push(messages.to_vec());
+        let answer = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| {
+                let question = messages.iter().rev().find(|m| m.role == Role::User);
+                format!("echo: {}", question.map_or("", |m| m.content.as_str()))
+            });
+
+        let words = |text: &str| text.split_whitespace().count() as i64;
+        let usage = Usage {
+            prompt_eval_count: Some(messages.iter().map(|m| words(&m.content)).sum()),
+            eval_count: Some(words(&answer)),
+            ..Default::default()
+        };
+        (answer, usage)
+    }
+
+    fn response(&self, content: &str, done: bool, usage: Usage) -> ChatCompletionResponse {
+        ChatCompletionResponse {
+            model: self.name.clone(),
+            created_at: DateTime::UNIX_EPOCH,
+            message: Some(Message::assistant(content)),
+            done_reason: done.then(|| "stop".to_string()),
+            done,
+            usage,
+        }
+    }
+
+    fn embedding(&self, text: &str) -> Vec<f64> {
+        let mut embedding = vec![0.0; self.dimensions];
+        for word in text.split_whitespace() {
+            let hash = fnv1a(word.to_lowercase().as_bytes());
+            embedding[(hash % self.dimensions as u64) as usize] += 1.0;
+        }
+
+        let norm = embedding.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        embedding
+    }
+}
+
+#[async_trait]
+impl ChatModel for FakeModel {
+    async fn chat(
+        &self,
+        messages: &[Message],
+        _options: &Options,
+    ) -> Result<ChatCompletionResponse, OllamaError> {
+        let (answer, usage) = self.answer(messages);
+        Ok(self.response(&answer, true, usage))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        _options: &Options,
+    ) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError> {
+        let (answer, usage) = self.answer(messages);
+        let mut chunks: Vec<_> = answer
+            .split_inclusive(' ')
+            .map(|word| Ok(self.response(word, false, Usage::default())))
+            .collect();
+        chunks.push(Ok(self.response("", true, usage)));
+        Ok(Box::pin(tokio_stream::iter(chunks)))
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for FakeModel {
+    async fn embed(&self, texts: &[&str]) -> Result<GenerateEmbeddingsResponse, OllamaError> {
+        let words: usize = texts.iter().map(|t| t.split_whitespace().count()).sum();
+        Ok(GenerateEmbeddingsResponse {
+            model: self.name.clone(),
+            embeddings: texts.iter().map(|text| self.embedding(text)).collect(),
+            usage: Usage {
+                prompt_eval_count: Some(words as i64),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// 64-bit FNV-1a, stable across platforms and releases unlike the standard hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::{ChatModel, EmbeddingModel, FakeModel, OllamaModel};
+    use crate::abi::{Message, Options};
+    use crate::embeddings::cosine_similarity;
+    use crate::mock::MockOllama;
+
+    async fn ask(model: &dyn ChatModel, question: &str) -> String {
+        let messages = [Message::system("Be brief."), Message::user(question)];
+        let response = model.chat(&messages, &Options::default()).await.unwrap();
+        response.message.unwrap().content
+    }
+
+    async fn streamed(model: &dyn ChatModel, question: &str) -> (String, Option<i64>) {
+        let chunks: Vec<_> = model
+            .chat_stream(&[Message::user(question)], &Options::default())
+            .await
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        let content = chunks
+            .iter()
+            .map(|c| c.message.as_ref().unwrap().content.as_str())
+            .collect();
+        let last = chunks.last().unwrap();
+        assert!(last.done);
+        (content, last.usage.eval_count)
+    }
+
+    #[tokio::test]
+    async fn fake_model_should_answer_scripted_then_echo() {
+        let fake = FakeModel::new().respond("first").respond("second answer");
+
+        assert_eq!(ask(&fake, "a").await, "first");
+        assert_eq!(
+            streamed(&fake, "b").await,
+            ("second answer".to_string(), Some(2))
+        );
+        assert_eq!(ask(&fake, "hello there").await, "echo: hello there");
+
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2][1], Message::user("hello there"));
+    }
+
+    #[tokio::test]
+    async fn fake_embeddings_should_be_deterministic() {
+        let fake = FakeModel::new().dimensions(64);
+        let response = fake
+            .embed(&["the cat sat", "The cat sat", "a dog ran far away", ""])
+            .await
+            .unwrap();
+        let [a, b, c, empty] = response.embeddings.as_slice() else {
+            panic!("expected four embeddings");
+        };
+
+        assert_eq!(a.len(), 64);
+        assert_eq!(a, b);
+        assert!((cosine_similarity(a, a) - 1.0).abs() < 1e-9);
+        assert!(cosine_similarity(a, c) < 0.5);
+        assert!(empty.iter().all(|x| *x == 0.0));
+        assert_eq!(response.usage.prompt_eval_count, Some(11));
+
+        let again = FakeModel::new()
+            .dimensions(64)
+            .embed(&["the cat sat"])
+            .await;
+        assert_eq!(&again.unwrap().embeddings[0], a);
+    }
+
+    #[tokio::test]
+    async fn ollama_model_should_implement_traits() {
+        let mock = MockOllama::start().await;
+        let chat = OllamaModel::new(mock.ollama(), "llama3.1:8b");
+        let embed: Box<dyn EmbeddingModel> =
+            Box::new(OllamaModel::new(mock.ollama(), "all-minilm"));
+
+        assert_eq!(ask(&chat, "hello there").await, "echo: hello there");
+        assert_eq!(
+            streamed(&chat, "hello there").await,
+            ("echo: hello there".to_string(), Some(3))
+        );
+        assert_eq!(
+            mock.requests("/api/chat")[0]["messages"][0]["role"],
+            "system"
+        );
+
+        let response = embed.embed(&["a b", "c"]).await.unwrap();
+        assert_eq!(
+            response.embeddings,
+            vec![vec![3.0, 2.0, 1.0], vec![1.0, 1.0, 1.0]]
+        );
+    }
+}
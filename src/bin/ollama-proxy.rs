@@ -0,0 +1,46 @@
+//! Serves the OpenAI-compatible API of [`ollama_native::server`] in front of an Ollama
+//! server, logging every request to stderr.
+//!
+//! Configured through the environment:
+//! - `OLLAMA_HOST`: the Ollama server to forward to (default: `http://127.0.0.1:11434`).
+//! - `PROXY_ADDR`: the address to listen on (default: `127.0.0.1:8080`).
+//! - `PROXY_API_KEY`: if set, the bearer token clients must send.
+
+use std::time::Instant;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use ollama_native::{Ollama, server::Server};
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let host = std::env::var("OLLAMA_HOST").unwrap_or_else(|_| "127.0.0.1:11434".to_string());
+    let host = match host.contains("://") {
+        true => host,
+        false => format!("http://{host}"),
+    };
+    let addr = std::env::var("PROXY_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+
+    let mut server = Server::new(Ollama::new(host.trim_end_matches('/')));
+    if let Ok(api_key) = std::env::var("PROXY_API_KEY") {
+        server = server.api_key(api_key);
+    }
+    let router = server.router().layer(axum::middleware::from_fn(log));
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    eprintln!("listening on {addr}, forwarding to {host}");
+    axum::serve(listener, router).await
+}
+
+/// Logs the method, path, status and time to the response headers of each request.
+async fn log(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    eprintln!(
+        "{method} {path} {} {:?}",
+        response.status().as_u16(),
+        start.elapsed()
+    );
+    response
+}
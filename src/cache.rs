@@ -0,0 +1,725 @@
+//! An opt-in cache for chat and generate responses, for runs that send the same requests
+//! over and over, such as evals and CI with a fixed `seed` and a `temperature` of 0.
+//!
+//! Entries are keyed by the model digest and the serialized request, so any change to the
+//! messages, prompt, images, format or options is a miss. Responses are stored as the chunks
+//! they were received in: streaming calls replay them as a stream, non-streaming calls get
+//! them merged into one response, whichever way they were first fetched.
+//!
+//! # Example
+//! ```rust,ignore
+//! use std::{sync::Arc, time::Duration};
+//! use ollama_native::cache::DirectoryResponseCache;
+//!
+//! let cache = Arc::new(DirectoryResponseCache::new(".cache/ollama")?.ttl(Duration::from_secs(86400)));
+//! let response = ollama
+//!     .chat("llama3.1:8b")
+//!     .user_message("Tell me a joke about sharks")
+//!     .seed(42)
+//!     .temperature(0.0)
+//!     .cache(cache.clone())
+//!     .await?;
+//! println!("{:?}", cache.stats());
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::abi::{
+    KeepAlive, Message, Role,
+    completion::{
+        chat::{ChatCompletionRequest, ChatCompletionResponse},
+        generate::{GenerateCompletionRequest, GenerateCompletionResponse},
+    },
+};
+use crate::error::OllamaError;
+use crate::storage::{Lru, write_atomic};
+
+#[cfg(feature = "stream")]
+use {crate::action::OllamaStream, async_stream::stream, std::sync::Arc, tokio_stream::StreamExt};
+
+/// Identifies a response by the kind of request, the model digest and the request itself.
+/// Whether the request is streamed and how long the model is kept alive are left out, as
+/// they do not change the response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResponseKey(String);
+
+impl ResponseKey {
+    pub fn chat(
+        model_digest: &str,
+        request: &ChatCompletionRequest<'_>,
+    ) -> Result<Self, OllamaError> {
+        let request = ChatCompletionRequest {
+            stream: false,
+            keep_alive: KeepAlive::default(),
+            ..request.clone()
+        };
+        Self::new("chat", model_digest, &request)
+    }
+
+    pub fn generate(
+        model_digest: &str,
+        request: &GenerateCompletionRequest<'_>,
+    ) -> Result<Self, OllamaError> {
+        let request = GenerateCompletionRequest {
+            stream: false,
+            keep_alive: KeepAlive::default(),
+            ..request.clone()
+        };
+        Self::new("generate", model_digest, &request)
+    }
+
+    fn new(kind: &str, model_digest: &str, request: &impl Serialize) -> Result<Self, OllamaError> {
+        let request =
+            serde_json::to_vec(request).map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+
+        // Length prefixes keep the fields from running into each other.
+        let mut hasher = Sha256::new();
+        for field in [kind.as_bytes(), model_digest.as_bytes(), &request] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field);
+        }
+
+        let hash = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(Self(hash))
+    }
+
+    /// The key as a lowercase hex SHA-256 digest.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ResponseKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A cached response: the chunks it was received in, a single one if it was not streamed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub stored_at: DateTime<Utc>,
+    pub chunks: Vec<Value>,
+}
+
+impl CacheEntry {
+    pub fn new<T: Serialize>(chunks: &[T]) -> Result<Self, OllamaError> {
+        let chunks = chunks
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()
+            .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        Ok(Self {
+            stored_at: Utc::now(),
+            chunks,
+        })
+    }
+
+    /// Whether the entry is older than `ttl`.
+    pub fn is_expired(&self, ttl: Option<Duration>) -> bool {
+        let Some(ttl) = ttl else {
+            return false;
+        };
+        let age = (Utc::now() - self.stored_at).to_std().unwrap_or_default();
+        age >= ttl
+    }
+
+    fn decode<T: DeserializeOwned>(self) -> Result<Vec<T>, OllamaError> {
+        self.chunks
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()
+            .map_err(|e| OllamaError::InvalidFormat(format!("corrupted cache entry: {e}")))
+    }
+}
+
+/// Counts of cache lookups and writes since the cache was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Lookups that found nothing, including expired entries.
+    pub misses: u64,
+    /// Entries found but older than the TTL.
+    pub expired: u64,
+    pub writes: u64,
+}
+
+impl CacheStats {
+    /// The share of lookups that hit, `None` before the first lookup.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    expired: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl Counters {
+    /// Counts the lookup and drops expired entries.
+    fn lookup(&self, entry: Option<CacheEntry>, ttl: Option<Duration>) -> Option<CacheEntry> {
+        let expired = entry.as_ref().is_some_and(|e| e.is_expired(ttl));
+        if expired {
+            self.expired.fetch_add(1, Ordering::Relaxed);
+        }
+        let entry = entry.filter(|_| !expired);
+        let counter = match entry {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Storage for responses, used through
+/// [`ChatAction::cache`][`crate::action::completion::chat::ChatAction::cache`] and
+/// [`GenerateAction::cache`][`crate::action::completion::generate::GenerateAction::cache`].
+/// Implementations are expected to treat expired entries as misses.
+#[async_trait]
+pub trait ResponseCache: Send + Sync {
+    async fn get(&self, key: &ResponseKey) -> Result<Option<CacheEntry>, OllamaError>;
+
+    async fn put(&self, key: &ResponseKey, entry: &CacheEntry) -> Result<(), OllamaError>;
+
+    fn stats(&self) -> CacheStats {
+        CacheStats::default()
+    }
+}
+
+/// An in-memory cache evicting the least recently used response once full.
+pub struct MemoryResponseCache {
+    ttl: Option<Duration>,
+    inner: Mutex<Lru<ResponseKey, CacheEntry>>,
+    counters: Counters,
+}
+
+impl MemoryResponseCache {
+    /// Creates a cache holding at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ttl: None,
+            inner: Mutex::new(Lru::new(capacity)),
+            counters: Counters::default(),
+        }
+    }
+
+    /// Expire responses once they are older than `ttl`.
+    #[inline]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl ResponseCache for MemoryResponseCache {
+    async fn get(&self, key: &ResponseKey) -> Result<Option<CacheEntry>, OllamaError> {
+        let mut lru = self.inner.lock().unwrap();
+        let entry = self.counters.lookup(lru.get(key).cloned(), self.ttl);
+        if entry.is_none() {
+            lru.remove(key);
+        }
+        Ok(entry)
+    }
+
+    async fn put(&self, key: &ResponseKey, entry: &CacheEntry) -> Result<(), OllamaError> {
+        let mut lru = self.inner.lock().unwrap();
+        if lru.insert(key.clone(), entry.clone()) {
+            self.counters.writes.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.counters.stats()
+    }
+}
+
+/// An on-disk cache storing one JSON file per response under a two character fan-out
+/// directory (`<root>/ab/abcdef....json`). Expired and corrupted entries are deleted when
+/// looked up.
+pub struct DirectoryResponseCache {
+    root: PathBuf,
+    ttl: Option<Duration>,
+    counters: Counters,
+}
+
+impl DirectoryResponseCache {
+    /// Opens the cache at `root`, creating the directory if needed.
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, OllamaError> {
+        let root = root.into();
+        std::fs::create_dir_all(&root).map_err(OllamaError::FileError)?;
+        Ok(Self {
+            root,
+            ttl: None,
+            counters: Counters::default(),
+        })
+    }
+
+    /// Expire responses once they are older than `ttl`.
+    #[inline]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &ResponseKey) -> PathBuf {
+        self.root
+            .join(&key.as_str()[..2])
+            .join(format!("{key}.json"))
+    }
+}
+
+#[async_trait]
+impl ResponseCache for DirectoryResponseCache {
+    async fn get(&self, key: &ResponseKey) -> Result<Option<CacheEntry>, OllamaError> {
+        let path = self.path(key);
+        let entry = match tokio::fs::read(&path).await {
+            Ok(bytes) => match serde_json::from_slice::<CacheEntry>(&bytes) {
+                Ok(entry) => Some(entry),
+                // A corrupted entry is a miss, deleted so that it can be stored again.
+                Err(_) => {
+                    let _ = tokio::fs::remove_file(&path).await;
+                    None
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(OllamaError::FileError(e)),
+        };
+
+        let found = entry.is_some();
+        let entry = self.counters.lookup(entry, self.ttl);
+        if found && entry.is_none() {
+            // Another process may have removed it already.
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        Ok(entry)
+    }
+
+    async fn put(&self, key: &ResponseKey, entry: &CacheEntry) -> Result<(), OllamaError> {
+        let path = self.path(key);
+        let bytes =
+            serde_json::to_vec(entry).map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        tokio::task::spawn_blocking(move || write_atomic(&path, &bytes))
+            .await
+            .map_err(|e| OllamaError::FileError(e.into()))?
+            .map_err(OllamaError::FileError)?;
+        self.counters.writes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.counters.stats()
+    }
+}
+
+/// A response that can be cached as chunks and rebuilt from them.
+pub(crate) trait Cacheable: Serialize + DeserializeOwned + Send + 'static {
+    /// Merges streamed chunks into the single response a non-streaming request returns.
+    fn merge(chunks: Vec<Self>) -> Option<Self>;
+}
+
+impl Cacheable for ChatCompletionResponse {
+    fn merge(chunks: Vec<Self>) -> Option<Self> {
+        let mut content = String::new();
+        let mut thinking = String::new();
+        let mut tool_calls = vec![];
+        let mut role = Role::Assistant;
+        let mut last = None;
+        for chunk in chunks {
+            if let Some(message) = &chunk.message {
+                role = message.role.clone();
+                content.push_str(&message.content);
+                thinking.push_str(message.thinking.as_deref().unwrap_or_default());
+                tool_calls.extend(message.tool_calls.iter().flatten().cloned());
+            }
+            last = Some(chunk);
+        }
+
+        let mut response = last?;
+        response.message = Some(Message {
+            role,
+            content,
+            thinking: (!thinking.is_empty()).then_some(thinking),
+            images: None,
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+        });
+        Some(response)
+    }
+}
+
+impl Cacheable for GenerateCompletionResponse {
+    fn merge(chunks: Vec<Self>) -> Option<Self> {
+        let mut text = String::new();
+        let mut thinking = String::new();
+        let mut last = None;
+        for chunk in chunks {
+            text.push_str(&chunk.response);
+            thinking.push_str(chunk.thinking.as_deref().unwrap_or_default());
+            last = Some(chunk);
+        }
+
+        let mut response = last?;
+        response.response = text;
+        response.thinking = (!thinking.is_empty()).then_some(thinking);
+        Some(response)
+    }
+}
+
+/// Serve the response from the cache, or fetch and store it. The cache failing to read or
+/// store the entry does not fail the request.
+pub(crate) async fn cached<T: Cacheable>(
+    cache: &dyn ResponseCache,
+    key: &ResponseKey,
+    fetch: impl Future<Output = Result<T, OllamaError>>,
+) -> Result<T, OllamaError> {
+    // An entry that cannot be read or decoded is fetched again and overwritten.
+    if let Ok(Some(entry)) = cache.get(key).await
+        && let Some(response) = entry.decode().ok().and_then(T::merge)
+    {
+        return Ok(response);
+    }
+
+    let response = fetch.await?;
+    if let Ok(entry) = CacheEntry::new(std::slice::from_ref(&response)) {
+        let _ = cache.put(key, &entry).await;
+    }
+    Ok(response)
+}
+
+/// Replay the chunks from the cache, or pass the fetched stream through and store its
+/// chunks once it completes. Streams that fail or end before a `done` chunk are not stored,
+/// and the cache failing to read or store the entry does not fail the stream.
+#[cfg(feature = "stream")]
+pub(crate) async fn cached_stream<T: Cacheable>(
+    cache: Arc<dyn ResponseCache>,
+    key: ResponseKey,
+    fetch: impl Future<Output = Result<OllamaStream<T>, OllamaError>>,
+) -> Result<OllamaStream<T>, OllamaError> {
+    if let Ok(Some(entry)) = cache.get(&key).await
+        && let Ok(chunks) = entry.decode::<T>()
+    {
+        return Ok(Box::pin(tokio_stream::iter(chunks.into_iter().map(Ok))));
+    }
+
+    let mut live = fetch.await?;
+    let s = stream! {
+        let mut chunks: Vec<Value> = vec![];
+        let mut complete = true;
+        while let Some(item) = live.next().await {
+            match &item {
+                Ok(chunk) => match serde_json::to_value(chunk) {
+                    Ok(chunk) => chunks.push(chunk),
+                    Err(_) => complete = false,
+                },
+                Err(_) => complete = false,
+            }
+            yield item;
+        }
+
+        let done = chunks.last().is_some_and(|chunk| chunk["done"] == true);
+        if complete && done {
+            let entry = CacheEntry {
+                stored_at: Utc::now(),
+                chunks,
+            };
+            let _ = cache.put(&key, &entry).await;
+        }
+    };
+    Ok(Box::pin(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use serde_json::json;
+    use tokio_stream::StreamExt;
+
+    use super::{
+        CacheEntry, CacheStats, DirectoryResponseCache, MemoryResponseCache, ResponseCache,
+        ResponseKey,
+    };
+    use crate::abi::completion::chat::ChatCompletionRequest;
+    use crate::abi::{KeepAlive, Message};
+    use crate::action::IntoStream;
+    use crate::error::OllamaError;
+    use crate::mock::MockOllama;
+
+    /// A cache that fails every read and write.
+    struct BrokenCache;
+
+    #[async_trait]
+    impl ResponseCache for BrokenCache {
+        async fn get(&self, _key: &ResponseKey) -> Result<Option<CacheEntry>, OllamaError> {
+            Err(OllamaError::FileError(
+                std::io::ErrorKind::PermissionDenied.into(),
+            ))
+        }
+
+        async fn put(&self, _key: &ResponseKey, _entry: &CacheEntry) -> Result<(), OllamaError> {
+            Err(OllamaError::FileError(
+                std::io::ErrorKind::ReadOnlyFilesystem.into(),
+            ))
+        }
+    }
+
+    fn key(content: &str) -> ResponseKey {
+        let mut request = ChatCompletionRequest::new("llama3.1:8b");
+        request.messages.push(Message::user(content));
+        ResponseKey::chat("sha256:abc", &request).unwrap()
+    }
+
+    fn entry(text: &str) -> CacheEntry {
+        CacheEntry::new(&[json!({ "response": text, "done": true })]).unwrap()
+    }
+
+    #[test]
+    fn response_key_should_ignore_stream_and_keep_alive() {
+        let mut request = ChatCompletionRequest::new("llama3.1:8b");
+        request.messages.push(Message::user("hi"));
+        let base = ResponseKey::chat("sha256:abc", &request).unwrap();
+        assert_eq!(base, key("hi"));
+        assert_eq!(base.as_str().len(), 64);
+
+        let mut streamed = request.clone();
+        streamed.stream = true;
+        streamed.keep_alive = KeepAlive::UnloadNow;
+        assert_eq!(base, ResponseKey::chat("sha256:abc", &streamed).unwrap());
+
+        let mut seeded = request.clone();
+        seeded.options.seed(42);
+        assert_ne!(base, ResponseKey::chat("sha256:abc", &seeded).unwrap());
+        assert_ne!(base, ResponseKey::chat("sha256:abd", &request).unwrap());
+        assert_ne!(base, key("hi!"));
+    }
+
+    #[tokio::test]
+    async fn memory_cache_should_expire_and_evict() {
+        let cache = MemoryResponseCache::new(2).ttl(Duration::from_secs(60));
+        cache.put(&key("a"), &entry("a")).await.unwrap();
+        cache.put(&key("b"), &entry("b")).await.unwrap();
+
+        // Reading "a" makes "b" the least recently used.
+        let a = cache.get(&key("a")).await.unwrap().unwrap();
+        assert_eq!(a.chunks, entry("a").chunks);
+        cache.put(&key("c"), &entry("c")).await.unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("b")).await.unwrap().is_none());
+        assert!(cache.get(&key("c")).await.unwrap().is_some());
+
+        let mut stale = entry("d");
+        stale.stored_at = Utc::now() - chrono::Duration::seconds(61);
+        cache.put(&key("d"), &stale).await.unwrap();
+        assert!(cache.get(&key("d")).await.unwrap().is_none());
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 2,
+                expired: 1,
+                writes: 4,
+            }
+        );
+        assert_eq!(cache.stats().hit_rate(), Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn directory_cache_should_persist_and_expire() {
+        let root = std::env::temp_dir().join(format!(
+            "ollama-native-response-cache-{}",
+            std::process::id()
+        ));
+        let cache = DirectoryResponseCache::new(&root).unwrap();
+
+        assert!(cache.get(&key("a")).await.unwrap().is_none());
+        let stored = entry("a");
+        cache.put(&key("a"), &stored).await.unwrap();
+        assert_eq!(cache.get(&key("a")).await.unwrap(), Some(stored.clone()));
+
+        // A new handle on the same directory sees the stored entries, unless they expired.
+        let reopened = DirectoryResponseCache::new(&root).unwrap();
+        assert_eq!(reopened.get(&key("a")).await.unwrap(), Some(stored));
+        let expiring = DirectoryResponseCache::new(&root)
+            .unwrap()
+            .ttl(Duration::ZERO);
+        assert!(expiring.get(&key("a")).await.unwrap().is_none());
+        assert!(cache.get(&key("a")).await.unwrap().is_none());
+        assert_eq!(expiring.stats().expired, 1);
+
+        // A corrupted entry is a miss, and is deleted.
+        let path = cache.path(&key("b"));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"{\"stored_at\":").unwrap();
+        assert!(cache.get(&key("b")).await.unwrap().is_none());
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn chat_should_be_served_from_cache() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let cache = Arc::new(MemoryResponseCache::new(10));
+
+        let chat = || {
+            ollama
+                .chat("llama3.1:8b")
+                .user_message("hello there")
+                .seed(42)
+                .cache(cache.clone())
+        };
+
+        let first = chat().await.unwrap();
+        let second = chat().await.unwrap();
+        assert_eq!(
+            serde_json::to_value(&first).unwrap(),
+            serde_json::to_value(&second).unwrap()
+        );
+
+        // The non-streamed response is replayed as a single done chunk.
+        let chunks: Vec<_> = chat()
+            .stream()
+            .await
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].message, first.message);
+        assert!(chunks[0].done);
+
+        assert_eq!(mock.requests("/api/chat").len(), 1);
+        // The digest is looked up once per call.
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[tokio::test]
+    async fn streams_should_be_cached_and_replayed() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let cache = Arc::new(MemoryResponseCache::new(10));
+
+        let generate = || {
+            ollama
+                .generate("llama3.1:8b")
+                .prompt("why is the sky blue")
+                .model_digest("sha256:46e0c10c039e")
+                .cache(cache.clone())
+        };
+        let collect = || async {
+            generate()
+                .stream()
+                .await
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .await
+                .unwrap()
+        };
+
+        let live = collect().await;
+        let replayed = collect().await;
+        assert_eq!(live.len(), 7);
+        assert_eq!(
+            serde_json::to_value(&live).unwrap(),
+            serde_json::to_value(&replayed).unwrap()
+        );
+
+        // A non-streaming call gets the chunks merged.
+        let merged = generate().await.unwrap();
+        assert_eq!(merged.response, "echo: why is the sky blue");
+        assert!(merged.done);
+        assert_eq!(merged.usage.eval_count, Some(6));
+        assert_eq!(mock.requests("/api/generate").len(), 1);
+        assert!(mock.requests("/api/tags").is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_streams_should_not_be_cached() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let cache = Arc::new(MemoryResponseCache::new(10));
+
+        mock.fail_next(1);
+        let failed: Vec<_> = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello")
+            .model_digest("sha256:46e0c10c039e")
+            .cache(cache.clone())
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(failed.iter().any(Result::is_err));
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cache_failures_should_not_fail_requests() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let cache: Arc<dyn ResponseCache> = Arc::new(BrokenCache);
+
+        let chat = || {
+            ollama
+                .chat("llama3.1:8b")
+                .user_message("hello")
+                .model_digest("sha256:46e0c10c039e")
+                .cache(cache.clone())
+        };
+        chat().await.unwrap();
+        let chunks: Vec<_> = chat().stream().await.unwrap().collect().await;
+        assert!(!chunks.is_empty());
+        assert!(chunks.iter().all(Result::is_ok));
+        assert_eq!(mock.requests("/api/chat").len(), 2);
+    }
+}
@@ -0,0 +1,532 @@
+//! Record and replay HTTP exchanges with Ollama, for regression tests that run offline.
+//!
+//! In [`Cassette::record`] mode every request sent through the client goes to the server
+//! and the exchange is appended to the cassette file once the response body has been read
+//! in full. The body is stored as the chunks it was received in, so streamed NDJSON keeps
+//! its chunk boundaries. In [`Cassette::replay`] mode requests are matched on method, path
+//! and JSON body against the recorded ones and answered from the file, each recorded
+//! exchange at most once and in order. Requests that do not match are sent to the server,
+//! or fail with [`OllamaError::UnmatchedRequest`] in [`Cassette::strict`] mode.
+//!
+//! Redaction hooks run on every exchange before it is recorded, and on every request before
+//! it is matched, so a request can be matched against its redacted recording.
+//!
+//! Blob uploads (`push_blob`) are recorded without the uploaded file, so they are matched on
+//! their digest alone.
+//!
+//! # Example
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use ollama_native::{Ollama, cassette::Cassette};
+//!
+//! let cassette = Cassette::replay("tests/cassettes/chat.json")?.strict(true);
+//! let ollama = Ollama::new("http://localhost:11434").cassette(Arc::new(cassette));
+//! let response = ollama.chat("llama3.1:8b").user_message("Why is the sky blue?").await?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_stream::stream;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytes::Bytes;
+use futures::StreamExt;
+use reqwest::{
+    Method,
+    header::{HeaderName, HeaderValue},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::action::build_response;
+use crate::error::OllamaError;
+
+type Redactor = Box<dyn Fn(&mut Interaction) + Send + Sync>;
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Whether a cassette sends requests to the server or answers them from its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// A request and the response it got.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// What a request is matched on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+
+    /// The JSON body, `None` for `GET` requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+
+    /// The body, in the chunks it was received in.
+    pub chunks: Vec<Chunk>,
+}
+
+/// A piece of a response body, stored as text unless it is not valid UTF-8, which happens
+/// when a chunk boundary falls inside a character.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Chunk {
+    Text(String),
+    Binary { base64: String },
+}
+
+impl From<&[u8]> for Chunk {
+    fn from(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Chunk::Text(text.to_string()),
+            Err(_) => Chunk::Binary {
+                base64: STANDARD.encode(bytes),
+            },
+        }
+    }
+}
+
+impl Chunk {
+    fn to_bytes(&self) -> Result<Bytes, OllamaError> {
+        match self {
+            Chunk::Text(text) => Ok(Bytes::from(text.clone())),
+            Chunk::Binary { base64 } => STANDARD
+                .decode(base64)
+                .map(Bytes::from)
+                .map_err(|e| OllamaError::InvalidFormat(e.to_string())),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+struct Tape {
+    interactions: Vec<Interaction>,
+    played: Vec<bool>,
+}
+
+/// A file of recorded exchanges, see the [module documentation](self).
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    strict: bool,
+    redactors: Vec<Redactor>,
+    tape: Mutex<Tape>,
+}
+
+impl Cassette {
+    /// Record exchanges into `path`, replacing what it contains.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::with_interactions(path.into(), CassetteMode::Record, vec![])
+    }
+
+    /// Replay the exchanges recorded in `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, OllamaError> {
+        let path = path.into();
+        let bytes = std::fs::read(&path).map_err(OllamaError::FileError)?;
+        let file: CassetteFile = serde_json::from_slice(&bytes)
+            .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        Ok(Self::with_interactions(
+            path,
+            CassetteMode::Replay,
+            file.interactions,
+        ))
+    }
+
+    fn with_interactions(
+        path: PathBuf,
+        mode: CassetteMode,
+        interactions: Vec<Interaction>,
+    ) -> Self {
+        let played = vec![false; interactions.len()];
+        Self {
+            path,
+            mode,
+            strict: false,
+            redactors: vec![],
+            tape: Mutex::new(Tape {
+                interactions,
+                played,
+            }),
+        }
+    }
+
+    /// Fail requests that match no recorded exchange instead of sending them to the server
+    /// (default: false). Only applies to replay mode.
+    #[inline]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Add a hook that removes secrets or volatile values from exchanges before they are
+    /// recorded and from requests before they are matched.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let cassette = Cassette::record("chat.json").redact(|interaction| {
+    ///     interaction.response.headers.remove("date");
+    /// });
+    /// ```
+    #[inline]
+    pub fn redact(mut self, redactor: impl Fn(&mut Interaction) + Send + Sync + 'static) -> Self {
+        self.redactors.push(Box::new(redactor));
+        self
+    }
+
+    #[inline]
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The recorded exchanges, in the order their responses completed.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.tape.lock().unwrap().interactions.clone()
+    }
+
+    /// The number of recorded exchanges that have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.tape
+            .lock()
+            .unwrap()
+            .played
+            .iter()
+            .filter(|p| !**p)
+            .count()
+    }
+
+    fn redacted(&self, mut interaction: Interaction) -> Interaction {
+        self.redactors.iter().for_each(|r| r(&mut interaction));
+        interaction
+    }
+
+    /// Answer a request from the cassette, or through `send` and record the exchange.
+    pub(crate) async fn exchange(
+        self: &Arc<Self>,
+        method: &Method,
+        path: &str,
+        body: Option<&[u8]>,
+        send: impl Future<Output = Result<reqwest::Response, OllamaError>>,
+    ) -> Result<reqwest::Response, OllamaError> {
+        let request = RecordedRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.and_then(|b| serde_json::from_slice(b).ok()),
+        };
+
+        match self.mode {
+            CassetteMode::Replay => match self.play(request)? {
+                Some(response) => Ok(response),
+                None => send.await,
+            },
+            CassetteMode::Record => Ok(self.clone().tee(request, send.await?)),
+        }
+    }
+
+    fn play(&self, request: RecordedRequest) -> Result<Option<reqwest::Response>, OllamaError> {
+        let request = self
+            .redacted(Interaction {
+                request,
+                response: RecordedResponse::default(),
+            })
+            .request;
+
+        let mut tape = self.tape.lock().unwrap();
+        let Tape {
+            interactions,
+            played,
+        } = &mut *tape;
+        let found = interactions
+            .iter()
+            .zip(played.iter_mut())
+            .find(|(interaction, played)| !**played && interaction.request == request);
+
+        let Some((interaction, played)) = found else {
+            if self.strict {
+                return Err(OllamaError::UnmatchedRequest(format!(
+                    "{} {}",
+                    request.method, request.path
+                )));
+            }
+            return Ok(None);
+        };
+        *played = true;
+
+        let chunks = interaction
+            .response
+            .chunks
+            .iter()
+            .map(Chunk::to_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
+        let body = futures::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>));
+        let headers = interaction
+            .response
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let name = HeaderName::try_from(name.as_str()).ok()?;
+                Some((name, HeaderValue::try_from(value.as_str()).ok()?))
+            })
+            .collect();
+        build_response(
+            interaction.response.status,
+            headers,
+            reqwest::Body::wrap_stream(body),
+        )
+        .map(Some)
+    }
+
+    /// Pass the response through, recording its chunks as they are read.
+    fn tee(
+        self: Arc<Self>,
+        request: RecordedRequest,
+        response: reqwest::Response,
+    ) -> reqwest::Response {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let mut recorded = RecordedResponse {
+            status,
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            chunks: vec![],
+        };
+
+        let mut bytes = response.bytes_stream();
+        let body = stream! {
+            while let Some(item) = bytes.next().await {
+                match item {
+                    Ok(chunk) => {
+                        recorded.chunks.push(Chunk::from(&chunk[..]));
+                        yield Ok(chunk);
+                    }
+                    Err(e) => {
+                        yield Err(Box::new(e) as BoxError);
+                        return;
+                    }
+                }
+            }
+
+            let interaction = Interaction { request, response: recorded };
+            if let Err(e) = self.push(interaction) {
+                yield Err(Box::new(e) as BoxError);
+            }
+        };
+
+        // Only the body is replaced, so building the response cannot fail.
+        build_response(status, headers, reqwest::Body::wrap_stream(body)).unwrap()
+    }
+
+    fn push(&self, interaction: Interaction) -> Result<(), OllamaError> {
+        let interaction = self.redacted(interaction);
+        let mut tape = self.tape.lock().unwrap();
+        tape.interactions.push(interaction);
+        tape.played.push(false);
+
+        let file = CassetteFile {
+            interactions: tape.interactions.clone(),
+        };
+        let bytes = serde_json::to_vec_pretty(&file)
+            .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(OllamaError::FileError)?;
+        }
+        std::fs::write(&self.path, bytes).map_err(OllamaError::FileError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use tokio_stream::StreamExt;
+
+    use super::{Cassette, CassetteFile, Chunk, Interaction, RecordedRequest, RecordedResponse};
+    use crate::Ollama;
+    use crate::action::IntoStream;
+    use crate::error::OllamaError;
+    use crate::mock::MockOllama;
+
+    const OFFLINE: &str = "http://127.0.0.1:1";
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ollama-native-{name}-{}.json", std::process::id()))
+    }
+
+    fn redact(interaction: &mut Interaction) {
+        interaction.response.headers.remove("date");
+        if let Some(messages) = interaction
+            .request
+            .body
+            .as_mut()
+            .and_then(|b| b["messages"].as_array_mut())
+        {
+            for message in messages.iter_mut().filter(|m| m["role"] == "system") {
+                message["content"] = json!("[redacted]");
+            }
+        }
+    }
+
+    async fn exchanges(ollama: &Ollama) -> Vec<serde_json::Value> {
+        let chat = ollama
+            .chat("llama3.1:8b")
+            .system_message("my secret")
+            .user_message("why is the sky blue")
+            .await
+            .unwrap();
+        let chunks: Vec<_> = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .stream()
+            .await
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        let models = ollama.list_local_models().await.unwrap();
+        vec![
+            serde_json::to_value(chat).unwrap(),
+            serde_json::to_value(chunks).unwrap(),
+            json!(format!("{models:?}")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn recorded_exchanges_should_replay_offline() {
+        let mock = MockOllama::start().await;
+        let path = path("cassette-roundtrip");
+
+        let recorder = Arc::new(Cassette::record(&path).redact(redact));
+        let recorded = exchanges(&mock.ollama().cassette(recorder.clone())).await;
+        assert_eq!(recorder.interactions().len(), 3);
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("my secret"));
+        assert!(!file.contains("\"date\""));
+        assert!(file.contains("/api/tags"));
+
+        let player = Arc::new(Cassette::replay(&path).unwrap().redact(redact).strict(true));
+        let replayed = exchanges(&Ollama::new(OFFLINE).cassette(player.clone())).await;
+        assert_eq!(recorded, replayed);
+        assert_eq!(player.remaining(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_should_keep_chunk_boundaries() {
+        let path = path("cassette-chunks");
+        let body = concat!(
+            r#"{"model":"m","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":"héllo "},"done":false}"#,
+            "\n",
+            r#"{"model":"m","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":"world"},"done":true}"#,
+            "\n",
+        )
+        .as_bytes();
+        // Split inside the first line, inside "é" and inside the second line.
+        let split = body.iter().position(|b| *b == 0xc3).unwrap() + 1;
+        let chunks: Vec<Chunk> = [
+            &body[..40],
+            &body[40..split],
+            &body[split..150],
+            &body[150..],
+        ]
+        .into_iter()
+        .map(Chunk::from)
+        .collect();
+        assert!(matches!(chunks[1], Chunk::Binary { .. }));
+
+        let interaction = Interaction {
+            request: RecordedRequest {
+                method: "POST".to_string(),
+                path: "/api/chat".to_string(),
+                body: Some(json!({
+                    "model": "m",
+                    "messages": [{ "role": "user", "content": "hi" }],
+                    "stream": true,
+                })),
+            },
+            response: RecordedResponse {
+                status: 200,
+                headers: [(
+                    "content-type".to_string(),
+                    "application/x-ndjson".to_string(),
+                )]
+                .into(),
+                chunks,
+            },
+        };
+        let file = CassetteFile {
+            interactions: vec![interaction],
+        };
+        std::fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        let cassette = Arc::new(Cassette::replay(&path).unwrap().strict(true));
+        let ollama = Ollama::new(OFFLINE).cassette(cassette);
+        let chat = || ollama.chat("m").user_message("hi").stream();
+
+        let content: String = chat()
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap().message.unwrap().content)
+            .collect()
+            .await;
+        assert_eq!(content, "héllo world");
+
+        // Every exchange is replayed once.
+        assert!(matches!(
+            chat().await.err(),
+            Some(OllamaError::UnmatchedRequest(request)) if request == "POST /api/chat"
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unmatched_requests_should_fail_only_when_strict() {
+        let mock = MockOllama::start().await;
+        let path = path("cassette-empty");
+        std::fs::write(&path, r#"{"interactions":[]}"#).unwrap();
+
+        let lenient = Arc::new(Cassette::replay(&path).unwrap());
+        let ollama = mock.ollama().cassette(lenient);
+        assert!(ollama.list_local_models().await.is_ok());
+        assert_eq!(mock.requests("/api/tags").len(), 1);
+
+        let strict = Arc::new(Cassette::replay(&path).unwrap().strict(true));
+        let ollama = mock.ollama().cassette(strict);
+        assert!(matches!(
+            ollama.list_local_models().await,
+            Err(OllamaError::UnmatchedRequest(_))
+        ));
+        assert_eq!(mock.requests("/api/tags").len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
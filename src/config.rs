@@ -3,11 +3,33 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OllamaConfig {
     pub url: String,
+
+    /// The API that requests are sent to.
+    #[cfg(feature = "openai")]
+    #[serde(default)]
+    pub wire_format: WireFormat,
 }
 
 impl OllamaConfig {
     pub fn from_url(url: &str) -> Self {
         let url = url.to_string();
-        Self { url }
+        Self {
+            url,
+            #[cfg(feature = "openai")]
+            wire_format: WireFormat::default(),
+        }
     }
 }
+
+/// The API a client talks to, see [`crate::openai`].
+#[cfg(feature = "openai")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// Ollama's own API under `/api`.
+    #[default]
+    Native,
+
+    /// The OpenAI-compatible API under `/v1`.
+    OpenAi,
+}
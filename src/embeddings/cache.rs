@@ -0,0 +1,251 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    abi::Options,
+    error::OllamaError,
+    storage::{Lru, write_atomic},
+};
+
+/// Identifies an embedding by everything that affects its value: the model digest, whether
+/// inputs are truncated, the model options and the input text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn new(
+        model_digest: &str,
+        truncate: Option<bool>,
+        options: &Options,
+        text: &str,
+    ) -> Result<Self, OllamaError> {
+        let options =
+            serde_json::to_vec(options).map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        let truncate = truncate.unwrap_or(true).to_string();
+
+        // Length prefixes keep the fields from running into each other.
+        let mut hasher = Sha256::new();
+        for field in [
+            model_digest.as_bytes(),
+            truncate.as_bytes(),
+            &options,
+            text.as_bytes(),
+        ] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field);
+        }
+
+        let hash = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(Self(hash))
+    }
+
+    /// The key as a lowercase hex SHA-256 digest.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Storage for embeddings, used through
+/// [`GenerateEmbeddingsAction::cache`][`crate::action::model::generate_embeddings::GenerateEmbeddingsAction::cache`].
+#[async_trait]
+pub trait EmbeddingCache: Send + Sync {
+    async fn get(&self, key: &CacheKey) -> Result<Option<Vec<f64>>, OllamaError>;
+
+    async fn put(&self, key: &CacheKey, embedding: &[f64]) -> Result<(), OllamaError>;
+}
+
+/// An in-memory cache evicting the least recently used embedding once full.
+pub struct MemoryCache {
+    inner: Mutex<Lru<CacheKey, Vec<f64>>>,
+}
+
+impl MemoryCache {
+    /// Creates a cache holding at most `capacity` embeddings.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Lru::new(capacity)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl EmbeddingCache for MemoryCache {
+    async fn get(&self, key: &CacheKey) -> Result<Option<Vec<f64>>, OllamaError> {
+        Ok(self.inner.lock().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: &CacheKey, embedding: &[f64]) -> Result<(), OllamaError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .insert(key.clone(), embedding.to_vec());
+        Ok(())
+    }
+}
+
+/// An on-disk cache storing one file per embedding, as little-endian `f64`s, under
+/// a two character fan-out directory (`<root>/ab/abcdef...`).
+pub struct DirectoryCache {
+    root: PathBuf,
+}
+
+impl DirectoryCache {
+    /// Opens the cache at `root`, creating the directory if needed.
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, OllamaError> {
+        let root = root.into();
+        std::fs::create_dir_all(&root).map_err(OllamaError::FileError)?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.root.join(&key.as_str()[..2]).join(key.as_str())
+    }
+}
+
+#[async_trait]
+impl EmbeddingCache for DirectoryCache {
+    async fn get(&self, key: &CacheKey) -> Result<Option<Vec<f64>>, OllamaError> {
+        let bytes = match tokio::fs::read(self.path(key)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(OllamaError::FileError(e)),
+        };
+
+        if bytes.len() % 8 != 0 {
+            return Err(OllamaError::InvalidFormat(format!(
+                "corrupted cache entry {key}"
+            )));
+        }
+        let embedding = bytes
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok(Some(embedding))
+    }
+
+    async fn put(&self, key: &CacheKey, embedding: &[f64]) -> Result<(), OllamaError> {
+        let path = self.path(key);
+        let bytes: Vec<u8> = embedding.iter().flat_map(|x| x.to_le_bytes()).collect();
+        tokio::task::spawn_blocking(move || write_atomic(&path, &bytes))
+            .await
+            .map_err(|e| OllamaError::FileError(e.into()))?
+            .map_err(OllamaError::FileError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheKey, DirectoryCache, EmbeddingCache, MemoryCache};
+    use crate::abi::Options;
+
+    fn key(text: &str) -> CacheKey {
+        CacheKey::new("sha256:abc", None, &Options::default(), text).unwrap()
+    }
+
+    #[test]
+    fn cache_key_should_depend_on_every_field() {
+        let options = Options::default();
+        let mut num_ctx = Options::default();
+        num_ctx.num_ctx(512);
+
+        let base = key("shark");
+        assert_eq!(base, key("shark"));
+        assert_eq!(base.as_str().len(), 64);
+        assert_ne!(base, key("sharks"));
+        assert_ne!(
+            base,
+            CacheKey::new("sha256:abd", None, &options, "shark").unwrap()
+        );
+        assert_ne!(
+            base,
+            CacheKey::new("sha256:abc", Some(false), &options, "shark").unwrap()
+        );
+        assert_eq!(
+            base,
+            CacheKey::new("sha256:abc", Some(true), &options, "shark").unwrap()
+        );
+        assert_ne!(
+            base,
+            CacheKey::new("sha256:abc", None, &num_ctx, "shark").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_cache_should_evict_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.put(&key("a"), &[1.0]).await.unwrap();
+        cache.put(&key("b"), &[2.0]).await.unwrap();
+
+        // Reading "a" makes "b" the least recently used.
+        assert_eq!(cache.get(&key("a")).await.unwrap(), Some(vec![1.0]));
+        cache.put(&key("c"), &[3.0]).await.unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key("b")).await.unwrap(), None);
+        assert_eq!(cache.get(&key("a")).await.unwrap(), Some(vec![1.0]));
+        assert_eq!(cache.get(&key("c")).await.unwrap(), Some(vec![3.0]));
+
+        cache.put(&key("a"), &[4.0]).await.unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key("a")).await.unwrap(), Some(vec![4.0]));
+    }
+
+    #[tokio::test]
+    async fn directory_cache_should_work() {
+        let root = std::env::temp_dir().join(format!("ollama-native-cache-{}", std::process::id()));
+        let cache = DirectoryCache::new(&root).unwrap();
+
+        assert_eq!(cache.get(&key("a")).await.unwrap(), None);
+        cache.put(&key("a"), &[0.5, -1.25, 3.0]).await.unwrap();
+        assert_eq!(
+            cache.get(&key("a")).await.unwrap(),
+            Some(vec![0.5, -1.25, 3.0])
+        );
+
+        // A new handle on the same directory sees the stored entries.
+        let reopened = DirectoryCache::new(&root).unwrap();
+        assert_eq!(
+            reopened.get(&key("a")).await.unwrap(),
+            Some(vec![0.5, -1.25, 3.0])
+        );
+
+        // Concurrent writes of the same entry each use their own temporary file.
+        let b = key("b");
+        let embeddings: Vec<[f64; 1]> = (0..8).map(|i| [i as f64]).collect();
+        let writes = embeddings.iter().map(|embedding| cache.put(&b, embedding));
+        for result in futures::future::join_all(writes).await {
+            result.unwrap();
+        }
+        assert_eq!(cache.get(&b).await.unwrap().unwrap().len(), 1);
+        let dir = cache.path(&b).parent().unwrap().to_path_buf();
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    abi::model::generate_embeddings::GenerateEmbeddingsResponse,
+    action::model::generate_embeddings::GenerateEmbeddingsAction, error::OllamaError,
+};
+
+use super::{cosine_similarity, dot, euclidean_distance};
+
+/// Arbitrary JSON metadata attached to a document, used for filtering search results.
+pub type Metadata = serde_json::Map<String, serde_json::Value>;
+
+/// How search results are scored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Cosine similarity, higher is closer.
+    #[default]
+    Cosine,
+
+    /// Dot product, higher is closer. Equivalent to cosine for normalized embeddings.
+    Dot,
+
+    /// Euclidean distance, lower is closer.
+    Euclidean,
+}
+
+impl Metric {
+    fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        match self {
+            Metric::Cosine => cosine_similarity(a, b),
+            Metric::Dot => dot(a, b),
+            Metric::Euclidean => euclidean_distance(a, b),
+        }
+    }
+
+    fn is_closer(&self, a: f64, b: f64) -> bool {
+        match self {
+            Metric::Cosine | Metric::Dot => a > b,
+            Metric::Euclidean => a < b,
+        }
+    }
+}
+
+/// A piece of text to embed and store in a [`VectorIndex`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+impl Document {
+    pub fn new(id: impl ToString, text: impl ToString) -> Self {
+        Self {
+            id: id.to_string(),
+            text: text.to_string(),
+            metadata: Metadata::new(),
+        }
+    }
+
+    /// Attach a metadata value to the document.
+    #[inline]
+    pub fn metadata(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+/// A document stored in a [`VectorIndex`] along with its embedding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub document: Document,
+    pub embedding: Vec<f64>,
+}
+
+/// A search hit, borrowed from the index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult<'a> {
+    /// The similarity (or distance, for [`Metric::Euclidean`]) to the query.
+    pub score: f64,
+    pub document: &'a Document,
+    pub embedding: &'a [f64],
+}
+
+/// A small in-memory vector index with exact (brute force) search, meant for prototypes
+/// and small corpora rather than as a replacement for a vector database.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::embeddings::{Document, VectorIndex};
+///
+/// let documents = vec![
+///     Document::new("sharks", "Sharks are fish").metadata("topic", "ocean"),
+///     Document::new("tigers", "Tigers are cats").metadata("topic", "land"),
+/// ];
+///
+/// let mut index = VectorIndex::new();
+/// index
+///     .embed_documents(ollama.generate_embeddings("all-minilm"), &documents)
+///     .await?;
+///
+/// let query = ollama.generate_embeddings("all-minilm").input("fish").await?;
+/// let hits = index.search_filtered(&query.embeddings[0], 3, |meta| {
+///     meta.get("topic").is_some_and(|t| t == "ocean")
+/// });
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VectorIndex {
+    metric: Metric,
+    entries: Vec<IndexEntry>,
+}
+
+impl VectorIndex {
+    /// Creates an empty index using cosine similarity.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty index using the given metric.
+    pub fn with_metric(metric: Metric) -> Self {
+        Self {
+            metric,
+            entries: vec![],
+        }
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Number of dimensions of the stored embeddings, `None` if the index is empty.
+    pub fn dimensions(&self) -> Option<usize> {
+        self.entries.first().map(|e| e.embedding.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IndexEntry> {
+        self.entries.iter().find(|e| e.document.id == id)
+    }
+
+    /// Add a document, replacing any document with the same id.
+    ///
+    /// # Errors
+    /// - `OllamaError::DimensionMismatch`: The embedding does not match the dimensions of the index.
+    pub fn add(&mut self, document: Document, embedding: Vec<f64>) -> Result<(), OllamaError> {
+        if let Some(expected) = self.dimensions()
+            && expected != embedding.len()
+        {
+            return Err(OllamaError::DimensionMismatch {
+                expected,
+                actual: embedding.len(),
+            });
+        }
+
+        let entry = IndexEntry {
+            document,
+            embedding,
+        };
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.document.id == entry.document.id)
+        {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// Add documents along with the response of embedding their texts, in the same order.
+    pub fn add_response(
+        &mut self,
+        documents: impl IntoIterator<Item = Document>,
+        response: GenerateEmbeddingsResponse,
+    ) -> Result<(), OllamaError> {
+        let documents: Vec<Document> = documents.into_iter().collect();
+        if documents.len() != response.embeddings.len() {
+            return Err(OllamaError::InvalidFormat(format!(
+                "got {} embeddings for {} documents",
+                response.embeddings.len(),
+                documents.len()
+            )));
+        }
+
+        documents
+            .into_iter()
+            .zip(response.embeddings)
+            .try_for_each(|(document, embedding)| self.add(document, embedding))
+    }
+
+    /// Embed the documents' texts with the given action and add them to the index.
+    pub async fn embed_documents<'a>(
+        &mut self,
+        action: GenerateEmbeddingsAction<'a>,
+        documents: &'a [Document],
+    ) -> Result<(), OllamaError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        let inputs = documents.iter().map(|d| d.text.as_str()).collect();
+        let response = action.inputs(inputs).await?;
+        self.add_response(documents.iter().cloned(), response)
+    }
+
+    /// Remove a document by id.
+    pub fn remove(&mut self, id: &str) -> Option<IndexEntry> {
+        let position = self.entries.iter().position(|e| e.document.id == id)?;
+        Some(self.entries.remove(position))
+    }
+
+    /// Remove every document matching the filter, returning how many were removed.
+    pub fn remove_where(&mut self, filter: impl Fn(&Document) -> bool) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|e| !filter(&e.document));
+        before - self.entries.len()
+    }
+
+    /// The `k` documents closest to the query, closest first.
+    pub fn search(&self, query: &[f64], k: usize) -> Vec<SearchResult<'_>> {
+        self.search_filtered(query, k, |_| true)
+    }
+
+    /// The `k` documents closest to the query whose metadata matches the filter, closest first.
+    pub fn search_filtered(
+        &self,
+        query: &[f64],
+        k: usize,
+        filter: impl Fn(&Metadata) -> bool,
+    ) -> Vec<SearchResult<'_>> {
+        let mut results: Vec<SearchResult<'_>> = self
+            .entries
+            .iter()
+            .filter(|e| e.embedding.len() == query.len() && filter(&e.document.metadata))
+            .map(|e| SearchResult {
+                score: self.metric.score(query, &e.embedding),
+                document: &e.document,
+                embedding: &e.embedding,
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            if self.metric.is_closer(a.score, b.score) {
+                std::cmp::Ordering::Less
+            } else if self.metric.is_closer(b.score, a.score) {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        });
+        results.truncate(k);
+        results
+    }
+
+    /// Save the index as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), OllamaError> {
+        let serialized =
+            serde_json::to_vec(self).map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        std::fs::write(path, serialized).map_err(OllamaError::FileError)
+    }
+
+    /// Load an index saved with [`VectorIndex::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, OllamaError> {
+        let serialized = std::fs::read(path).map_err(OllamaError::FileError)?;
+        serde_json::from_slice(&serialized).map_err(|e| OllamaError::InvalidFormat(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, Metric, VectorIndex};
+
+    fn index(metric: Metric) -> VectorIndex {
+        let mut index = VectorIndex::with_metric(metric);
+        let docs = [
+            ("sharks", vec![1.0, 0.0, 0.0], "ocean"),
+            ("whales", vec![0.9, 0.1, 0.0], "ocean"),
+            ("tigers", vec![0.0, 1.0, 0.0], "land"),
+            ("eagles", vec![0.0, 0.0, 1.0], "air"),
+        ];
+        for (id, embedding, topic) in docs {
+            let document = Document::new(id, format!("about {id}")).metadata("topic", topic);
+            index.add(document, embedding).unwrap();
+        }
+        index
+    }
+
+    fn ids(results: &[super::SearchResult<'_>]) -> Vec<String> {
+        results.iter().map(|r| r.document.id.clone()).collect()
+    }
+
+    #[test]
+    fn search_should_return_top_k() {
+        for metric in [Metric::Cosine, Metric::Dot, Metric::Euclidean] {
+            let index = index(metric);
+            let results = index.search(&[1.0, 0.05, 0.0], 2);
+            assert_eq!(ids(&results), vec!["sharks", "whales"], "{metric:?}");
+        }
+    }
+
+    #[test]
+    fn search_filtered_should_apply_metadata_filter() {
+        let index = index(Metric::Cosine);
+        let results = index.search_filtered(&[1.0, 0.0, 0.0], 10, |meta| meta["topic"] != "ocean");
+        assert_eq!(ids(&results), vec!["tigers", "eagles"]);
+    }
+
+    #[test]
+    fn add_and_remove_should_work() {
+        let mut index = index(Metric::Cosine);
+        assert_eq!(index.len(), 4);
+
+        // Adding an existing id replaces the document.
+        index
+            .add(Document::new("sharks", "updated"), vec![0.0, 1.0, 0.0])
+            .unwrap();
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.get("sharks").unwrap().document.text, "updated");
+
+        assert!(index.remove("sharks").is_some());
+        assert!(index.remove("sharks").is_none());
+        assert_eq!(index.remove_where(|d| d.metadata["topic"] == "ocean"), 1);
+        assert_eq!(index.len(), 2);
+
+        let err = index
+            .add(Document::new("fish", "fish"), vec![1.0, 0.0])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "dimension mismatch: expected 3, got 2");
+    }
+
+    #[test]
+    fn save_and_load_should_work() {
+        let index = index(Metric::Euclidean);
+        let path = std::env::temp_dir().join("ollama-native-vector-index.json");
+        index.save(&path).unwrap();
+        let loaded = VectorIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.metric(), Metric::Euclidean);
+        assert_eq!(loaded.entries(), index.entries());
+    }
+}
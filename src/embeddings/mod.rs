@@ -0,0 +1,111 @@
+//! Helpers for working with the vectors returned by [`generate_embeddings`][`crate::Ollama::generate_embeddings`].
+//!
+//! All functions expect vectors of the same length and panic otherwise.
+
+pub mod cache;
+pub mod index;
+
+pub use cache::{CacheKey, DirectoryCache, EmbeddingCache, MemoryCache};
+pub use index::{Document, IndexEntry, Metadata, Metric, SearchResult, VectorIndex};
+
+use crate::abi::model::generate_embeddings::GenerateEmbeddingsResponse;
+
+/// Dot product of two vectors.
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    assert_eq!(a.len(), b.len(), "vectors must have the same length");
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// L2 norm (length) of a vector.
+pub fn norm(v: &[f64]) -> f64 {
+    v.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+/// Cosine similarity of two vectors, in `[-1, 1]`. Returns `0` if either vector is all zeros.
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        return 0.0;
+    }
+    dot(a, b) / norms
+}
+
+/// Euclidean distance between two vectors.
+pub fn euclidean_distance(a: &[f64], b: &[f64]) -> f64 {
+    assert_eq!(a.len(), b.len(), "vectors must have the same length");
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Scale a vector in place to unit length. All-zero vectors are left untouched.
+pub fn normalize(v: &mut [f64]) {
+    let n = norm(v);
+    if n > 0.0 {
+        v.iter_mut().for_each(|x| *x /= n);
+    }
+}
+
+/// Returns a copy of the vector scaled to unit length.
+pub fn normalized(v: &[f64]) -> Vec<f64> {
+    let mut v = v.to_vec();
+    normalize(&mut v);
+    v
+}
+
+/// Converts a vector to `f32`, halving its memory footprint.
+pub fn to_f32(v: &[f64]) -> Vec<f32> {
+    v.iter().map(|x| *x as f32).collect()
+}
+
+impl GenerateEmbeddingsResponse {
+    /// The embeddings converted to `f32`.
+    pub fn embeddings_f32(&self) -> Vec<Vec<f32>> {
+        self.embeddings.iter().map(|e| to_f32(e)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn similarity_should_work() {
+        let a = [1.0, 0.0, 0.0];
+        let b = [0.0, 2.0, 0.0];
+        let c = [3.0, 0.0, 0.0];
+
+        assert!(approx_eq(dot(&a, &c), 3.0));
+        assert!(approx_eq(cosine_similarity(&a, &b), 0.0));
+        assert!(approx_eq(cosine_similarity(&a, &c), 1.0));
+        assert!(approx_eq(cosine_similarity(&a, &[0.0; 3]), 0.0));
+        assert!(approx_eq(euclidean_distance(&a, &c), 2.0));
+        assert!(approx_eq(euclidean_distance(&a, &b), 5f64.sqrt()));
+    }
+
+    #[test]
+    fn normalize_should_work() {
+        let v = normalized(&[3.0, 4.0]);
+        assert!(approx_eq(v[0], 0.6));
+        assert!(approx_eq(v[1], 0.8));
+        assert!(approx_eq(norm(&v), 1.0));
+
+        let mut zero = [0.0, 0.0];
+        normalize(&mut zero);
+        assert_eq!(zero, [0.0, 0.0]);
+
+        assert_eq!(to_f32(&[0.5, -1.25]), vec![0.5f32, -1.25f32]);
+    }
+
+    #[test]
+    #[should_panic(expected = "vectors must have the same length")]
+    fn dot_with_different_lengths_should_panic() {
+        dot(&[1.0], &[1.0, 2.0]);
+    }
+}
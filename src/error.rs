@@ -20,6 +20,10 @@ pub enum OllamaError {
     #[error("invalid format: {0}")]
     InvalidFormat(String),
 
+    /// An image could not be read, decoded or does not satisfy the configured limits.
+    #[error("image error: {0}")]
+    ImageError(String),
+
     /// Error returned by the Ollama server.
     #[error("ollama error: {0}")]
     OllamaServerError(String),
@@ -39,8 +43,46 @@ pub enum OllamaError {
     #[error("unexpected digest")]
     UnexpectedDigest,
 
+    /// An embedding does not have the same number of dimensions as the others.
+    #[cfg(feature = "embeddings")]
+    #[error("dimension mismatch: expected {expected}, got {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+
+    /// A prompt template could not be parsed or executed.
+    #[cfg(feature = "template")]
+    #[error("template error: {0}")]
+    TemplateError(String),
+
+    /// A request waited longer than the queue timeout of the scheduler.
+    #[cfg(feature = "scheduler")]
+    #[error("timed out after {0:?} in the request queue")]
+    QueueTimeout(std::time::Duration),
+
+    /// A request matched no recorded exchange of a strict cassette.
+    #[cfg(feature = "cassette")]
+    #[error("unmatched request: {0}")]
+    UnmatchedRequest(String),
+
+    /// A request was sent through a pool with no hosts.
+    #[cfg(feature = "pool")]
+    #[error("no host available")]
+    NoHostAvailable,
+
+    /// A server did not pass its health checks in time.
+    #[cfg(feature = "health")]
+    #[error("not ready after {timeout:?}: {error}")]
+    NotReady {
+        timeout: std::time::Duration,
+        error: String,
+    },
+
     /// Error occurred while performing file operations.
-    #[cfg(feature = "model")]
+    #[cfg(any(
+        feature = "model",
+        feature = "session",
+        feature = "gguf",
+        feature = "cassette"
+    ))]
     #[error("file error: {0}")]
     FileError(std::io::Error),
 }
@@ -0,0 +1,648 @@
+//! Reading the header of GGUF model files: the metadata and the tensor table, without
+//! loading the weights.
+//!
+//! Metadata keys are the ones [`show_model_information`][`crate::Ollama::show_model_information`]
+//! returns in `model_info`, e.g. `general.architecture` or `llama.context_length`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::error::OllamaError;
+
+const MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+/// Strings longer than this are treated as a corrupted file rather than allocated.
+const MAX_STRING_LEN: u64 = 1 << 30;
+
+/// Arrays nested deeper than this are treated as a corrupted file rather than recursed into.
+const MAX_ARRAY_DEPTH: usize = 8;
+
+/// Arrays `model_info` leaves empty unless `verbose` is set, as they hold the whole vocabulary.
+const VERBOSE_KEYS: &[&str] = &[
+    "tokenizer.ggml.tokens",
+    "tokenizer.ggml.token_type",
+    "tokenizer.ggml.merges",
+    "tokenizer.ggml.scores",
+];
+
+/// A metadata value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    String(String),
+    Array(Vec<MetadataValue>),
+}
+
+impl MetadataValue {
+    /// The value as an unsigned integer, if it is a non-negative integer of any width.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::U8(v) => Some(v.into()),
+            Self::U16(v) => Some(v.into()),
+            Self::U32(v) => Some(v.into()),
+            Self::U64(v) => Some(v),
+            Self::I8(v) => u64::try_from(v).ok(),
+            Self::I16(v) => u64::try_from(v).ok(),
+            Self::I32(v) => u64::try_from(v).ok(),
+            Self::I64(v) => u64::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::F32(v) => Some(v.into()),
+            Self::F64(v) => Some(v),
+            _ => self.as_u64().map(|v| v as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[MetadataValue]> {
+        match self {
+            Self::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// The value as JSON, the way `model_info` reports it.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Self::U8(v) => Value::from(*v),
+            Self::I8(v) => Value::from(*v),
+            Self::U16(v) => Value::from(*v),
+            Self::I16(v) => Value::from(*v),
+            Self::U32(v) => Value::from(*v),
+            Self::I32(v) => Value::from(*v),
+            Self::U64(v) => Value::from(*v),
+            Self::I64(v) => Value::from(*v),
+            Self::F32(v) => Value::from(*v),
+            Self::F64(v) => Value::from(*v),
+            Self::Bool(v) => Value::from(*v),
+            Self::String(v) => Value::from(v.as_str()),
+            Self::Array(a) => Value::from(a.iter().map(Self::to_json).collect::<Vec<_>>()),
+        }
+    }
+}
+
+/// An entry of the tensor table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorInfo {
+    pub name: String,
+    pub dimensions: Vec<u64>,
+
+    /// The ggml type of the tensor, see [`TensorInfo::type_name`].
+    pub ggml_type: u32,
+
+    /// The offset of the tensor data from the start of the data section.
+    pub offset: u64,
+}
+
+impl TensorInfo {
+    /// The number of elements of the tensor, saturating at `u64::MAX`.
+    pub fn elements(&self) -> u64 {
+        self.dimensions
+            .iter()
+            .fold(1, |elements, &dimension| elements.saturating_mul(dimension))
+    }
+
+    /// The name of the ggml type, e.g. `Q4_K`.
+    pub fn type_name(&self) -> Option<&'static str> {
+        const TYPES: &[&str] = &[
+            "F32", "F16", "Q4_0", "Q4_1", "", "", "Q5_0", "Q5_1", "Q8_0", "Q8_1", "Q2_K", "Q3_K",
+            "Q4_K", "Q5_K", "Q6_K", "Q8_K", "IQ2_XXS", "IQ2_XS", "IQ3_XXS", "IQ1_S", "IQ4_NL",
+            "IQ3_S", "IQ2_S", "IQ4_XS", "I8", "I16", "I32", "I64", "F64", "IQ1_M", "BF16",
+        ];
+        TYPES
+            .get(self.ggml_type as usize)
+            .copied()
+            .filter(|t| !t.is_empty())
+    }
+}
+
+/// The header of a GGUF file.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::gguf::Gguf;
+///
+/// let gguf = Gguf::open("llama3.1-8b-q4_0.gguf")?;
+/// assert_eq!(gguf.architecture(), Some("llama"));
+/// println!(
+///     "{} parameters, {:?}, context of {:?} tokens",
+///     gguf.parameter_count(),
+///     gguf.quantization(),
+///     gguf.context_length(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gguf {
+    pub version: u32,
+    pub metadata: BTreeMap<String, MetadataValue>,
+    pub tensors: Vec<TensorInfo>,
+
+    /// The offset of the data section from the start of the file.
+    pub data_offset: u64,
+}
+
+impl Gguf {
+    /// Reads the header of a GGUF file, checking that every tensor starts within the file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OllamaError> {
+        let file = File::open(path).map_err(OllamaError::FileError)?;
+        let len = file.metadata().map_err(OllamaError::FileError)?.len();
+        let gguf = Self::read(BufReader::new(file))?;
+
+        if let Some(tensor) = gguf.tensors.iter().find(|t| {
+            gguf.data_offset
+                .checked_add(t.offset)
+                .is_none_or(|start| start > len)
+        }) {
+            return Err(OllamaError::InvalidFormat(format!(
+                "tensor {} starts past the end of the file",
+                tensor.name
+            )));
+        }
+        Ok(gguf)
+    }
+
+    /// Reads a GGUF header, leaving the reader at the end of the tensor table.
+    pub fn read(reader: impl Read) -> Result<Self, OllamaError> {
+        let mut reader = Reader {
+            inner: reader,
+            read: 0,
+            version: 0,
+        };
+
+        let mut magic = [0; 4];
+        reader.bytes(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a GGUF file"));
+        }
+        let version = reader.u32()?;
+        if !(1..=3).contains(&version) {
+            return Err(invalid(format!("unsupported GGUF version {version}")));
+        }
+        reader.version = version;
+
+        let tensor_count = reader.count()?;
+        let metadata_count = reader.count()?;
+
+        let mut metadata = BTreeMap::new();
+        for _ in 0..metadata_count {
+            let key = reader.string()?;
+            let value_type = reader.u32()?;
+            let value = reader.value(value_type, 0)?;
+            metadata.insert(key, value);
+        }
+
+        let mut tensors = vec![];
+        for _ in 0..tensor_count {
+            let name = reader.string()?;
+            let dimension_count = reader.u32()?;
+            if dimension_count > 4 {
+                return Err(invalid(format!(
+                    "tensor {name} has {dimension_count} dimensions"
+                )));
+            }
+            let dimensions = (0..dimension_count)
+                .map(|_| reader.count())
+                .collect::<Result<_, _>>()?;
+            let ggml_type = reader.u32()?;
+            let offset = reader.u64()?;
+            tensors.push(TensorInfo {
+                name,
+                dimensions,
+                ggml_type,
+                offset,
+            });
+        }
+
+        let alignment = metadata
+            .get("general.alignment")
+            .and_then(MetadataValue::as_u64)
+            .filter(|a| *a > 0)
+            .unwrap_or(DEFAULT_ALIGNMENT);
+        let data_offset = reader.read.div_ceil(alignment) * alignment;
+
+        Ok(Self {
+            version,
+            metadata,
+            tensors,
+            data_offset,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.metadata.get(key)
+    }
+
+    /// The architecture, e.g. `llama`, read from `general.architecture`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.get("general.architecture")?.as_str()
+    }
+
+    /// The model name, read from `general.name`.
+    pub fn name(&self) -> Option<&str> {
+        self.get("general.name")?.as_str()
+    }
+
+    /// A key of the architecture, e.g. `context_length` for `llama.context_length`.
+    fn architecture_u64(&self, key: &str) -> Option<u64> {
+        let architecture = self.architecture()?;
+        self.get(&format!("{architecture}.{key}"))?.as_u64()
+    }
+
+    /// The context length the model was trained with, in tokens.
+    pub fn context_length(&self) -> Option<u64> {
+        self.architecture_u64("context_length")
+    }
+
+    pub fn embedding_length(&self) -> Option<u64> {
+        self.architecture_u64("embedding_length")
+    }
+
+    /// The number of layers.
+    pub fn block_count(&self) -> Option<u64> {
+        self.architecture_u64("block_count")
+    }
+
+    pub fn head_count(&self) -> Option<u64> {
+        self.architecture_u64("attention.head_count")
+    }
+
+    /// The quantization of the file, read from `general.file_type`.
+    pub fn file_type(&self) -> Option<u64> {
+        self.get("general.file_type")?.as_u64()
+    }
+
+    /// The name of the quantization, e.g. `Q4_K_M`, as reported in `details.quantization_level`.
+    pub fn quantization(&self) -> Option<&'static str> {
+        const FILE_TYPES: &[&str] = &[
+            "F32", "F16", "Q4_0", "Q4_1", "", "", "", "Q8_0", "Q5_0", "Q5_1", "Q2_K", "Q3_K_S",
+            "Q3_K_M", "Q3_K_L", "Q4_K_S", "Q4_K_M", "Q5_K_S", "Q5_K_M", "Q6_K", "IQ2_XXS",
+            "IQ2_XS", "Q2_K_S", "IQ3_XS", "IQ3_XXS", "IQ1_S", "IQ4_NL", "IQ3_S", "IQ3_M", "IQ2_S",
+            "IQ2_M", "IQ4_XS", "IQ1_M", "BF16",
+        ];
+        FILE_TYPES
+            .get(usize::try_from(self.file_type()?).ok()?)
+            .copied()
+            .filter(|t| !t.is_empty())
+    }
+
+    /// The tokenizer model, e.g. `gpt2` or `llama`, read from `tokenizer.ggml.model`.
+    pub fn tokenizer_model(&self) -> Option<&str> {
+        self.get("tokenizer.ggml.model")?.as_str()
+    }
+
+    /// The number of tokens in the vocabulary.
+    pub fn vocab_size(&self) -> Option<usize> {
+        Some(self.get("tokenizer.ggml.tokens")?.as_array()?.len())
+    }
+
+    pub fn bos_token_id(&self) -> Option<u64> {
+        self.get("tokenizer.ggml.bos_token_id")?.as_u64()
+    }
+
+    pub fn eos_token_id(&self) -> Option<u64> {
+        self.get("tokenizer.ggml.eos_token_id")?.as_u64()
+    }
+
+    /// The Jinja chat template shipped with the model, if any.
+    pub fn chat_template(&self) -> Option<&str> {
+        self.get("tokenizer.chat_template")?.as_str()
+    }
+
+    /// The total number of weights, saturating at `u64::MAX`.
+    pub fn parameter_count(&self) -> u64 {
+        self.tensors
+            .iter()
+            .fold(0, |count, tensor| count.saturating_add(tensor.elements()))
+    }
+
+    /// The metadata as `model_info` reports it, with `general.parameter_count` added.
+    /// Unless `verbose`, the vocabulary arrays are left empty, as Ollama does.
+    pub fn model_info(&self, verbose: bool) -> BTreeMap<String, Value> {
+        let mut info: BTreeMap<String, Value> = self
+            .metadata
+            .iter()
+            .map(|(key, value)| {
+                let value = if !verbose && VERBOSE_KEYS.contains(&key.as_str()) {
+                    Value::Array(vec![])
+                } else {
+                    value.to_json()
+                };
+                (key.clone(), value)
+            })
+            .collect();
+        info.insert(
+            "general.parameter_count".to_string(),
+            Value::from(self.parameter_count()),
+        );
+        info
+    }
+}
+
+fn invalid(message: impl Into<String>) -> OllamaError {
+    OllamaError::InvalidFormat(message.into())
+}
+
+/// A little-endian reader keeping track of its position.
+struct Reader<R> {
+    inner: R,
+    read: u64,
+    version: u32,
+}
+
+macro_rules! read_number {
+    ($($name:ident: $ty:ty),*) => {
+        $(fn $name(&mut self) -> Result<$ty, OllamaError> {
+            let mut buf = [0; size_of::<$ty>()];
+            self.bytes(&mut buf)?;
+            Ok(<$ty>::from_le_bytes(buf))
+        })*
+    };
+}
+
+impl<R: Read> Reader<R> {
+    fn bytes(&mut self, buf: &mut [u8]) -> Result<(), OllamaError> {
+        self.inner.read_exact(buf).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                invalid("unexpected end of GGUF header")
+            } else {
+                invalid(format!("failed to read GGUF header: {e}"))
+            }
+        })?;
+        self.read += buf.len() as u64;
+        Ok(())
+    }
+
+    read_number!(u8: u8, i8: i8, u16: u16, i16: i16, u32: u32, i32: i32, u64: u64, i64: i64, f32: f32, f64: f64);
+
+    /// Counts and lengths are 32 bits wide in version 1, 64 bits since.
+    fn count(&mut self) -> Result<u64, OllamaError> {
+        if self.version == 1 {
+            Ok(self.u32()?.into())
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> Result<String, OllamaError> {
+        let len = self.count()?;
+        if len > MAX_STRING_LEN {
+            return Err(invalid(format!("string of {len} bytes")));
+        }
+        let mut buf = vec![0; len as usize];
+        self.bytes(&mut buf)?;
+        String::from_utf8(buf).map_err(|e| invalid(format!("invalid string: {e}")))
+    }
+
+    /// Reads a value of `value_type`, `depth` being the number of arrays it is nested in.
+    fn value(&mut self, value_type: u32, depth: usize) -> Result<MetadataValue, OllamaError> {
+        let value = match value_type {
+            0 => MetadataValue::U8(self.u8()?),
+            1 => MetadataValue::I8(self.i8()?),
+            2 => MetadataValue::U16(self.u16()?),
+            3 => MetadataValue::I16(self.i16()?),
+            4 => MetadataValue::U32(self.u32()?),
+            5 => MetadataValue::I32(self.i32()?),
+            6 => MetadataValue::F32(self.f32()?),
+            7 => MetadataValue::Bool(self.u8()? != 0),
+            8 => MetadataValue::String(self.string()?),
+            9 if depth >= MAX_ARRAY_DEPTH => {
+                return Err(invalid(format!(
+                    "arrays nested deeper than {MAX_ARRAY_DEPTH} levels"
+                )));
+            }
+            9 => {
+                let item_type = self.u32()?;
+                let len = self.count()?;
+                // Grown as items are read, so a corrupted length fails on EOF instead of
+                // allocating.
+                let mut items = vec![];
+                for _ in 0..len {
+                    items.push(self.value(item_type, depth + 1)?);
+                }
+                MetadataValue::Array(items)
+            }
+            10 => MetadataValue::U64(self.u64()?),
+            11 => MetadataValue::I64(self.i64()?),
+            12 => MetadataValue::F64(self.f64()?),
+            _ => return Err(invalid(format!("unknown metadata type {value_type}"))),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Gguf, MetadataValue};
+    use crate::error::OllamaError;
+
+    /// Writes a version 3 GGUF header.
+    #[derive(Default)]
+    struct Writer {
+        metadata: Vec<u8>,
+        metadata_count: u64,
+        tensors: Vec<u8>,
+        tensor_count: u64,
+    }
+
+    fn string(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as u64).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+
+    impl Writer {
+        fn kv(mut self, key: &str, value_type: u32, value: &[u8]) -> Self {
+            string(&mut self.metadata, key);
+            self.metadata.extend(value_type.to_le_bytes());
+            self.metadata.extend(value);
+            self.metadata_count += 1;
+            self
+        }
+
+        fn string(self, key: &str, value: &str) -> Self {
+            let mut buf = vec![];
+            string(&mut buf, value);
+            self.kv(key, 8, &buf)
+        }
+
+        fn tensor(mut self, name: &str, dimensions: &[u64], ggml_type: u32, offset: u64) -> Self {
+            string(&mut self.tensors, name);
+            self.tensors.extend((dimensions.len() as u32).to_le_bytes());
+            dimensions
+                .iter()
+                .for_each(|d| self.tensors.extend(d.to_le_bytes()));
+            self.tensors.extend(ggml_type.to_le_bytes());
+            self.tensors.extend(offset.to_le_bytes());
+            self.tensor_count += 1;
+            self
+        }
+
+        fn build(self) -> Vec<u8> {
+            let mut buf = b"GGUF".to_vec();
+            buf.extend(3u32.to_le_bytes());
+            buf.extend(self.tensor_count.to_le_bytes());
+            buf.extend(self.metadata_count.to_le_bytes());
+            buf.extend(self.metadata);
+            buf.extend(self.tensors);
+            buf
+        }
+    }
+
+    fn tokens(tokens: &[&str]) -> Vec<u8> {
+        let mut buf = 8u32.to_le_bytes().to_vec();
+        buf.extend((tokens.len() as u64).to_le_bytes());
+        tokens.iter().for_each(|t| string(&mut buf, t));
+        buf
+    }
+
+    fn llama() -> Vec<u8> {
+        Writer::default()
+            .string("general.architecture", "llama")
+            .string("general.name", "Tiny Llama")
+            .kv("general.file_type", 4, &15u32.to_le_bytes())
+            .kv("llama.context_length", 4, &8192u32.to_le_bytes())
+            .kv("llama.embedding_length", 4, &64u32.to_le_bytes())
+            .kv("llama.block_count", 4, &2u32.to_le_bytes())
+            .kv("llama.rope.freq_base", 6, &500000f32.to_le_bytes())
+            .string("tokenizer.ggml.model", "gpt2")
+            .kv("tokenizer.ggml.tokens", 9, &tokens(&["<s>", "</s>", "a"]))
+            .kv("tokenizer.ggml.eos_token_id", 4, &1u32.to_le_bytes())
+            .tensor("token_embd.weight", &[64, 3], 12, 0)
+            .tensor("output_norm.weight", &[64], 0, 256)
+            .build()
+    }
+
+    #[test]
+    fn read_should_parse_header() {
+        let bytes = llama();
+        let gguf = Gguf::read(bytes.as_slice()).unwrap();
+
+        assert_eq!(gguf.version, 3);
+        assert_eq!(gguf.architecture(), Some("llama"));
+        assert_eq!(gguf.name(), Some("Tiny Llama"));
+        assert_eq!(gguf.context_length(), Some(8192));
+        assert_eq!(gguf.embedding_length(), Some(64));
+        assert_eq!(gguf.block_count(), Some(2));
+        assert_eq!(gguf.head_count(), None);
+        assert_eq!(gguf.quantization(), Some("Q4_K_M"));
+        assert_eq!(gguf.tokenizer_model(), Some("gpt2"));
+        assert_eq!(gguf.vocab_size(), Some(3));
+        assert_eq!(gguf.eos_token_id(), Some(1));
+        assert_eq!(gguf.parameter_count(), 64 * 3 + 64);
+
+        assert_eq!(gguf.tensors.len(), 2);
+        assert_eq!(gguf.tensors[0].type_name(), Some("Q4_K"));
+        assert_eq!(gguf.tensors[1].dimensions, vec![64]);
+        assert_eq!(gguf.data_offset, (bytes.len() as u64).div_ceil(32) * 32);
+
+        let info = gguf.model_info(false);
+        assert_eq!(info["general.architecture"], json!("llama"));
+        assert_eq!(info["llama.context_length"], json!(8192));
+        assert_eq!(info["llama.rope.freq_base"], json!(500000.0));
+        assert_eq!(info["general.parameter_count"], json!(256));
+        assert_eq!(info["tokenizer.ggml.tokens"], json!([]));
+        assert_eq!(
+            gguf.model_info(true)["tokenizer.ggml.tokens"],
+            json!(["<s>", "</s>", "a"])
+        );
+        assert_eq!(
+            gguf.get("tokenizer.ggml.tokens")
+                .unwrap()
+                .as_array()
+                .unwrap()[2],
+            MetadataValue::String("a".into())
+        );
+    }
+
+    #[test]
+    fn read_should_reject_deeply_nested_arrays() {
+        // An array of one array of one array... of `depth` levels, ending with an empty
+        // array of u32.
+        let nested = |depth: usize| {
+            let mut buf = vec![];
+            for _ in 1..depth {
+                buf.extend(9u32.to_le_bytes());
+                buf.extend(1u64.to_le_bytes());
+            }
+            buf.extend(4u32.to_le_bytes());
+            buf.extend(0u64.to_le_bytes());
+            buf
+        };
+
+        let bytes = Writer::default().kv("nested", 9, &nested(3)).build();
+        let gguf = Gguf::read(bytes.as_slice()).unwrap();
+        let outer = gguf.get("nested").unwrap().as_array().unwrap();
+        assert!(matches!(&outer[0], MetadataValue::Array(inner) if inner.len() == 1));
+
+        let bytes = Writer::default()
+            .kv("nested", 9, &nested(1_000_000))
+            .build();
+        assert!(matches!(
+            Gguf::read(bytes.as_slice()),
+            Err(OllamaError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn open_should_check_file() {
+        let path = std::env::temp_dir().join(format!("ollama-native-{}.gguf", std::process::id()));
+
+        // The header alone: the second tensor starts 256 bytes into the missing data.
+        std::fs::write(&path, llama()).unwrap();
+        assert!(Gguf::open(&path).is_err());
+
+        let mut bytes = llama();
+        bytes.resize(bytes.len() + 512, 0);
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(Gguf::open(&path).unwrap().tensors.len(), 2);
+
+        // Crafted offsets and dimensions must not overflow.
+        let mut bytes = Writer::default()
+            .tensor("huge", &[u64::MAX, 2], 0, u64::MAX)
+            .tensor("huger", &[u64::MAX], 0, 0)
+            .build();
+        bytes.resize(bytes.len() + 512, 0);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            Gguf::open(&path),
+            Err(OllamaError::InvalidFormat(_))
+        ));
+        let gguf = Gguf::read(bytes.as_slice()).unwrap();
+        assert_eq!(gguf.tensors[0].elements(), u64::MAX);
+        assert_eq!(gguf.parameter_count(), u64::MAX);
+        std::fs::remove_file(&path).unwrap();
+
+        let bytes = llama();
+        assert!(Gguf::read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Gguf::read(&b"GGML\x03\0\0\0"[..]).is_err());
+        let mut future = llama();
+        future[4] = 4;
+        assert!(Gguf::read(future.as_slice()).is_err());
+    }
+}
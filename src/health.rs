@@ -0,0 +1,307 @@
+//! Health and readiness checks of an Ollama server.
+//!
+//! [`HealthCheck`] pings the root endpoint and `/api/version`, then optionally checks that a
+//! model is present locally with `/api/show` and that it loads with a one-token generation.
+//! Each check is timed and its error, if any, kept in a [`HealthReport`] rather than returned,
+//! so a report always says how far the server got.
+//!
+//! [`HealthCheck::wait_until_ready`] repeats the checks until they all pass, e.g. to wait for
+//! a server started alongside tests or in another container.
+//!
+//! # Example
+//! ```rust,ignore
+//! use std::time::Duration;
+//! use ollama_native::Ollama;
+//!
+//! let ollama = Ollama::new("http://localhost:11434");
+//! let report = ollama.health().model("llama3.1:8b").await;
+//! for check in &report.checks {
+//!     println!("{:?}: {:?} {:?}", check.kind, check.latency, check.error);
+//! }
+//!
+//! ollama
+//!     .health()
+//!     .model("llama3.1:8b")
+//!     .load()
+//!     .wait_until_ready(Duration::from_secs(60))
+//!     .await?;
+//! ```
+
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::Ollama;
+use crate::action::OllamaRequest;
+use crate::error::OllamaError;
+
+/// What a check verifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    /// The root endpoint answers, as it does once the server listens.
+    Root,
+
+    /// `/api/version` answers with a version.
+    Version,
+
+    /// The model is present locally.
+    Model,
+
+    /// The model loads and generates a token.
+    Load,
+}
+
+/// The outcome of a check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub kind: CheckKind,
+
+    /// From sending the request of the check to its outcome.
+    pub latency: Duration,
+
+    /// Why the check failed, if it did.
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The outcome of a [`HealthCheck`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HealthReport {
+    /// Whether every check requested ran and passed.
+    pub ready: bool,
+
+    /// The version of the server, if `/api/version` answered.
+    pub version: Option<String>,
+
+    /// The checks run, in order. The model is only checked once the server answered, and
+    /// only loaded once it is present.
+    pub checks: Vec<CheckResult>,
+}
+
+impl HealthReport {
+    /// The first error of the checks, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.checks.iter().find_map(|check| check.error.as_deref())
+    }
+}
+
+/// Checks whether a server is up and, optionally, whether it serves a model, see the
+/// [module](self) docs.
+pub struct HealthCheck<'a> {
+    ollama: Ollama,
+    model: Option<&'a str>,
+    load: bool,
+    poll_interval: Duration,
+}
+
+impl<'a> HealthCheck<'a> {
+    pub fn new(ollama: Ollama) -> Self {
+        Self {
+            ollama,
+            model: None,
+            load: false,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Check that the model is present locally.
+    #[inline]
+    pub fn model(mut self, model: &'a str) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Check that the model loads, with a one-token generation. This may take as long as
+    /// loading the model does.
+    #[inline]
+    pub fn load(mut self) -> Self {
+        self.load = true;
+        self
+    }
+
+    /// Set how long [`HealthCheck::wait_until_ready`] waits between rounds of checks, 500
+    /// milliseconds by default.
+    #[inline]
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Run the checks until they all pass, returning the report that passed.
+    ///
+    /// # Errors
+    /// - `OllamaError::NotReady`: The checks did not pass within `timeout`.
+    pub async fn wait_until_ready(self, timeout: Duration) -> Result<HealthReport, OllamaError> {
+        let mut error = None;
+        let poll = async {
+            loop {
+                let report = self.run().await;
+                if report.ready {
+                    return report;
+                }
+                error = report.error().map(str::to_string);
+                tokio::time::sleep(self.poll_interval).await;
+            }
+        };
+
+        match tokio::time::timeout(timeout, poll).await {
+            Ok(report) => Ok(report),
+            Err(_) => Err(OllamaError::NotReady {
+                timeout,
+                error: error.unwrap_or_else(|| "no check finished".to_string()),
+            }),
+        }
+    }
+
+    async fn run(&self) -> HealthReport {
+        let mut report = HealthReport::default();
+
+        let root = timed(CheckKind::Root, async {
+            let response = self.ollama.client().get(&RootRequest).await?;
+            match response.error_for_status() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(OllamaError::RequestError(e)),
+            }
+        });
+        report.checks.push(root.await);
+
+        let version = timed(CheckKind::Version, async {
+            report.version = Some(self.ollama.version().await?.version);
+            Ok(())
+        });
+        let version = version.await;
+        let mut passed = version.passed();
+        report.checks.push(version);
+
+        if let Some(model) = self.model.filter(|_| passed) {
+            let show = timed(CheckKind::Model, async {
+                self.ollama.show_model_information(model).await.map(|_| ())
+            });
+            let show = show.await;
+            passed = show.passed();
+            report.checks.push(show);
+
+            if self.load && passed {
+                let generate = timed(CheckKind::Load, async {
+                    let generate = self.ollama.generate(model).prompt("Hi").num_predict(1);
+                    generate.await.map(|_| ())
+                });
+                report.checks.push(generate.await);
+            }
+        }
+
+        let expected = 2 + self.model.map_or(0, |_| 1 + self.load as usize);
+        report.ready =
+            report.checks.len() == expected && report.checks.iter().all(CheckResult::passed);
+        report
+    }
+}
+
+impl<'a> IntoFuture for HealthCheck<'a> {
+    type Output = HealthReport;
+    type IntoFuture = BoxFuture<'a, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move { self.run().await })
+    }
+}
+
+/// A request to the root endpoint, which answers "Ollama is running".
+#[derive(Serialize)]
+struct RootRequest;
+
+impl OllamaRequest for RootRequest {
+    fn path(&self) -> String {
+        "/".to_string()
+    }
+}
+
+async fn timed(
+    kind: CheckKind,
+    check: impl Future<Output = Result<(), OllamaError>>,
+) -> CheckResult {
+    let started = Instant::now();
+    let result = check.await;
+    CheckResult {
+        kind,
+        latency: started.elapsed(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CheckKind;
+    use crate::Ollama;
+    use crate::error::OllamaError;
+    use crate::mock::MockOllama;
+
+    #[tokio::test]
+    async fn health_should_report_each_check() {
+        let mock = MockOllama::start().await;
+        let report = mock.ollama().health().model("llama3.1:8b").load().await;
+
+        assert!(report.ready, "{:?}", report.error());
+        assert_eq!(report.version.as_deref(), Some("0.6.0"));
+        let kinds: Vec<_> = report.checks.iter().map(|check| check.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                CheckKind::Root,
+                CheckKind::Version,
+                CheckKind::Model,
+                CheckKind::Load
+            ]
+        );
+        assert_eq!(
+            mock.requests("/api/generate")[0]["options"]["num_predict"],
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_models_should_fail_without_loading() {
+        let mock = MockOllama::start().await;
+        let report = mock.ollama().health().model("qwen2.5").load().await;
+
+        assert!(!report.ready);
+        assert_eq!(report.checks.len(), 3);
+        assert!(report.checks[..2].iter().all(|check| check.passed()));
+        assert_eq!(report.checks[2].kind, CheckKind::Model);
+        assert!(report.error().unwrap().contains("model not found"));
+        assert!(mock.requests("/api/generate").is_empty());
+    }
+
+    #[tokio::test]
+    async fn wait_until_ready_should_retry_until_the_timeout() {
+        let mock = MockOllama::start().await;
+        mock.fail_next(2);
+        let health = mock
+            .ollama()
+            .health()
+            .poll_interval(Duration::from_millis(10));
+        let report = health
+            .wait_until_ready(Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(report.ready);
+        assert_eq!(mock.requests("/").len(), 3);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let health = Ollama::new(&dead)
+            .health()
+            .poll_interval(Duration::from_millis(10));
+        let error = health.wait_until_ready(Duration::from_millis(200)).await;
+        assert!(matches!(error, Err(OllamaError::NotReady { .. })));
+    }
+}
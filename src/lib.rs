@@ -2,8 +2,66 @@ pub mod abi;
 pub mod action;
 pub mod config;
 pub mod error;
+pub mod tokens;
+
+#[cfg(feature = "backend")]
+pub mod backend;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+
+#[cfg(feature = "cassette")]
+pub mod cassette;
+
+#[cfg(feature = "embeddings")]
+pub mod embeddings;
+
+#[cfg(feature = "gguf")]
+pub mod gguf;
+
+#[cfg(feature = "health")]
+pub mod health;
 pub mod ollama;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "openai")]
+pub mod openai;
+
+#[cfg(feature = "pool")]
+pub mod pool;
+
+#[cfg(feature = "rag")]
+pub mod rag;
+
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "session")]
+pub mod session;
+
+#[cfg(feature = "splitter")]
+pub mod splitter;
+
+#[cfg(feature = "store")]
+pub mod store;
+
+#[cfg(any(feature = "cache", feature = "embeddings"))]
+mod storage;
+
+#[cfg(feature = "tracing")]
+pub mod telemetry;
+
+#[cfg(feature = "template")]
+pub mod template;
+
+#[cfg(test)]
+mod mock;
+
 pub use ollama::Ollama;
 
 pub use abi::completion::{
@@ -14,9 +72,10 @@ pub use abi::version::VersionResponse;
 
 #[cfg(feature = "model")]
 pub use abi::model::{
-    create::CreateModelResponse, generate_embeddings::GenerateEmbeddingsResponse,
-    list_local::ListLocalModelsResponse, list_running::ListRunningModelsResponse,
-    pull::PullModelResponse, push::PushModelResponse, show_info::ShowModelInformationResponse,
+    batch_embeddings::BatchEmbedding, create::CreateModelResponse,
+    generate_embeddings::GenerateEmbeddingsResponse, list_local::ListLocalModelsResponse,
+    list_running::ListRunningModelsResponse, pull::PullModelResponse, push::PushModelResponse,
+    show_info::ShowModelInformationResponse, watch_running::RunningModelEvent,
 };
 
 #[cfg(feature = "model")]
@@ -0,0 +1,525 @@
+//! Prometheus metrics for the requests sent to Ollama.
+//!
+//! [`ClientMetrics`] keeps:
+//! - `ollama_client_requests_total{endpoint, model, status}`: finished requests. The status
+//!   is the HTTP status code, or the type of the error if no response was received, e.g.
+//!   `connect` or `timeout`.
+//! - `ollama_client_request_duration_seconds{endpoint, model}`: a histogram of the time from
+//!   sending a request to reading its response body in full.
+//! - `ollama_client_time_to_first_token_seconds{endpoint, model}`: a histogram of the time
+//!   from sending a streamed request to receiving its first chunk.
+//! - `ollama_client_in_flight_requests{endpoint, model}` and
+//!   `ollama_client_in_flight_streams{endpoint, model}`: requests, and streamed ones, whose
+//!   response body has not been read yet.
+//! - `ollama_client_prompt_tokens_total{model}` and
+//!   `ollama_client_generated_tokens_total{model}`.
+//! - `ollama_client_tokens_per_second{model}`: `eval_count / eval_duration` of the last
+//!   response of the model.
+//!
+//! The endpoint is the path of the request, e.g. `/api/chat`, or `/api/blobs` for blob
+//! uploads and checks, and the model is empty for requests without one. [`ClientMetrics::render`] encodes the metrics in the Prometheus text
+//! format, to be served from an existing `/metrics` endpoint.
+//!
+//! # Example
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use axum::{Router, routing::get};
+//! use ollama_native::{Ollama, metrics::{self, ClientMetrics}};
+//!
+//! let metrics = Arc::new(ClientMetrics::new());
+//! let ollama = Ollama::new("http://localhost:11434").metrics(metrics.clone());
+//!
+//! let app = Router::new().route(
+//!     "/metrics",
+//!     get(move || async move { ([("content-type", metrics::CONTENT_TYPE)], metrics.render()) }),
+//! );
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use serde_json::Value;
+
+use crate::action::observe::{ResponseStats, error_type, observe};
+use crate::error::OllamaError;
+
+/// The content type of [`ClientMetrics::render`].
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const DEFAULT_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+];
+
+/// Endpoint and model.
+type Labels = (String, String);
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    /// Observations per bucket, not cumulative.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self {
+            buckets: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, bounds: &[f64], value: f64) {
+        if let Some(i) = bounds.iter().position(|bound| value <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    requests: BTreeMap<(String, String, String), u64>,
+    durations: BTreeMap<Labels, Histogram>,
+    first_tokens: BTreeMap<Labels, Histogram>,
+    in_flight_requests: BTreeMap<Labels, i64>,
+    in_flight_streams: BTreeMap<Labels, i64>,
+    prompt_tokens: BTreeMap<String, u64>,
+    generated_tokens: BTreeMap<String, u64>,
+    tokens_per_second: BTreeMap<String, f64>,
+}
+
+/// Metrics of the requests sent by one or more clients, see the [module documentation](self).
+pub struct ClientMetrics {
+    buckets: Vec<f64>,
+    registry: Mutex<Registry>,
+}
+
+impl Default for ClientMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientMetrics {
+    pub fn new() -> Self {
+        Self {
+            buckets: DEFAULT_BUCKETS.to_vec(),
+            registry: Mutex::new(Registry::default()),
+        }
+    }
+
+    /// The upper bounds of the latency histogram buckets, in seconds (default: 5ms to 2m).
+    pub fn buckets(mut self, buckets: impl Into<Vec<f64>>) -> Self {
+        let mut buckets = buckets.into();
+        buckets.retain(|bound| bound.is_finite());
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+        self.buckets = buckets;
+        self
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        let name = "ollama_client_requests_total";
+        header(&mut out, name, "counter", "Requests sent to Ollama.");
+        for ((endpoint, model, status), value) in &registry.requests {
+            let labels = [("endpoint", endpoint), ("model", model), ("status", status)];
+            sample(&mut out, name, &labels, *value as f64);
+        }
+
+        self.histogram(
+            &mut out,
+            "ollama_client_request_duration_seconds",
+            "Time from sending a request to reading its response in full.",
+            &registry.durations,
+        );
+        self.histogram(
+            &mut out,
+            "ollama_client_time_to_first_token_seconds",
+            "Time from sending a streamed request to receiving its first chunk.",
+            &registry.first_tokens,
+        );
+
+        let gauges = [
+            (
+                "ollama_client_in_flight_requests",
+                "Requests whose response has not been read yet.",
+                &registry.in_flight_requests,
+            ),
+            (
+                "ollama_client_in_flight_streams",
+                "Streamed requests whose response has not been read yet.",
+                &registry.in_flight_streams,
+            ),
+        ];
+        for (name, help, values) in gauges {
+            header(&mut out, name, "gauge", help);
+            for ((endpoint, model), value) in values {
+                let labels = [("endpoint", endpoint), ("model", model)];
+                sample(&mut out, name, &labels, *value as f64);
+            }
+        }
+
+        let tokens = [
+            (
+                "ollama_client_prompt_tokens_total",
+                "Tokens in the prompts.",
+                &registry.prompt_tokens,
+            ),
+            (
+                "ollama_client_generated_tokens_total",
+                "Tokens generated.",
+                &registry.generated_tokens,
+            ),
+        ];
+        for (name, help, values) in tokens {
+            header(&mut out, name, "counter", help);
+            for (model, value) in values {
+                sample(&mut out, name, &[("model", model)], *value as f64);
+            }
+        }
+
+        let name = "ollama_client_tokens_per_second";
+        header(
+            &mut out,
+            name,
+            "gauge",
+            "Generation speed of the last response.",
+        );
+        for (model, value) in &registry.tokens_per_second {
+            sample(&mut out, name, &[("model", model)], *value);
+        }
+
+        out
+    }
+
+    fn histogram(
+        &self,
+        out: &mut String,
+        name: &str,
+        help: &str,
+        histograms: &BTreeMap<Labels, Histogram>,
+    ) {
+        header(out, name, "histogram", help);
+        for ((endpoint, model), histogram) in histograms {
+            let mut cumulative = 0;
+            for (bound, count) in self.buckets.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                let le = bound.to_string();
+                let labels = [("endpoint", endpoint), ("model", model), ("le", &le)];
+                sample(out, &format!("{name}_bucket"), &labels, cumulative as f64);
+            }
+            let inf = "+Inf".to_string();
+            let labels = [("endpoint", endpoint), ("model", model), ("le", &inf)];
+            sample(
+                out,
+                &format!("{name}_bucket"),
+                &labels,
+                histogram.count as f64,
+            );
+
+            let labels = [("endpoint", endpoint), ("model", model)];
+            sample(out, &format!("{name}_sum"), &labels, histogram.sum);
+            sample(
+                out,
+                &format!("{name}_count"),
+                &labels,
+                histogram.count as f64,
+            );
+        }
+    }
+
+    /// Send the request, counting it as in flight until its response body is read.
+    pub(crate) async fn instrument(
+        self: &Arc<Self>,
+        path: &str,
+        body: Option<&Bytes>,
+        send: impl Future<Output = Result<reqwest::Response, OllamaError>>,
+    ) -> Result<reqwest::Response, OllamaError> {
+        let request = InFlight::start(self.clone(), path, body);
+        let started = Instant::now();
+        match send.await {
+            Ok(response) => {
+                let status = response.status().as_str().to_string();
+                Ok(observe(response, started, move |stats| {
+                    request.finish(&status, stats.elapsed, Some(&stats))
+                }))
+            }
+            Err(e) => {
+                request.finish(error_type(&e), started.elapsed(), None);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A request whose response body has not been read yet.
+struct InFlight {
+    metrics: Arc<ClientMetrics>,
+    labels: Labels,
+    stream: bool,
+}
+
+impl InFlight {
+    fn start(metrics: Arc<ClientMetrics>, path: &str, body: Option<&Bytes>) -> Self {
+        let body: Value = body
+            .and_then(|b| serde_json::from_slice(b).ok())
+            .unwrap_or_default();
+        let model = body["model"].as_str().unwrap_or_default();
+        // One series for all blobs rather than one per digest.
+        let endpoint = match path.starts_with("/api/blobs/") {
+            true => "/api/blobs",
+            false => path,
+        };
+        let labels = (endpoint.to_string(), model.to_string());
+        let stream = body["stream"] == true;
+
+        let mut registry = metrics.registry.lock().unwrap();
+        *registry
+            .in_flight_requests
+            .entry(labels.clone())
+            .or_default() += 1;
+        if stream {
+            *registry
+                .in_flight_streams
+                .entry(labels.clone())
+                .or_default() += 1;
+        }
+        drop(registry);
+
+        Self {
+            metrics,
+            labels,
+            stream,
+        }
+    }
+
+    fn finish(self, status: &str, elapsed: Duration, stats: Option<&ResponseStats>) {
+        let bounds = &self.metrics.buckets;
+        let mut registry = self.metrics.registry.lock().unwrap();
+        let (endpoint, model) = self.labels.clone();
+
+        *registry
+            .requests
+            .entry((endpoint, model.clone(), status.to_string()))
+            .or_default() += 1;
+        registry
+            .durations
+            .entry(self.labels.clone())
+            .or_insert_with(|| Histogram::new(bounds))
+            .observe(bounds, elapsed.as_secs_f64());
+
+        let Some(stats) = stats else {
+            return;
+        };
+        if let Some(first_chunk) = stats.first_chunk.filter(|_| self.stream) {
+            registry
+                .first_tokens
+                .entry(self.labels.clone())
+                .or_insert_with(|| Histogram::new(bounds))
+                .observe(bounds, first_chunk.as_secs_f64());
+        }
+        if let Some(tokens) = stats.input_tokens {
+            *registry.prompt_tokens.entry(model.clone()).or_default() += tokens.max(0) as u64;
+        }
+        if let Some(tokens) = stats.output_tokens {
+            *registry.generated_tokens.entry(model.clone()).or_default() += tokens.max(0) as u64;
+        }
+        if let Some(speed) = stats.tokens_per_second() {
+            registry.tokens_per_second.insert(model, speed);
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut registry = self.metrics.registry.lock().unwrap();
+        if let Some(value) = registry.in_flight_requests.get_mut(&self.labels) {
+            *value -= 1;
+        }
+        if let Some(value) = registry
+            .in_flight_streams
+            .get_mut(&self.labels)
+            .filter(|_| self.stream)
+        {
+            *value -= 1;
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &String)], value: f64) {
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|(label, value)| format!("{label}=\"{}\"", escape(value)))
+        .collect();
+    let _ = writeln!(out, "{name}{{{}}} {value}", labels.join(","));
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio_stream::StreamExt;
+
+    use super::ClientMetrics;
+    use crate::Ollama;
+    use crate::action::IntoStream;
+    use crate::mock::MockOllama;
+
+    fn value<'a>(rendered: &'a str, series: &str) -> Option<&'a str> {
+        rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+    }
+
+    #[tokio::test]
+    async fn requests_should_be_counted_and_timed() {
+        let mock = MockOllama::start().await;
+        let metrics = Arc::new(ClientMetrics::new().buckets([1.0, 0.5]));
+        let ollama = mock.ollama().metrics(metrics.clone());
+
+        ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .await
+            .unwrap();
+        let mut stream = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .stream()
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap();
+
+        let chat = r#"{endpoint="/api/chat",model="llama3.1:8b"}"#;
+        let rendered = metrics.render();
+        for name in ["in_flight_requests", "in_flight_streams"] {
+            let series = format!("ollama_client_{name}{chat}");
+            assert_eq!(value(&rendered, &series), Some("1"));
+        }
+
+        drop(stream);
+        assert!(ollama.show_model_information("missing").await.is_err());
+        let offline = Ollama::new("http://127.0.0.1:1").metrics(metrics.clone());
+        assert!(offline.version().await.is_err());
+
+        let rendered = metrics.render();
+        let expected = [
+            (
+                r#"ollama_client_requests_total{endpoint="/api/chat",model="llama3.1:8b",status="200"}"#,
+                "2",
+            ),
+            (
+                r#"ollama_client_requests_total{endpoint="/api/show",model="missing",status="404"}"#,
+                "1",
+            ),
+            (
+                r#"ollama_client_requests_total{endpoint="/api/version",model="",status="connect"}"#,
+                "1",
+            ),
+            (
+                r#"ollama_client_request_duration_seconds_bucket{endpoint="/api/chat",model="llama3.1:8b",le="0.5"}"#,
+                "2",
+            ),
+            (
+                r#"ollama_client_request_duration_seconds_bucket{endpoint="/api/chat",model="llama3.1:8b",le="1"}"#,
+                "2",
+            ),
+            (
+                r#"ollama_client_request_duration_seconds_bucket{endpoint="/api/chat",model="llama3.1:8b",le="+Inf"}"#,
+                "2",
+            ),
+            (
+                r#"ollama_client_time_to_first_token_seconds_count{endpoint="/api/chat",model="llama3.1:8b"}"#,
+                "1",
+            ),
+            (
+                r#"ollama_client_in_flight_requests{endpoint="/api/chat",model="llama3.1:8b"}"#,
+                "0",
+            ),
+            (
+                r#"ollama_client_in_flight_streams{endpoint="/api/chat",model="llama3.1:8b"}"#,
+                "0",
+            ),
+            (
+                r#"ollama_client_prompt_tokens_total{model="llama3.1:8b"}"#,
+                "20",
+            ),
+            (
+                r#"ollama_client_generated_tokens_total{model="llama3.1:8b"}"#,
+                "6",
+            ),
+            (
+                r#"ollama_client_tokens_per_second{model="llama3.1:8b"}"#,
+                "6",
+            ),
+        ];
+        for (series, expected) in expected {
+            assert_eq!(value(&rendered, series), Some(expected), "{series}");
+        }
+        assert!(rendered.contains("# TYPE ollama_client_request_duration_seconds histogram\n"));
+    }
+
+    #[tokio::test]
+    async fn model_management_requests_should_be_counted() {
+        let mock = MockOllama::start().await;
+        let metrics = Arc::new(ClientMetrics::new());
+        let ollama = mock.ollama().metrics(metrics.clone());
+
+        let path = std::env::temp_dir().join(format!("ollama-native-blob-{}", std::process::id()));
+        std::fs::write(&path, b"GGUF").unwrap();
+        let digest = "sha256:0bc6ff5f4d3b5e33ee9ab4cb6e2b3fbd52a1f5a4cb1d2b3d35e0fb5ec4f8cbd2";
+        assert!(ollama.check_blob_exists(digest).await.is_err());
+        ollama
+            .push_blob(path.to_str().unwrap(), digest)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        ollama.check_blob_exists(digest).await.unwrap();
+        ollama.delete_model("llama3.1:8b").await.unwrap();
+
+        let rendered = metrics.render();
+        let expected = [
+            (r#"endpoint="/api/blobs",model="",status="404""#, "1"),
+            (r#"endpoint="/api/blobs",model="",status="201""#, "1"),
+            (r#"endpoint="/api/blobs",model="",status="200""#, "1"),
+            (
+                r#"endpoint="/api/delete",model="llama3.1:8b",status="200""#,
+                "1",
+            ),
+        ];
+        for (labels, expected) in expected {
+            let series = format!("ollama_client_requests_total{{{labels}}}");
+            assert_eq!(value(&rendered, &series), Some(expected), "{series}");
+        }
+    }
+
+    #[test]
+    fn labels_should_be_escaped() {
+        let mut out = String::new();
+        let model = "a\"b\\c\nd".to_string();
+        super::sample(&mut out, "m", &[("model", &model)], 1.5);
+        assert_eq!(out, "m{model=\"a\\\"b\\\\c\\nd\"} 1.5\n");
+    }
+}
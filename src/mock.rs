@@ -0,0 +1,557 @@
+//! A minimal in-process Ollama server for tests that need a real HTTP round trip.
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use serde_json::{Value, json};
+
+use crate::Ollama;
+
+#[derive(Default)]
+pub(crate) struct MockState {
+    /// Every request received, as `(path, body)`.
+    requests: Mutex<Vec<(String, Value)>>,
+    /// Number of upcoming requests to fail with a 500.
+    failures: AtomicUsize,
+    /// Models listed by `/api/ps`.
+    loaded: Mutex<Vec<String>>,
+    /// Digests of the blobs uploaded to `/api/blobs`.
+    blobs: Mutex<Vec<String>>,
+}
+
+pub(crate) struct MockOllama {
+    pub url: String,
+    state: Arc<MockState>,
+}
+
+impl MockOllama {
+    pub async fn start() -> Self {
+        let state = Arc::new(MockState::default());
+        let router = Router::new()
+            .route("/api/embed", post(embed))
+            .route("/api/tags", get(tags))
+            .route("/api/show", post(show))
+            .route("/api/chat", post(chat))
+            .route("/api/generate", post(generate))
+            .route("/", get(root))
+            .route("/api/version", get(version))
+            .route("/api/ps", get(running))
+            .route("/api/blobs/{digest}", post(push_blob).head(check_blob))
+            .route("/api/create", post(create))
+            .route("/api/delete", delete(delete_model))
+            .route("/v1/chat/completions", post(openai_chat))
+            .route("/v1/completions", post(openai_completions))
+            .route("/v1/embeddings", post(openai_embeddings))
+            .route("/v1/models", get(openai_models))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Self { url, state }
+    }
+
+    pub fn ollama(&self) -> Ollama {
+        Ollama::new(&self.url)
+    }
+
+    /// Fail the next `n` requests with an internal server error.
+    pub fn fail_next(&self, n: usize) {
+        self.state.failures.store(n, Ordering::SeqCst);
+    }
+
+    /// List `model` as loaded in `/api/ps`.
+    pub fn load(&self, model: &str) {
+        self.state.loaded.lock().unwrap().push(model.to_string());
+    }
+
+    /// Bodies of the requests received on `path`, in arrival order.
+    pub fn requests(&self, path: &str) -> Vec<Value> {
+        self.state
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _)| p == path)
+            .map(|(_, body)| body.clone())
+            .collect()
+    }
+}
+
+impl MockState {
+    /// Records the request and returns the error response to send instead, if any.
+    /// The body is parsed by hand as the client does not send a JSON content type.
+    fn record(&self, uri: &Uri, body: &[u8]) -> (Value, Option<Response>) {
+        let body: Value = serde_json::from_slice(body).unwrap_or_default();
+        self.requests
+            .lock()
+            .unwrap()
+            .push((uri.path().to_string(), body.clone()));
+
+        let fail = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        let error = fail.then(|| {
+            let body = Json(json!({ "error": "mock failure" }));
+            (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+        });
+        (body, error)
+    }
+}
+
+/// Embeds each input as `[chars, words, 1.0]`. Batches starting with a short input are
+/// answered more slowly so responses arrive out of order under concurrency. An empty model
+/// name is rejected with a 400, as Ollama does.
+async fn embed(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+    if body["model"] == "" {
+        let body = Json(json!({ "error": "model is required" }));
+        return (StatusCode::BAD_REQUEST, body).into_response();
+    }
+
+    let inputs = inputs(&body);
+    let first_len = inputs.first().map_or(0, |s| s.chars().count()) as u64;
+    tokio::time::sleep(Duration::from_millis(20u64.saturating_sub(first_len))).await;
+
+    let embeddings: Vec<Vec<f64>> = inputs.iter().map(|s| embedding(s)).collect();
+
+    Json(json!({
+        "model": body["model"],
+        "embeddings": embeddings,
+        "total_duration": 1000,
+        "prompt_eval_count": inputs.len(),
+    }))
+    .into_response()
+}
+
+fn inputs(body: &Value) -> Vec<String> {
+    match &body["input"] {
+        Value::String(s) => vec![s.clone()],
+        input => serde_json::from_value(input.clone()).unwrap_or_default(),
+    }
+}
+
+fn embedding(input: &str) -> Vec<f64> {
+    vec![
+        input.chars().count() as f64,
+        input.split_whitespace().count() as f64,
+        1.0,
+    ]
+}
+
+async fn tags(State(state): State<Arc<MockState>>, uri: Uri) -> Response {
+    if let (_, Some(response)) = state.record(&uri, &[]) {
+        return response;
+    }
+
+    let model = |name: &str, digest: &str, family: &str, parameter_size: &str| {
+        json!({
+            "name": name,
+            "modified_at": "2025-01-01T00:00:00Z",
+            "size": 1000,
+            "digest": digest,
+            "details": {
+                "format": "gguf",
+                "family": family,
+                "families": [family],
+                "parameter_size": parameter_size,
+                "quantization_level": "Q4_0",
+            },
+        })
+    };
+
+    Json(json!({
+        "models": [
+            model("all-minilm:latest", "sha256:1b226e2802db", "bert", "23M"),
+            model("llama3.1:8b", "sha256:46e0c10c039e", "llama", "8.0B"),
+        ]
+    }))
+    .into_response()
+}
+
+async fn root(State(state): State<Arc<MockState>>, uri: Uri) -> Response {
+    if let (_, Some(response)) = state.record(&uri, &[]) {
+        return response;
+    }
+    "Ollama is running".into_response()
+}
+
+/// Answers probes, which are not recorded.
+async fn version() -> Response {
+    Json(json!({ "version": "0.6.0" })).into_response()
+}
+
+async fn running(State(state): State<Arc<MockState>>) -> Response {
+    let loaded = state.loaded.lock().unwrap();
+    let models = loaded.iter().map(|model| {
+        json!({
+            "name": model,
+            "model": model,
+            "size": 1000,
+            "digest": "sha256:46e0c10c039e",
+            "details": { "format": "gguf", "family": "llama" },
+            "expires_at": "2025-01-01T00:05:00Z",
+            "size_vram": 1000,
+        })
+    });
+    Json(json!({ "models": models.collect::<Vec<_>>() })).into_response()
+}
+
+async fn push_blob(
+    State(state): State<Arc<MockState>>,
+    Path(digest): Path<String>,
+    uri: Uri,
+) -> Response {
+    if let (_, Some(response)) = state.record(&uri, &[]) {
+        return response;
+    }
+    state.blobs.lock().unwrap().push(digest);
+    StatusCode::CREATED.into_response()
+}
+
+async fn check_blob(
+    State(state): State<Arc<MockState>>,
+    Path(digest): Path<String>,
+    uri: Uri,
+) -> Response {
+    if let (_, Some(response)) = state.record(&uri, &[]) {
+        return response;
+    }
+    match state.blobs.lock().unwrap().contains(&digest) {
+        true => StatusCode::OK.into_response(),
+        false => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Creates a model from the uploaded blobs, failing with a 400 if one is missing.
+async fn create(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let blobs = state.blobs.lock().unwrap();
+    let mut files = body["files"].as_object().into_iter().flatten();
+    if let Some((name, _)) = files.find(|(_, digest)| !blobs.iter().any(|b| *digest == b)) {
+        let body = Json(json!({ "error": format!("blob for {name} not found") }));
+        return (StatusCode::BAD_REQUEST, body).into_response();
+    }
+    Json(json!({ "status": "success" })).into_response()
+}
+
+async fn delete_model(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    match state.record(&uri, &body) {
+        (_, Some(response)) => response,
+        (_, None) => StatusCode::OK.into_response(),
+    }
+}
+
+async fn show(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let (family, context_length) = match body["model"].as_str() {
+        Some("all-minilm" | "all-minilm:latest") => ("bert", 512),
+        Some("llama3.1:8b") => ("llama", 8192),
+        _ => {
+            let body = Json(json!({ "error": "model not found" }));
+            return (StatusCode::NOT_FOUND, body).into_response();
+        }
+    };
+
+    Json(json!({
+        "license": "",
+        "modelfile": "",
+        "parameters": "",
+        "template": "{{ .Prompt }}",
+        "details": {
+            "format": "gguf",
+            "family": family,
+            "families": [family],
+            "parameter_size": "8.0B",
+            "quantization_level": "Q4_0",
+        },
+        "model_info": {
+            "general.architecture": family,
+            format!("{family}.context_length"): context_length,
+            format!("{family}.embedding_length"): 384,
+        },
+    }))
+    .into_response()
+}
+
+/// Answers with `echo: <last user message>`. Streamed answers are sent one word per line.
+async fn chat(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let question = body["messages"]
+        .as_array()
+        .and_then(|messages| messages.iter().rev().find(|m| m["role"] == "user"))
+        .and_then(|m| m["content"].as_str())
+        .unwrap_or_default();
+    let answer = format!("echo: {question}");
+    let model = &body["model"];
+    let created_at = "2025-01-01T00:00:00Z";
+    let usage = json!({
+        "total_duration": 2000,
+        "prompt_eval_count": 10,
+        "eval_count": answer.split(' ').count(),
+        "eval_duration": 500_000_000,
+    });
+
+    let done = |content: &str| {
+        let mut done = json!({
+            "model": model,
+            "created_at": created_at,
+            "message": { "role": "assistant", "content": content },
+            "done": true,
+            "done_reason": "stop",
+        });
+        done.as_object_mut()
+            .unwrap()
+            .extend(usage.as_object().unwrap().clone());
+        done
+    };
+
+    if body["stream"] != true {
+        return Json(done(&answer)).into_response();
+    }
+
+    let mut lines = String::new();
+    for (i, word) in answer.split(' ').enumerate() {
+        let content = if i == 0 {
+            word.to_string()
+        } else {
+            format!(" {word}")
+        };
+        let chunk = json!({
+            "model": model,
+            "created_at": created_at,
+            "message": { "role": "assistant", "content": content },
+            "done": false,
+        });
+        lines.push_str(&format!("{chunk}\n"));
+    }
+    lines.push_str(&format!("{}\n", done("")));
+    lines.into_response()
+}
+
+/// Answers with `echo: <prompt>`. Streamed answers are sent one word per line.
+async fn generate(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let answer = format!("echo: {}", body["prompt"].as_str().unwrap_or_default());
+    let chunk = |response: &str, done: bool| {
+        let mut chunk = json!({
+            "model": body["model"],
+            "created_at": "2025-01-01T00:00:00Z",
+            "response": response,
+            "done": done,
+        });
+        if done {
+            chunk["done_reason"] = json!("stop");
+            chunk["eval_count"] = json!(answer.split(' ').count());
+        }
+        chunk
+    };
+
+    if body["stream"] != true {
+        return Json(chunk(&answer, true)).into_response();
+    }
+
+    let mut lines = String::new();
+    for word in answer.split_inclusive(' ') {
+        lines.push_str(&format!("{}\n", chunk(word, false)));
+    }
+    lines.push_str(&format!("{}\n", chunk("", true)));
+    lines.into_response()
+}
+
+/// Created timestamp of every OpenAI-compatible response, `2025-01-01T00:00:00Z`.
+const CREATED: i64 = 1735689600;
+
+/// `/v1/chat/completions`, answering like [`chat`]. Requests with tools are answered with
+/// a call to the first tool for Paris.
+async fn openai_chat(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let question = body["messages"]
+        .as_array()
+        .and_then(|messages| messages.iter().rev().find(|m| m["role"] == "user"))
+        .map(|m| match &m["content"] {
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<String>(),
+            content => content.as_str().unwrap_or_default().to_string(),
+        })
+        .unwrap_or_default();
+
+    if let Some(tool) = body["tools"].as_array().and_then(|tools| tools.first()) {
+        return Json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": CREATED,
+            "model": body["model"],
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {
+                            "name": tool["function"]["name"],
+                            "arguments": r#"{"city":"Paris"}"#,
+                        },
+                    }],
+                },
+                "finish_reason": "tool_calls",
+            }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 1, "total_tokens": 11 },
+        }))
+        .into_response();
+    }
+
+    openai_answer(
+        &body,
+        "chat.completion",
+        &format!("echo: {question}"),
+        |text| json!({ "role": "assistant", "content": text }),
+    )
+}
+
+/// `/v1/completions`, answering with `echo: <prompt>`.
+async fn openai_completions(
+    State(state): State<Arc<MockState>>,
+    uri: Uri,
+    body: Bytes,
+) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let answer = format!("echo: {}", body["prompt"].as_str().unwrap_or_default());
+    openai_answer(&body, "text_completion", &answer, |text| json!(text))
+}
+
+/// Answers with `answer` as a whole or, when streaming, as server-sent events of one word
+/// each, followed by a finish chunk and a usage chunk. `content` builds the `message`
+/// (or `delta`) of chat completions and the `text` of completions.
+fn openai_answer(
+    body: &Value,
+    object: &str,
+    answer: &str,
+    content: impl Fn(&str) -> Value,
+) -> Response {
+    let chat = object == "chat.completion";
+    let choice = |text: &str, finish_reason: Value| {
+        let key = match (chat, body["stream"] == true) {
+            (true, true) => "delta",
+            (true, false) => "message",
+            (false, _) => "text",
+        };
+        json!({ "index": 0, key: content(text), "finish_reason": finish_reason })
+    };
+    let completion = |choices: Vec<Value>, usage: Value| {
+        let object = match body["stream"] == true && chat {
+            true => "chat.completion.chunk",
+            false => object,
+        };
+        json!({
+            "id": "cmpl-1",
+            "object": object,
+            "created": CREATED,
+            "model": body["model"],
+            "choices": choices,
+            "usage": usage,
+        })
+    };
+    let words = answer.split(' ').count();
+    let usage =
+        json!({ "prompt_tokens": 10, "completion_tokens": words, "total_tokens": 10 + words });
+
+    if body["stream"] != true {
+        return Json(completion(vec![choice(answer, json!("stop"))], usage)).into_response();
+    }
+
+    let mut events = String::new();
+    for (i, word) in answer.split(' ').enumerate() {
+        let text = if i == 0 {
+            word.to_string()
+        } else {
+            format!(" {word}")
+        };
+        let chunk = completion(vec![choice(&text, Value::Null)], Value::Null);
+        events.push_str(&format!("data: {chunk}\n\n"));
+    }
+    let finish = completion(vec![choice("", json!("stop"))], Value::Null);
+    events.push_str(&format!("data: {finish}\n\n"));
+    events.push_str(&format!("data: {}\n\n", completion(vec![], usage)));
+    events.push_str("data: [DONE]\n\n");
+    events.into_response()
+}
+
+/// `/v1/embeddings`, embedding like [`embed`] but listing the results in reverse order.
+async fn openai_embeddings(State(state): State<Arc<MockState>>, uri: Uri, body: Bytes) -> Response {
+    let (body, error) = state.record(&uri, &body);
+    if let Some(response) = error {
+        return response;
+    }
+
+    let inputs = inputs(&body);
+    let data: Vec<Value> = inputs
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, input)| {
+            json!({ "object": "embedding", "index": index, "embedding": embedding(input) })
+        })
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "model": body["model"],
+        "data": data,
+        "usage": { "prompt_tokens": inputs.len(), "total_tokens": inputs.len() },
+    }))
+    .into_response()
+}
+
+async fn openai_models() -> Response {
+    let model = |id: &str| json!({ "id": id, "object": "model", "created": CREATED, "owned_by": "library" });
+    Json(json!({
+        "object": "list",
+        "data": [model("all-minilm:latest"), model("llama3.1:8b")],
+    }))
+    .into_response()
+}
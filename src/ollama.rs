@@ -5,6 +5,32 @@ use crate::action::{
 };
 use crate::config::OllamaConfig;
 
+#[cfg(feature = "openai")]
+use crate::config::WireFormat;
+
+#[cfg(feature = "cassette")]
+use crate::cassette::Cassette;
+
+#[cfg(feature = "health")]
+use crate::health::HealthCheck;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::ClientMetrics;
+
+#[cfg(feature = "pool")]
+use crate::pool::HostPool;
+
+#[cfg(feature = "scheduler")]
+use crate::scheduler::Scheduler;
+
+#[cfg(any(
+    feature = "cassette",
+    feature = "metrics",
+    feature = "pool",
+    feature = "scheduler"
+))]
+use std::sync::Arc;
+
 #[cfg(feature = "model")]
 use crate::action::model::{
     check_blob_exists::CheckBlobExistsAction, copy::CopyModelAction, create::CreateModelAction,
@@ -13,6 +39,13 @@ use crate::action::model::{
     push::PushModelAction, push_blob::PushBlobAction, show_info::ShowModelInformationAction,
 };
 
+#[cfg(feature = "model")]
+#[cfg(feature = "stream")]
+use crate::action::model::{
+    batch_embeddings::BatchEmbeddingsAction, watch_running::WatchRunningModelsAction,
+};
+
+#[derive(Clone)]
 pub struct Ollama {
     client: OllamaClient,
 }
@@ -35,6 +68,99 @@ impl Ollama {
         Self { client }
     }
 
+    /// Creates a client that spreads its requests over the hosts of a pool, see
+    /// [`crate::pool`]. Clients sharing a pool share its health and in-flight counts.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use std::sync::Arc;
+    /// use ollama_native::{Ollama, pool::{HostPool, Policy}};
+    ///
+    /// let pool = HostPool::new(["http://gpu-1:11434", "http://gpu-2:11434"])
+    ///     .policy(Policy::LeastInFlight);
+    /// let ollama = Ollama::with_pool(Arc::new(pool));
+    /// ```
+    #[cfg(feature = "pool")]
+    pub fn with_pool(pool: Arc<HostPool>) -> Self {
+        let config = OllamaConfig::from_url(pool.first_url());
+        let mut client = OllamaClient::new(config);
+        client.pool = Some(pool);
+        Self { client }
+    }
+
+    /// Selects the API that requests are sent to. With [`WireFormat::OpenAi`], `chat`,
+    /// `generate`, `generate_embeddings` and `list_local_models` use the OpenAI-compatible
+    /// endpoints under `/v1` and map the results into the usual response types, see
+    /// [`crate::openai`] for what is not carried over. Other requests are unaffected.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use ollama_native::{Ollama, config::WireFormat};
+    ///
+    /// let ollama = Ollama::new("http://localhost:11434").wire_format(WireFormat::OpenAi);
+    /// ```
+    #[cfg(feature = "openai")]
+    pub fn wire_format(mut self, wire_format: WireFormat) -> Self {
+        self.client.config.wire_format = wire_format;
+        self
+    }
+
+    /// Records the requests sent by this client into a cassette, or answers them from one,
+    /// see [`crate::cassette`].
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use std::sync::Arc;
+    /// use ollama_native::{Ollama, cassette::Cassette};
+    ///
+    /// let cassette = Arc::new(Cassette::record("tests/cassettes/chat.json"));
+    /// let ollama = Ollama::new("http://localhost:11434").cassette(cassette);
+    /// ```
+    #[cfg(feature = "cassette")]
+    pub fn cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.client.cassette = Some(cassette);
+        self
+    }
+
+    /// Counts and times the requests sent by this client, see [`crate::metrics`].
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use std::sync::Arc;
+    /// use ollama_native::{Ollama, metrics::ClientMetrics};
+    ///
+    /// let metrics = Arc::new(ClientMetrics::new());
+    /// let ollama = Ollama::new("http://localhost:11434").metrics(metrics.clone());
+    /// println!("{}", metrics.render());
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: Arc<ClientMetrics>) -> Self {
+        self.client.metrics = Some(metrics);
+        self
+    }
+
+    /// Queues the chat, generate and embedding requests of this client, see
+    /// [`crate::scheduler`]. Clients sharing a scheduler share its limits.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use std::sync::Arc;
+    /// use ollama_native::{Ollama, scheduler::Scheduler};
+    ///
+    /// let scheduler = Arc::new(Scheduler::new().default_model_limit(1));
+    /// let ollama = Ollama::new("http://localhost:11434").scheduler(scheduler);
+    /// ```
+    #[cfg(feature = "scheduler")]
+    pub fn scheduler(mut self, scheduler: Arc<Scheduler>) -> Self {
+        self.client.scheduler = Some(scheduler);
+        self
+    }
+
+    #[cfg(any(feature = "session", feature = "health"))]
+    pub(crate) fn client(&self) -> &OllamaClient {
+        &self.client
+    }
+
     /// Generate a response for a given prompt with a provided model.
     /// The final response object will include statistics and additional data from the request.
     ///
@@ -44,14 +170,17 @@ impl Ollama {
     /// # Methods
     /// - `prompt`: The prompt to generate a response for.
     /// - `suffix`: The text after the model response.
-    /// - `image`: Insert a base64-encoded image to image list.
-    /// - `images`: Insert a list of base64-encoded images (for multimodal models such as `llava`) to image list.
+    /// - `image`: Insert an image to image list: a base64 string, a `data:` URI, an `http(s)` URL, a path or raw bytes.
+    /// - `images`: Insert a list of images (for multimodal models such as `llava`) to image list.
+    /// - `max_image_bytes`: Reject images larger than the given size after downscaling.
+    /// - `max_image_dimension`: Downscale images larger than the given width or height (requires the `image` feature).
     /// - `format`: The format to return a response in. Format can be `json` or a JSON schema.
     /// - `options`: Additional model parameters listed in [Modelfile](https://github.com/ollama/ollama/blob/main/docs/modelfile.md#valid-parameters-and-values) such as `temperature`.
     /// - `system`: System message to (overrides what is defined in the `Modelfile`).
     /// - `template`: The prompt template to use (overrides what is defined in the `Modelfile`).
+    /// - `think`: Let thinking models return their reasoning separately, in the `thinking` field.
     /// - `raw`: If specified no formatting will be applied to the prompt.
-    /// You may choose to use the `raw` parameter if you are specifying a full templated prompt in your request to the API.
+    ///   You may choose to use the `raw` parameter if you are specifying a full templated prompt in your request to the API.
     /// - `keep_alive`: Controls how long the model will stay loaded into memory following the request (default: 5m).
     /// - `stream`: If not specified, the response will be returned as a single response object, rather than a stream of objects.
     /// - `load`: Load a model into memory, this method will convert the reponse into [GenerateCompletionModelResponse][`crate::abi::completion::generate::GenerateCompletionResponse`].
@@ -109,6 +238,9 @@ impl Ollama {
     /// - `messages`: Insert messages to message list.
     /// - `tool`: Insert a tool in JSON for the model to use if supported.
     /// - `tools`: Insert a list of tools in JSON for the model to use if supported.
+    /// - `think`: Let thinking models return their reasoning separately, in the message's `thinking` field.
+    /// - `max_image_bytes`: Reject message images larger than the given size after downscaling.
+    /// - `max_image_dimension`: Downscale message images larger than the given width or height (requires the `image` feature).
     /// - `format`: The format to return a response in. Format can be `json` or a JSON schema.
     /// - `options`: Additional model parameters listed in [Modelfile](https://github.com/ollama/ollama/blob/main/docs/modelfile.md#valid-parameters-and-values) such as `temperature`.
     /// - `keep_alive`: Controls how long the model will stay loaded into memory following the request (default: 5m).
@@ -166,6 +298,29 @@ impl Ollama {
     pub fn version(&self) -> VersionAction<'_> {
         VersionAction::new(self.client.clone())
     }
+
+    /// Check whether the server is up and, optionally, whether it serves a model, see
+    /// [`crate::health`].
+    ///
+    /// # Methods
+    /// - `model`: Check that the model is present locally.
+    /// - `load`: Check that the model loads, with a one-token generation.
+    /// - `poll_interval`: The time between rounds of checks in `wait_until_ready`.
+    /// - `wait_until_ready`: Run the checks until they all pass or the timeout elapses.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use std::time::Duration;
+    ///
+    /// let report = ollama.health().model("llama3.1:8b").load().await;
+    /// assert!(report.ready, "{:?}", report.error());
+    ///
+    /// ollama.health().wait_until_ready(Duration::from_secs(30)).await?;
+    /// ```
+    #[cfg(feature = "health")]
+    pub fn health<'a>(&self) -> HealthCheck<'a> {
+        HealthCheck::new(self.clone())
+    }
 }
 
 #[cfg(feature = "model")]
@@ -174,6 +329,7 @@ impl Ollama {
     /// - another model;
     /// - a safetensors directory; or
     /// - a GGUF file.
+    ///
     /// If you are creating a model from a safetensors directory or from a GGUF file, you must `create
     /// a blob` for each of the files and then use the file name and SHA256 digest associated with each
     /// blob in the files field.
@@ -236,7 +392,7 @@ impl Ollama {
     ///     println!("{}", model.name);
     /// }
     /// ```
-    pub fn list_local_models(&self) -> ListLocalModelAction {
+    pub fn list_local_models(&self) -> ListLocalModelAction<'_> {
         ListLocalModelAction::new(self.client.clone())
     }
 
@@ -260,6 +416,38 @@ impl Ollama {
         ListRunningModelsAction::new(self.client.clone())
     }
 
+    /// Watch the models that are loaded into memory by polling `/api/ps`, emitting an event
+    /// whenever a model is loaded, unloaded, changes its VRAM footprint or is about to expire.
+    /// Models that are already running when the watcher starts are reported as `Loaded`.
+    ///
+    /// # Methods
+    /// - `interval`: How often `/api/ps` is polled (default: 5s).
+    /// - `expiry_threshold`: How long before `expires_at` an `ExpiringSoon` event is emitted (default: 30s).
+    ///
+    /// # Returns
+    /// A never-ending stream of [RunningModelEvent][`crate::abi::model::watch_running::RunningModelEvent`].
+    /// A failed poll yields an error but does not end the stream.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use ollama_native::action::IntoStream;
+    /// use tokio_stream::StreamExt;
+    ///
+    /// let mut events = ollama
+    ///     .watch_running_models()
+    ///     .interval(Duration::from_secs(1))
+    ///     .stream()
+    ///     .await?;
+    ///
+    /// while let Some(Ok(event)) = events.next().await {
+    ///     println!("{event:?}");
+    /// }
+    /// ```
+    #[cfg(feature = "stream")]
+    pub fn watch_running_models(&self) -> WatchRunningModelsAction {
+        WatchRunningModelsAction::new(self.client.clone())
+    }
+
     /// Show information about a model including details, modelfile, template, parameters, license, system prompt.
     /// # Parameter
     /// - `model`: Name of the model to show.
@@ -367,7 +555,7 @@ impl Ollama {
     ///
     /// while let Some(Ok(item)) = resp.next().await {
     ///     let serialized = serde_json::to_string(&item)?;
-    ///     out.write(format!("{}\n", serialized).as_bytes()).await?
+    ///     out.write_all(format!("{}\n", serialized).as_bytes()).await?
     ///     out.flush().await?;
     /// }
     /// ```
@@ -419,6 +607,8 @@ impl Ollama {
     /// - `truncate`: Truncates the end of each input to fit within context length. Returns error if `false` and context length is exceeded. Defaults to `true`.
     /// - `options`: Additional model parameters listed in [Modelfile](https://github.com/ollama/ollama/blob/main/docs/modelfile.md#valid-parameters-and-values) such as `temperature`.
     /// - `keep_alive`: Controls how long the model will stay loaded into memory following the request (default: 5m).
+    /// - `cache`: Serve inputs from an [EmbeddingCache][`crate::embeddings::EmbeddingCache`] and only send the misses (`embeddings` feature).
+    /// - `model_digest`: The model digest used for cache keys, skipping the `/api/tags` lookup (`embeddings` feature).
     ///
     /// # Returns
     /// **Single input:**
@@ -427,9 +617,12 @@ impl Ollama {
     ///     embeddings: vec![
     ///         vec![0.010071029, -0.0017594862, 0.05007221, 0.04692972, 0.054916814, 0.008599704, 0.105441414, -0.025878139, 0.12958129, 0.031952348]
     ///     ],
-    ///     total_duration: Some(14143917),
-    ///     load_duration: Some(1019500),
-    ///     prompt_eval_count: Some(8)
+    ///     usage: Usage {
+    ///         total_duration: Some(14.143917ms),
+    ///         load_duration: Some(1.0195ms),
+    ///         prompt_eval_count: Some(8),
+    ///         ..
+    ///     }
     /// }`
     ///
     /// **Multiple input:**
@@ -439,9 +632,7 @@ impl Ollama {
     ///         vec![0.010071029, -0.0017594862, 0.05007221, 0.04692972, 0.054916814, 0.008599704, 0.105441414, -0.025878139, 0.12958129, 0.031952348],
     ///         vec![-0.0098027075, 0.06042469, 0.025257962, -0.006364387, 0.07272725, 0.017194884, 0.09032035, -0.051705178, 0.09951512, 0.09072481]
     ///     ],
-    ///     total_duration: None,
-    ///     load_duration: None,
-    ///     prompt_eval_count: None
+    ///     usage: Usage { total_duration: None, load_duration: None, prompt_eval_count: None, .. }
     /// }`
     ///
     /// # Errors
@@ -461,6 +652,59 @@ impl Ollama {
         GenerateEmbeddingsAction::new(self.client.clone(), model)
     }
 
+    /// Generate embeddings for a large number of texts. The inputs are split into batches
+    /// bounded by size and estimated token count, sent concurrently, and the embeddings are
+    /// yielded in input order.
+    ///
+    /// # Parameters
+    /// - `model`: Name of model to generate embeddings from.
+    ///
+    /// # Methods
+    /// - `inputs`: Texts to generate embeddings for, from any iterator.
+    /// - `input_stream`: Texts to generate embeddings for, from a stream consumed lazily.
+    /// - `batch_size`: Maximum number of inputs per request (default: 64).
+    /// - `max_batch_tokens`: Maximum number of estimated tokens per request (4 characters per token).
+    /// - `concurrency`: Number of requests in flight at the same time (default: 4).
+    /// - `retries`: How many times a failed batch is retried (default: 3).
+    /// - `retry_backoff`: Delay before the first retry, doubled on every attempt (default: 500ms).
+    /// - `truncate`: Truncates the end of each input to fit within context length.
+    /// - `keep_alive`: Controls how long the model will stay loaded into memory following each request.
+    /// - `num_ctx`: Sets the size of the context window.
+    ///
+    /// # Returns
+    /// A stream of [BatchEmbedding][`crate::abi::model::batch_embeddings::BatchEmbedding`],
+    /// each carrying the input index, its embedding and the progress of the job.
+    ///
+    /// # Errors
+    /// A batch that still fails after all retries yields its error and ends the stream.
+    /// - `OllamaError::RequestError`: There is an error with the request.
+    /// - `OllamaError::DecodeError`: There is an error decoding the response.
+    /// - `OllamaError::OllamaServerError`: There is an error with the Ollama server.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use ollama_native::action::IntoStream;
+    /// use tokio_stream::StreamExt;
+    ///
+    /// let mut embeddings = ollama
+    ///     .batch_embeddings("all-minilm")
+    ///     .inputs(documents)
+    ///     .batch_size(128)
+    ///     .concurrency(8)
+    ///     .stream()
+    ///     .await?;
+    ///
+    /// while let Some(item) = embeddings.next().await {
+    ///     let item = item?;
+    ///     store(item.index, item.embedding);
+    ///     println!("{}/{:?}", item.progress.completed, item.progress.total);
+    /// }
+    /// ```
+    #[cfg(feature = "stream")]
+    pub fn batch_embeddings(&self, model: &str) -> BatchEmbeddingsAction {
+        BatchEmbeddingsAction::new(self.client.clone(), model)
+    }
+
     /// Ensures that the file blob (Binary Large Object) used with create a model exists on the server.
     /// This checks your Ollama server and not ollama.com.
     ///
@@ -543,7 +787,7 @@ mod tests {
         let model = "llama3.1:8b";
         let resp = ollama.generate(model).load().await.unwrap();
         assert_eq!(resp.model, model);
-        assert_eq!(resp.done, true);
+        assert!(resp.done);
         assert_eq!(resp.response, "");
         assert_eq!(resp.done_reason, "load");
     }
@@ -555,7 +799,7 @@ mod tests {
         let model = "llama3.1:8b";
         let resp = ollama.generate(model).unload().await.unwrap();
         assert_eq!(resp.model, model);
-        assert_eq!(resp.done, true);
+        assert!(resp.done);
         assert_eq!(resp.response, "");
         assert_eq!(resp.done_reason, "unload");
     }
@@ -567,7 +811,7 @@ mod tests {
         let model = "llama3.1:8b";
         let resp = ollama.chat(model).load().await.unwrap();
         assert_eq!(resp.model, model);
-        assert_eq!(resp.done, true);
+        assert!(resp.done);
         assert_eq!(resp.message.role, Role::Assistant);
         assert_eq!(resp.message.content, "");
         assert_eq!(resp.done_reason, "load");
@@ -580,7 +824,7 @@ mod tests {
         let model = "llama3.1:8b";
         let resp = ollama.chat(model).unload().await.unwrap();
         assert_eq!(resp.model, model);
-        assert_eq!(resp.done, true);
+        assert!(resp.done);
         assert_eq!(resp.message.role, Role::Assistant);
         assert_eq!(resp.message.content, "");
         assert_eq!(resp.done_reason, "unload");
@@ -599,11 +843,13 @@ mod tests {
 
         let mut out = stdout();
         while let Some(item) = s.next().await {
-            out.write(item.unwrap().response.as_bytes()).await.unwrap();
+            out.write_all(item.unwrap().response.as_bytes())
+                .await
+                .unwrap();
             out.flush().await.unwrap();
         }
 
-        out.write(b"\n").await.unwrap();
+        out.write_all(b"\n").await.unwrap();
         out.flush().await.unwrap();
     }
 
@@ -618,8 +864,8 @@ mod tests {
             .chat("llama3.1:8b")
             .system_message("You are an expert on sharks")
             .user_message("Tell me a joke about sharks?")
-            .tool(&weather_tool)
-            .tool(&jokel_tool)
+            .tool(weather_tool)
+            .tool(jokel_tool)
             .await
             .unwrap();
         println!("{:?}", resp);
@@ -640,11 +886,11 @@ mod tests {
         let mut out = stdout();
         while let Some(item) = s.next().await {
             let content = item.unwrap().message.unwrap().content;
-            out.write(content.as_bytes()).await.unwrap();
+            out.write_all(content.as_bytes()).await.unwrap();
             out.flush().await.unwrap();
         }
 
-        out.write(b"\n").await.unwrap();
+        out.write_all(b"\n").await.unwrap();
         out.flush().await.unwrap();
     }
 
@@ -719,7 +965,7 @@ mod tests {
     async fn chat_with_images_should_work() {
         let ollama = Ollama::new(mock_config());
         let image = "iVBORw0KGgoAAAANSUhEUgAAAG0AAABmCAYAAADBPx+VAAAACXBIWXMAAAsTAAALEwEAmpwYAAAAAXNSR0IArs4c6QAAAARnQU1BAACxjwv8YQUAAA3VSURBVHgB7Z27r0zdG8fX743i1bi1ikMoFMQloXRpKFFIqI7LH4BEQ+NWIkjQuSWCRIEoULk0gsK1kCBI0IhrQVT7tz/7zZo888yz1r7MnDl7z5xvsjkzs2fP3uu71nNfa7lkAsm7d++Sffv2JbNmzUqcc8m0adOSzZs3Z+/XES4ZckAWJEGWPiCxjsQNLWmQsWjRIpMseaxcuTKpG/7HP27I8P79e7dq1ars/yL4/v27S0ejqwv+cUOGEGGpKHR37tzJCEpHV9tnT58+dXXCJDdECBE2Ojrqjh071hpNECjx4cMHVycM1Uhbv359B2F79+51586daxN/+pyRkRFXKyRDAqxEp4yMlDDzXG1NPnnyJKkThoK0VFd1ELZu3TrzXKxKfW7dMBQ6bcuWLW2v0VlHjx41z717927ba22U9APcw7Nnz1oGEPeL3m3p2mTAYYnFmMOMXybPPXv2bNIPpFZr1NHn4HMw0KRBjg9NuRw95s8PEcz/6DZELQd/09C9QGq5RsmSRybqkwHGjh07OsJSsYYm3ijPpyHzoiacg35MLdDSIS/O1yM778jOTwYUkKNHWUzUWaOsylE00MyI0fcnOwIdjvtNdW/HZwNLGg+sR1kMepSNJXmIwxBZiG8tDTpEZzKg0GItNsosY8USkxDhD0Rinuiko2gfL/RbiD2LZAjU9zKQJj8RDR0vJBR1/Phx9+PHj9Z7REF4nTZkxzX4LCXHrV271qXkBAPGfP/atWvu/PnzHe4C97F48eIsRLZ9+3a3f/9+87dwP1JxaF7/3r17ba+5l4EcaVo0lj3SBq5kGTJSQmLWMjgYNei2GPT1MuMqGTDEFHzeQSP2wi/jGnkmPJ/nhccs44jvDAxpVcxnq0F6eT8h4ni/iIWpR5lPyA6ETkNXoSukvpJAD3AsXLiwpZs49+fPn5ke4j10TqYvegSfn0OnafC+Tv9ooA/JPkgQysqQNBzagXY55nO/oa1F7qvIPWkRL12WRpMWUvpVDYmxAPehxWSe8ZEXL20sadYIozfmNch4QJPAfeJgW3rNsnzphBKNJM2KKODo1rVOMRYik5ETy3ix4qWNI81qAAirizgMIc+yhTytx0JWZuNI03qsrgWlGtwjoS9XwgUhWGyhUaRZZQNNIEwCiXD16tXcAHUs79co0vSD8rrJCIW98pzvxpAWyyo3HYwqS0+H0BjStClcZJT5coMm6D2LOF8TolGJtK9fvyZpyiC5ePFi9nc/oJU4eiEP0jVoAnHa9wyJycITMP78+eMeP37sXrx44d6+fdt6f82aNdkx1pg9e3Zb5W+RSRE+n+VjksQWifvVaTKFhn5O8my63K8Qabdv33b379/PiAP//vuvW7BggZszZ072/+TJk91YgkafPn166zXB1rQHFvouAWHq9z3SEevSUerqCn2/dDCeta2jxYbr69evk4MHDyY7d+7MjhMnTiTPnz9Pfv/+nfQT2ggpO2dMF8cghuoM7Ygj5iWCqRlGFml0QC/ftGmTmzt3rmsaKDsgBSPh0/8yPeLLBihLkOKJc0jp8H8vUzcxIA1k6QJ/c78tWEyj5P3o4u9+jywNPdJi5rAH9x0KHcl4Hg570eQp3+vHXGyrmEeigzQsQsjavXt38ujRo44LQuDDhw+TW7duRS1HGgMxhNXHgflaNTOsHyKvHK5Ijo2jbFjJBQK9YwFd6RVMzfgRBmEfP37suBBm/p49e1qjEP2mwTViNRo0VJWH1deMXcNK08uUjVUu7s/zRaL+oLNxz1bpANco4npUgX4G2eFbpDFyQoQxojBCpEGSytmOH8qrH5Q9vuzD6ofQylkCUmh8DBAr+q8JCyVNtWQIidKQE9wNtLSQnS4jDSsxNHogzFuQBw4cyM61UKVsjfr3ooBkPSqqQHesUPWVtzi9/vQi1T+rJj7WiTz4Pt/l3LxUkr5P2VYZaZ4URpsE+st/dujQoaBBYokbrz/8TJNQYLSonrPS9kUaSkPeZyj1AWSj+d+VBoy1pIWVNed8P0Ll/ee5HdGRhrHhR5GGN0r4LGZBaj8oFDJitBTJzIZgFcmU0Y8ytWMZMzJOaXUSrUs5RxKnrxmbb5YXO9VGUhtpXldhEUogFr3IzIsvlpmdosVcGVGXFWp2oU9kLFL3dEkSz6NHEY1sjSRdIuDFWEhd8KxFqsRi1uM/nz9/zpxnwlESONdg6dKlbsaMGS4EHFHtjFIDHwKOo46l4TxSuxgDzi+rE2jg+BaFruOX4HXa0Nnf1lwAPufZeF8/r6zD97WK2qFnGjBxTw5qNGPxT+5T/r7/7RawFC3j4vTp09koCxkeHjqbHJqArmH5UrFKKksnxrK7FuRIs8STfBZv+luugXZ2pR/pP9Ois4z+TiMzUUkUjD0iEi1fzX8GmXyuxUBRcaUfykV0YZnlJGKQpOiGB76x5GeWkWWJc3mOrK6S7xdND+W5N6XyaRgtWJFe13GkaZnKOsYqGdOVVVbGupsyA/l7emTLHi7vwTdirNEt0qxnzAvBFcnQF16xh/TMpUuXHDowhlA9vQVraQhkudRdzOnK+04ZSP3DUhVSP61YsaLtd/ks7ZgtPcXqPqEafHkdqa84X6aCeL7YWlv6edGFHb+ZFICPlljHhg0bKuk0CSvVznWsotRu433alNdFrqG45ejoaPCaUkWERpLXjzFL2Rpllp7PJU2a/v7Ab8N05/9t27Z16KUqoFGsxnI9EosS2niSYg9SpU6B4JgTrvVW1flt1sT+0ADIJU2maXzcUTraGCRaL1Wp9rUMk16PMom8QhruxzvZIegJjFU7LLCePfS8uaQdPny4jTTL0dbee5mYokQsXTIWNY46kuMbnt8Kmec+LGWtOVIl9cT1rCB0V8WqkjAsRwta93TbwNYoGKsUSChN44lgBNCoHLHzquYKrU6qZ8lolCIN0Rh6cP0Q3U6I6IXILYOQI513hJaSKAorFpuHXJNfVlpRtmYBk1Su1obZr5dnKAO+L10Hrj3WZW+E3qh6IszE37F6EB+68mGpvKm4eb9bFrlzrok7fvr0Kfv727dvWRmdVTJHw0qiiCUSZ6wCK+7XL/AcsgNyL74DQQ730sv78Su7+t/A36MdY0sW5o40ahslXr58aZ5HtZB8GH64m9EmMZ7FpYw4T6QnrZfgenrhFxaSiSGXtPnz57e9TkNZLvTjeqhr734CNtrK41L40sUQckmj1lGKQ0rC37x544r8eNXRpnVE3ZZY7zXo8NomiO0ZUCj2uHz58rbXoZ6gc0uA+F6ZeKS/jhRDUq8MKrTho9fEkihMmhxtBI1DxKFY9XLpVcSkfoi8JGnToZO5sU5aiDQIW716ddt7ZLYtMQlhECdBGXZZMWldY5BHm5xgAroWj4C0hbYkSc/jBmggIrXJWlZM6pSETsEPGqZOndr2uuuR5rF169a2HoHPdurUKZM4CO1WTPqaDaAd+GFGKdIQkxAn9RuEWcTRyN2KSUgiSgF5aWzPTeA/lN5rZubMmR2bE4SIC4nJoltgAV/dVefZm72AtctUCJU2CMJ327hxY9t7EHbkyJFseq+EJSY16RPo3Dkq1kkr7+q0bNmyDuLQcZBEPYmHVdOBiJyIlrRDq41YPWfXOxUysi5fvtyaj+2BpcnsUV/oSoEMOk2CQGlr4ckhBwaetBhjCwH0ZHtJROPJkyc7UjcYLDjmrH7ADTEBXFfOYmB0k9oYBOjJ8b4aOYSe7QkKcYhFlq3QYLQhSidNmtS2RATwy8YOM3EQJsUjKiaWZ+vZToUQgzhkHXudb/PW5YMHD9yZM2faPsMwoc7RciYJXbGuBqJ1UIGKKLv915jsvgtJxCZDubdXr165mzdvtr1Hz5LONA8jrUwKPqsmVesKa49S3Q4WxmRPUEYdTjgiUcfUwLx589ySJUva3oMkP6IYddq6HMS4o55xBJBUeRjzfa4Zdeg56QZ43LhxoyPo7Lf1kNt7oO8wWAbNwaYjIv5lhyS7kRf96dvm5Jah8vfvX3flyhX35cuX6HfzFHOToS1H4BenCaHvO8pr8iDuwoUL7tevX+b5ZdbBair0xkFIlFDlW4ZknEClsp/TzXyAKVOmmHWFVSbDNw1l1+4f90U6IY/q4V27dpnE9bJ+v87QEydjqx/UamVVPRG+mwkNTYN+9tjkwzEx+atCm/X9WvWtDtAb68Wy9LXa1UmvCDDIpPkyOQ5ZwSzJ4jMrvFcr0rSjOUh+GcT4LSg5ugkW1Io0/SCDQBojh0hPlaJdah+tkVYrnTZowP8iq1F1TgMBBauufyB33x1v+NWFYmT5KmppgHC+NkAgbmRkpD3yn9QIseXymoTQFGQmIOKTxiZIWpvAatenVqRVXf2nTrAWMsPnKrMZHz6bJq5jvce6QK8J1cQNgKxlJapMPdZSR64/UivS9NztpkVEdKcrs5alhhWP9NeqlfWopzhZScI6QxseegZRGeg5a8C3Re1Mfl1ScP36ddcUaMuv24iOJtz7sbUjTS4qBvKmstYJoUauiuD3k5qhyr7QdUHMeCgLa1Ear9NquemdXgmum4fvJ6w1lqsuDhNrg1qSpleJK7K3TF0Q2jSd94uSZ60kK1e3qyVpQK6PVWXp2/FC3mp6jBhKKOiY2h3gtUV64TWM6wDETRPLDfSakXmH3w8g9Jlug8ZtTt4kVF0kLUYYmCCtD/DrQ5YhMGbA9L3ucdjh0y8kOHW5gU/VEEmJTcL4Pz/f7mgoAbYkAAAAAElFTkSuQmCC";
-        let message = Message::user("What's in the image").image(&image);
+        let message = Message::user("What's in the image").image(image);
         let response = ollama.chat("llava").message(message).await.unwrap();
         println!("{response:?}");
     }
@@ -804,7 +1050,7 @@ mod tests {
     #[ignore]
     async fn copy_a_model_should_work() {
         let ollama = Ollama::new(mock_config());
-        let _ = ollama
+        ollama
             .copy_model("llama3.1:8b", "llama3.1:another")
             .await
             .unwrap();
@@ -827,7 +1073,7 @@ mod tests {
     #[ignore]
     async fn delete_a_model_should_work() {
         let ollama = Ollama::new(mock_config());
-        let _ = ollama.delete_model("yuanshsdf:latest").await.unwrap();
+        ollama.delete_model("yuanshsdf:latest").await.unwrap();
     }
 
     #[tokio::test]
@@ -880,7 +1126,7 @@ mod tests {
     #[ignore]
     async fn push_blob_should_work() {
         let ollama = Ollama::new(mock_config());
-        let _ = ollama
+        ollama
             .push_blob(
                 "model.gguf",
                 "sha256:29fdb92e57cf0827ded04ae6461b5931d01fa595843f55d36f5b275a52087dd2",
@@ -900,7 +1146,7 @@ mod tests {
             match item {
                 Ok(item) => {
                     let serialized = serde_json::to_string(&item).unwrap();
-                    out.write(format!("{}\n", serialized).as_bytes())
+                    out.write_all(format!("{}\n", serialized).as_bytes())
                         .await
                         .unwrap();
                     out.flush().await.unwrap();
@@ -909,7 +1155,7 @@ mod tests {
             }
         }
 
-        out.write(b"\n").await.unwrap();
+        out.write_all(b"\n").await.unwrap();
         out.flush().await.unwrap();
     }
 }
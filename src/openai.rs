@@ -0,0 +1,929 @@
+//! Requests against the OpenAI-compatible endpoints Ollama serves under `/v1`, for servers
+//! and gateways that only expose those.
+//!
+//! Responses are mapped into this crate's own types, so switching a client to
+//! [`WireFormat::OpenAi`] with [`Ollama::wire_format`][crate::Ollama::wire_format] leaves
+//! application code unchanged:
+//!
+//! ```rust,ignore
+//! use ollama_native::{Ollama, config::WireFormat};
+//!
+//! let ollama = Ollama::new("http://localhost:11434").wire_format(WireFormat::OpenAi);
+//! let response = ollama.chat("llama3.1:8b").user_message("Hello").await?;
+//! ```
+//!
+//! The OpenAI API has no equivalent for most Modelfile options, only `temperature`, `top_p`,
+//! `seed`, `stop` and `num_predict` are sent. `think`, `keep_alive`, `truncate` and the
+//! `system`, `template` and `raw` fields of generate requests are ignored. Durations are
+//! not reported, so only the token counts of [`Usage`] are set.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use reqwest::{
+    StatusCode,
+    header::{CONTENT_TYPE, HeaderMap, HeaderValue},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::abi::completion::chat::{ChatCompletionRequest, ChatCompletionResponse, Format, Tool};
+use crate::abi::completion::generate::{GenerateCompletionRequest, GenerateCompletionResponse};
+use crate::abi::image::sniff_mime;
+use crate::abi::{Image, Message, Options, Role, Usage};
+use crate::action::{OllamaClient, OllamaRequest, parse_response};
+use crate::config::WireFormat;
+use crate::error::OllamaError;
+
+#[cfg(feature = "model")]
+use crate::abi::model::{
+    ModelInfoDetail,
+    generate_embeddings::{GenerateEmbeddingsRequest, GenerateEmbeddingsResponse},
+    list_local::{ListLocalModelsResponse, ModelInfo},
+};
+
+#[cfg(feature = "stream")]
+use {crate::action::OllamaStream, async_stream::stream, futures::Stream, tokio_stream::StreamExt};
+
+/// Body of `/v1/chat/completions`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatRequest<'a> {
+    pub model: &'a str,
+    pub messages: Vec<Value>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub tools: &'a [Tool<'a>],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<Value>,
+    #[serde(flatten)]
+    pub sampling: Sampling,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+/// Body of `/v1/completions`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionRequest<'a> {
+    pub model: &'a str,
+    pub prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<&'a str>,
+    #[serde(flatten)]
+    pub sampling: Sampling,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+/// Body of `/v1/embeddings`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [&'a str],
+    pub encoding_format: &'static str,
+}
+
+/// Body-less request for `/v1/models`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModelsRequest {}
+
+/// The sampling parameters shared by the completion endpoints.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Sampling {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i64>,
+}
+
+impl From<&Options> for Sampling {
+    fn from(options: &Options) -> Self {
+        Self {
+            temperature: options.temperature,
+            top_p: options.top_p,
+            seed: options.seed,
+            stop: options.stop.clone(),
+            // A negative `num_predict` means no limit, which is the default here.
+            max_tokens: options.num_predict.filter(|n| *n >= 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+impl OllamaRequest for ChatRequest<'_> {
+    fn path(&self) -> String {
+        "/v1/chat/completions".to_string()
+    }
+}
+
+impl OllamaRequest for CompletionRequest<'_> {
+    fn path(&self) -> String {
+        "/v1/completions".to_string()
+    }
+}
+
+impl OllamaRequest for EmbeddingRequest<'_> {
+    fn path(&self) -> String {
+        "/v1/embeddings".to_string()
+    }
+}
+
+impl OllamaRequest for ModelsRequest {
+    fn path(&self) -> String {
+        "/v1/models".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletion {
+    pub model: String,
+    pub created: i64,
+    pub choices: Vec<ChatChoice>,
+    #[serde(default)]
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatChoice {
+    /// `message` for complete responses, `delta` for streamed chunks.
+    #[serde(alias = "delta")]
+    pub message: ChatMessage,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChatMessage {
+    #[serde(default)]
+    pub role: Option<Role>,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolCall {
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// The arguments as a JSON-encoded string.
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Completion {
+    pub model: String,
+    pub created: i64,
+    pub choices: Vec<CompletionChoice>,
+    #[serde(default)]
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionChoice {
+    pub text: String,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CompletionUsage {
+    pub prompt_tokens: i64,
+    #[serde(default)]
+    pub completion_tokens: i64,
+}
+
+impl From<CompletionUsage> for Usage {
+    fn from(usage: CompletionUsage) -> Self {
+        Self {
+            prompt_eval_count: Some(usage.prompt_tokens),
+            eval_count: Some(usage.completion_tokens),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Embeddings {
+    pub model: String,
+    pub data: Vec<Embedding>,
+    #[serde(default)]
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Embedding {
+    pub index: usize,
+    pub embedding: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Models {
+    pub data: Vec<Model>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Model {
+    pub id: String,
+    pub created: i64,
+    pub owned_by: String,
+}
+
+/// `{"error": {"message": ...}}`, or Ollama's own `{"error": "..."}` from gateways that
+/// pass native errors through.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: Value,
+}
+
+/// Whether requests from `client` go to the OpenAI-compatible API.
+#[inline]
+pub(crate) fn enabled(client: &OllamaClient) -> bool {
+    client.config.wire_format == WireFormat::OpenAi
+}
+
+pub(crate) async fn chat(
+    client: &OllamaClient,
+    request: &ChatCompletionRequest<'_>,
+) -> Result<ChatCompletionResponse, OllamaError> {
+    let body = chat_request(request, false)?;
+    let completion: ChatCompletion = send(client, &body).await?;
+    let usage = completion.usage.map(Usage::from).unwrap_or_default();
+    let choice = completion
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| OllamaError::InvalidFormat("no choices in response".to_string()))?;
+
+    Ok(ChatCompletionResponse {
+        model: completion.model,
+        created_at: timestamp(completion.created),
+        message: Some(message(choice.message)),
+        done_reason: choice.finish_reason,
+        done: true,
+        usage,
+    })
+}
+
+#[cfg(feature = "stream")]
+pub(crate) async fn chat_stream(
+    client: &OllamaClient,
+    request: &ChatCompletionRequest<'_>,
+) -> Result<OllamaStream<ChatCompletionResponse>, OllamaError> {
+    let body = chat_request(request, true)?;
+    let response = send_raw(client, &body).await?;
+    Ok(Box::pin(decode_chunks::<ChatCompletion>(response)))
+}
+
+pub(crate) async fn generate(
+    client: &OllamaClient,
+    request: &GenerateCompletionRequest<'_>,
+) -> Result<GenerateCompletionResponse, OllamaError> {
+    let body = completion_request(request, false)?;
+    let completion: Completion = send(client, &body).await?;
+    let usage = completion.usage.map(Usage::from).unwrap_or_default();
+    let choice = completion
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| OllamaError::InvalidFormat("no choices in response".to_string()))?;
+
+    Ok(GenerateCompletionResponse {
+        model: completion.model,
+        created_at: timestamp(completion.created),
+        response: choice.text,
+        thinking: None,
+        done: true,
+        done_reason: choice.finish_reason,
+        context: None,
+        usage,
+    })
+}
+
+#[cfg(feature = "stream")]
+pub(crate) async fn generate_stream(
+    client: &OllamaClient,
+    request: &GenerateCompletionRequest<'_>,
+) -> Result<OllamaStream<GenerateCompletionResponse>, OllamaError> {
+    let body = completion_request(request, true)?;
+    let response = send_raw(client, &body).await?;
+    Ok(Box::pin(decode_chunks::<Completion>(response)))
+}
+
+#[cfg(feature = "model")]
+pub(crate) async fn embed(
+    client: &OllamaClient,
+    request: &GenerateEmbeddingsRequest<'_>,
+) -> Result<GenerateEmbeddingsResponse, OllamaError> {
+    let body = EmbeddingRequest {
+        model: request.model,
+        input: &request.input,
+        encoding_format: "float",
+    };
+    let mut response: Embeddings = send(client, &body).await?;
+    response.data.sort_by_key(|e| e.index);
+
+    Ok(GenerateEmbeddingsResponse {
+        model: response.model,
+        embeddings: response.data.into_iter().map(|e| e.embedding).collect(),
+        usage: Usage {
+            prompt_eval_count: response.usage.map(|u| u.prompt_tokens),
+            ..Default::default()
+        },
+    })
+}
+
+/// The models the server offers. Sizes, digests and details are not reported by
+/// `/v1/models` and are left empty.
+#[cfg(feature = "model")]
+pub(crate) async fn list_models(
+    client: &OllamaClient,
+) -> Result<ListLocalModelsResponse, OllamaError> {
+    let response = client.get(&ModelsRequest::default()).await?;
+    let models: Models = check(response).await?;
+
+    let models = models
+        .data
+        .into_iter()
+        .map(|model| ModelInfo {
+            name: model.id,
+            modified_at: timestamp(model.created).to_rfc3339(),
+            size: 0,
+            digest: String::new(),
+            details: ModelInfoDetail {
+                format: String::new(),
+                family: model.owned_by,
+                families: None,
+                parameter_size: String::new(),
+                quantization_level: String::new(),
+            },
+        })
+        .collect();
+    Ok(ListLocalModelsResponse { models })
+}
+
+fn chat_request<'a>(
+    request: &'a ChatCompletionRequest<'a>,
+    stream: bool,
+) -> Result<ChatRequest<'a>, OllamaError> {
+    let response_format = match request.format {
+        None => None,
+        Some(Format::Json) => Some(json!({ "type": "json_object" })),
+        Some(Format::Schema(schema)) => {
+            let schema: Value = serde_json::from_str(schema)
+                .map_err(|e| OllamaError::InvalidFormat(format!("invalid JSON schema: {e}")))?;
+            Some(json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema },
+            }))
+        }
+    };
+
+    Ok(ChatRequest {
+        model: request.model,
+        messages: request
+            .messages
+            .iter()
+            .map(openai_message)
+            .collect::<Result<_, _>>()?,
+        tools: &request.tools,
+        response_format,
+        sampling: Sampling::from(&request.options),
+        stream,
+        stream_options: stream.then_some(StreamOptions {
+            include_usage: true,
+        }),
+    })
+}
+
+fn completion_request<'a>(
+    request: &'a GenerateCompletionRequest<'a>,
+    stream: bool,
+) -> Result<CompletionRequest<'a>, OllamaError> {
+    if !request.images.is_empty() {
+        return Err(OllamaError::InvalidFormat(
+            "images are not supported by /v1/completions, use chat instead".to_string(),
+        ));
+    }
+
+    Ok(CompletionRequest {
+        model: request.model,
+        prompt: request.prompt.unwrap_or_default(),
+        suffix: request.suffix,
+        sampling: Sampling::from(&request.options),
+        stream,
+        stream_options: stream.then_some(StreamOptions {
+            include_usage: true,
+        }),
+    })
+}
+
+/// A native message in the OpenAI format: images become `image_url` content parts and tool
+/// call arguments are encoded as strings.
+fn openai_message(message: &Message) -> Result<Value, OllamaError> {
+    let content = match &message.images {
+        Some(images) if !images.is_empty() => {
+            let mut parts = vec![json!({ "type": "text", "text": message.content })];
+            for image in images {
+                parts.push(json!({
+                    "type": "image_url",
+                    "image_url": { "url": image_url(image)? },
+                }));
+            }
+            Value::Array(parts)
+        }
+        _ => Value::String(message.content.clone()),
+    };
+
+    let mut value = json!({ "role": message.role, "content": content });
+    if let Some(tool_calls) = &message.tool_calls {
+        let tool_calls: Vec<Value> = tool_calls
+            .iter()
+            .enumerate()
+            .map(|(i, call)| {
+                let function = &call["function"];
+                json!({
+                    "id": format!("call_{i}"),
+                    "type": "function",
+                    "function": {
+                        "name": function["name"],
+                        "arguments": function["arguments"].to_string(),
+                    },
+                })
+            })
+            .collect();
+        value["tool_calls"] = Value::Array(tool_calls);
+    }
+    Ok(value)
+}
+
+fn image_url(image: &Image) -> Result<String, OllamaError> {
+    match image {
+        Image::Base64(data) => {
+            // 16 characters decode to the 12 bytes the longest magic number needs.
+            let head = STANDARD
+                .decode(&data.as_bytes()[..data.len().min(16)])
+                .unwrap_or_default();
+            let mime = sniff_mime(&head).unwrap_or("image/jpeg");
+            Ok(format!("data:{mime};base64,{data}"))
+        }
+        Image::DataUri(uri) => Ok(uri.clone()),
+        Image::Url(url) => Ok(url.clone()),
+        Image::Path(_) | Image::Bytes(_) => Err(OllamaError::ImageError(
+            "image must be resolved before it is sent".to_string(),
+        )),
+    }
+}
+
+/// An OpenAI message as a native one, with tool call arguments decoded back into objects.
+fn message(message: ChatMessage) -> Message {
+    let tool_calls = message.tool_calls.map(|calls| {
+        calls
+            .into_iter()
+            .map(|call| {
+                let arguments = serde_json::from_str(&call.function.arguments)
+                    .unwrap_or(Value::String(call.function.arguments));
+                json!({ "function": { "name": call.function.name, "arguments": arguments } })
+            })
+            .collect()
+    });
+
+    Message {
+        role: message.role.unwrap_or(Role::Assistant),
+        content: message.content.unwrap_or_default(),
+        thinking: message.reasoning.filter(|r| !r.is_empty()),
+        images: None,
+        tool_calls,
+    }
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+fn json_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers
+}
+
+async fn send<T: DeserializeOwned>(
+    client: &OllamaClient,
+    request: &impl OllamaRequest,
+) -> Result<T, OllamaError> {
+    let response = client.post(request, Some(json_headers())).await?;
+    check(response).await
+}
+
+#[cfg(feature = "stream")]
+async fn send_raw(
+    client: &OllamaClient,
+    request: &impl OllamaRequest,
+) -> Result<reqwest::Response, OllamaError> {
+    let response = client.post(request, Some(json_headers())).await?;
+    match response.status() {
+        StatusCode::OK => Ok(response),
+        _code => Err(server_error(response).await),
+    }
+}
+
+async fn check<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, OllamaError> {
+    match response.status() {
+        StatusCode::OK => parse_response(response).await,
+        _code => Err(server_error(response).await),
+    }
+}
+
+async fn server_error(response: reqwest::Response) -> OllamaError {
+    match parse_response::<ErrorResponse>(response).await {
+        Ok(ErrorResponse { error }) => {
+            let message = error["message"].as_str().or(error.as_str());
+            OllamaError::OllamaServerError(
+                message.map_or_else(|| error.to_string(), str::to_string),
+            )
+        }
+        Err(e) => e,
+    }
+}
+
+/// A streamed chunk that maps into a native response.
+#[cfg(feature = "stream")]
+trait Chunk: DeserializeOwned {
+    type Response;
+
+    fn usage(&self) -> Option<Usage>;
+
+    /// The chunk as a response and whether it is the last one, or `None` for the chunk
+    /// without choices that carries the usage once `include_usage` is requested.
+    fn into_response(self) -> Option<(Self::Response, bool)>;
+
+    fn set_usage(response: &mut Self::Response, usage: Usage);
+}
+
+#[cfg(feature = "stream")]
+impl Chunk for ChatCompletion {
+    type Response = ChatCompletionResponse;
+
+    fn usage(&self) -> Option<Usage> {
+        self.usage.map(Usage::from)
+    }
+
+    fn into_response(self) -> Option<(Self::Response, bool)> {
+        let choice = self.choices.into_iter().next()?;
+        let done = choice.finish_reason.is_some();
+        let response = ChatCompletionResponse {
+            model: self.model,
+            created_at: timestamp(self.created),
+            message: Some(message(choice.message)),
+            done_reason: choice.finish_reason,
+            done,
+            usage: Usage::default(),
+        };
+        Some((response, done))
+    }
+
+    fn set_usage(response: &mut Self::Response, usage: Usage) {
+        response.usage = usage;
+    }
+}
+
+#[cfg(feature = "stream")]
+impl Chunk for Completion {
+    type Response = GenerateCompletionResponse;
+
+    fn usage(&self) -> Option<Usage> {
+        self.usage.map(Usage::from)
+    }
+
+    fn into_response(self) -> Option<(Self::Response, bool)> {
+        let choice = self.choices.into_iter().next()?;
+        let done = choice.finish_reason.is_some();
+        let response = GenerateCompletionResponse {
+            model: self.model,
+            created_at: timestamp(self.created),
+            response: choice.text,
+            thinking: None,
+            done,
+            done_reason: choice.finish_reason,
+            context: None,
+            usage: Usage::default(),
+        };
+        Some((response, done))
+    }
+
+    fn set_usage(response: &mut Self::Response, usage: Usage) {
+        response.usage = usage;
+    }
+}
+
+/// Decode a stream of chunks into native responses. The last response is held back until
+/// the usage chunk that follows it arrives, so that it carries the usage as in the native API.
+#[cfg(feature = "stream")]
+fn decode_chunks<C: Chunk>(
+    response: reqwest::Response,
+) -> impl Stream<Item = Result<C::Response, OllamaError>> {
+    let mut events = Box::pin(decode_sse::<C>(response));
+    stream! {
+        let mut last: Option<C::Response> = None;
+        while let Some(event) = events.next().await {
+            let chunk = match event {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    yield Err(e);
+                    continue;
+                }
+            };
+
+            let usage = chunk.usage();
+            match chunk.into_response() {
+                Some((mut response, done)) => {
+                    if let Some(usage) = usage {
+                        C::set_usage(&mut response, usage);
+                    }
+                    if !done {
+                        yield Ok(response);
+                    } else if let Some(previous) = last.replace(response) {
+                        yield Ok(previous);
+                    }
+                }
+                None => {
+                    if let Some(mut response) = last.take() {
+                        if let Some(usage) = usage {
+                            C::set_usage(&mut response, usage);
+                        }
+                        yield Ok(response);
+                    }
+                }
+            }
+        }
+
+        if let Some(response) = last {
+            yield Ok(response);
+        }
+    }
+}
+
+/// Decode the `data:` fields of a server-sent event stream up to the `[DONE]` sentinel.
+/// Lines may be split across network chunks or share one, so they are buffered until complete.
+#[cfg(feature = "stream")]
+fn decode_sse<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> impl Stream<Item = Result<T, OllamaError>> {
+    let mut bytes = response.bytes_stream();
+    stream! {
+        let mut buffer: Vec<u8> = vec![];
+        'read: while let Some(item) = bytes.next().await {
+            match item {
+                Ok(chunk) => buffer.extend_from_slice(&chunk),
+                Err(e) => {
+                    yield Err(OllamaError::DecodingError(e));
+                    continue;
+                }
+            }
+
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let Some(data) = line.strip_prefix(b"data:") else {
+                    // Blank separators, comments and other fields.
+                    continue;
+                };
+                let data = data.trim_ascii();
+                if data == b"[DONE]" {
+                    break 'read;
+                }
+                yield serde_json::from_slice(data)
+                    .map_err(|e| OllamaError::StreamDecodingError(e.to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::abi::{Image, Message};
+    use crate::config::WireFormat;
+    use crate::error::OllamaError;
+    use crate::mock::MockOllama;
+
+    use super::openai_message;
+
+    #[test]
+    fn openai_message_should_encode_images_and_tool_calls() {
+        let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAAB";
+        let message = Message::user("what is this?").image(Image::base64(png));
+        assert_eq!(
+            openai_message(&message).unwrap(),
+            json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "what is this?" },
+                    {
+                        "type": "image_url",
+                        "image_url": { "url": format!("data:image/png;base64,{png}") },
+                    },
+                ],
+            })
+        );
+
+        let message = Message::assistant("").tool_call(json!({
+            "function": { "name": "weather", "arguments": { "city": "Paris" } }
+        }));
+        let value = openai_message(&message).unwrap();
+        assert_eq!(value["content"], "");
+        assert_eq!(value["tool_calls"][0]["type"], "function");
+        assert_eq!(
+            value["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Paris"}"#
+        );
+
+        // Not base64, with a multi-byte character across the sniffed prefix.
+        let message = Message::user("").image(Image::base64("aaaaaaaaaaaaaaaé.jpg"));
+        let value = openai_message(&message).unwrap();
+        assert_eq!(
+            value["content"][1]["image_url"]["url"],
+            "data:image/jpeg;base64,aaaaaaaaaaaaaaaé.jpg"
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_should_map_into_native_response() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        let response = ollama
+            .chat("llama3.1:8b")
+            .system_message("Be brief.")
+            .user_message("hello there")
+            .format("json")
+            .temperature(0.2)
+            .num_predict(16)
+            .num_ctx(4096)
+            .await
+            .unwrap();
+        let message = response.message.unwrap();
+        assert_eq!(message.content, "echo: hello there");
+        assert!(response.done);
+        assert_eq!(response.done_reason.as_deref(), Some("stop"));
+        assert_eq!(response.usage.prompt_eval_count, Some(10));
+        assert_eq!(response.usage.eval_count, Some(3));
+        assert_eq!(response.created_at.timestamp(), 1735689600);
+
+        let requests = mock.requests("/v1/chat/completions");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["messages"][0]["role"], "system");
+        assert_eq!(requests[0]["response_format"]["type"], "json_object");
+        assert_eq!(requests[0]["temperature"], 0.2);
+        assert_eq!(requests[0]["max_tokens"], 16);
+        assert!(requests[0].get("options").is_none());
+        assert!(mock.requests("/api/chat").is_empty());
+    }
+
+    #[tokio::test]
+    async fn chat_should_decode_tool_call_arguments() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        let tool = r#"{"type":"function","function":{"name":"weather","parameters":{}}}"#;
+        let response = ollama
+            .chat("llama3.1:8b")
+            .user_message("weather in Paris?")
+            .tool(tool)
+            .await
+            .unwrap();
+        let message = response.message.unwrap();
+        assert_eq!(response.done_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(
+            message.tool_calls.unwrap(),
+            vec![json!({ "function": { "name": "weather", "arguments": { "city": "Paris" } } })]
+        );
+        assert_eq!(
+            mock.requests("/v1/chat/completions")[0]["tools"][0]["function"]["name"],
+            "weather"
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_stream_should_attach_usage_to_last_chunk() {
+        use crate::action::IntoStream;
+        use tokio_stream::StreamExt;
+
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        let stream = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .stream()
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.collect::<Result<_, _>>().await.unwrap();
+
+        let content: String = chunks
+            .iter()
+            .map(|c| c.message.as_ref().unwrap().content.as_str())
+            .collect();
+        assert_eq!(content, "echo: hello there");
+
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest.iter().all(|c| !c.done && c.usage.eval_count.is_none()));
+        assert!(last.done);
+        assert_eq!(last.usage.prompt_eval_count, Some(10));
+        assert_eq!(last.usage.eval_count, Some(3));
+        assert_eq!(
+            mock.requests("/v1/chat/completions")[0]["stream_options"]["include_usage"],
+            true
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_should_use_completions() {
+        use crate::action::IntoStream;
+        use tokio_stream::StreamExt;
+
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        let response = ollama
+            .generate("llama3.1:8b")
+            .prompt("why is the sky blue")
+            .await
+            .unwrap();
+        assert_eq!(response.response, "echo: why is the sky blue");
+        assert_eq!(response.usage.eval_count, Some(6));
+
+        let chunks: Vec<_> = ollama
+            .generate("llama3.1:8b")
+            .prompt("why is the sky blue")
+            .stream()
+            .await
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        let text: String = chunks.iter().map(|c| c.response.as_str()).collect();
+        assert_eq!(text, "echo: why is the sky blue");
+        assert!(chunks.last().unwrap().done);
+        assert_eq!(mock.requests("/v1/completions").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn embeddings_and_models_should_map_into_native_responses() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        let response = ollama
+            .generate_embeddings("all-minilm")
+            .inputs(vec!["a b", "c"])
+            .await
+            .unwrap();
+        assert_eq!(
+            response.embeddings,
+            vec![vec![3.0, 2.0, 1.0], vec![1.0, 1.0, 1.0]]
+        );
+        assert_eq!(response.usage.prompt_eval_count, Some(2));
+
+        let models = ollama.list_local_models().await.unwrap().models;
+        let names: Vec<_> = models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["all-minilm:latest", "llama3.1:8b"]);
+        assert_eq!(models[0].modified_at, "2025-01-01T00:00:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn errors_should_map_into_server_errors() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        mock.fail_next(1);
+        let error = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, OllamaError::OllamaServerError(m) if m == "mock failure"));
+
+        let error = ollama
+            .generate("llava")
+            .prompt("what is this?")
+            .image(Image::base64("iVBORw0KGgo="))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, OllamaError::InvalidFormat(_)));
+    }
+}
@@ -0,0 +1,494 @@
+//! Spreads requests over several Ollama hosts.
+//!
+//! A [`HostPool`] routes every request a client sends through [`OllamaClient::post`] and
+//! [`OllamaClient::get`] to one of its hosts, picked with a [`Policy`]. Healthy hosts are
+//! preferred; unhealthy ones are still tried last, as their health may be stale.
+//!
+//! A host is marked unhealthy when a request or a probe fails to reach it, and healthy again
+//! when a probe of `/api/version` succeeds or a request gets a response. Probes run with
+//! [`HostPool::probe`], or every interval with [`HostPool::spawn_probes`], and also fetch the
+//! models loaded on each host from `/api/ps` for [`Policy::ModelAffinity`].
+//!
+//! A request that fails to reach a host or gets a 5xx status fails over to the next host, as
+//! no bytes of its body have been streamed yet. Once every host failed, the last 5xx response
+//! is returned, or the last error if no host answered.
+//!
+//! Requests that manage models (blob uploads and checks, create, pull, push, copy and delete)
+//! are always sent to the first host and never fail over, so that a model is created on the
+//! host its blobs were uploaded to, and a failed pull or create is not started again on
+//! another host.
+//!
+//! # Example
+//! ```rust,ignore
+//! use std::{sync::Arc, time::Duration};
+//! use ollama_native::{Ollama, pool::{HostPool, Policy}};
+//!
+//! let pool = Arc::new(
+//!     HostPool::new(["http://gpu-1:11434", "http://gpu-2:11434"])
+//!         .policy(Policy::ModelAffinity),
+//! );
+//! pool.spawn_probes(Duration::from_secs(10));
+//!
+//! let ollama = Ollama::with_pool(pool);
+//! ```
+//!
+//! [`OllamaClient::post`]: crate::action::OllamaClient::post
+//! [`OllamaClient::get`]: crate::action::OllamaClient::get
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_stream::stream;
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::abi::model::name::normalize;
+use crate::action::build_response;
+use crate::error::OllamaError;
+
+/// How a host is picked for a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Each host in turn.
+    #[default]
+    RoundRobin,
+
+    /// The host with the fewest requests in flight, in turn among ties.
+    LeastInFlight,
+
+    /// A host that has the model of the request loaded, then as [`Policy::LeastInFlight`].
+    ModelAffinity,
+}
+
+/// The state of a host, see [`HostPool::hosts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostStatus {
+    pub url: String,
+    pub healthy: bool,
+
+    /// Requests sent whose body is not read in full or dropped yet.
+    pub in_flight: usize,
+
+    /// The models loaded on the host at the last probe, given the `latest` tag if untagged.
+    pub loaded_models: Vec<String>,
+
+    /// Requests sent to the host, including those that failed over.
+    pub requests: u64,
+
+    /// Requests that failed to reach the host or got a 5xx status.
+    pub failures: u64,
+}
+
+struct Host {
+    url: String,
+    in_flight: Arc<AtomicUsize>,
+    state: Mutex<HostState>,
+}
+
+struct HostState {
+    healthy: bool,
+    loaded_models: Vec<String>,
+    requests: u64,
+    failures: u64,
+}
+
+/// Several Ollama hosts that a client sends requests to, see the [module](self) docs.
+pub struct HostPool {
+    hosts: Vec<Host>,
+    policy: Policy,
+    probe_timeout: Duration,
+    cli: reqwest::Client,
+    next: AtomicUsize,
+}
+
+impl HostPool {
+    /// A pool of the hosts at the given URLs, e.g. "http://localhost:11434". Hosts are
+    /// healthy until a request or a probe fails to reach them.
+    pub fn new(urls: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let hosts = urls.into_iter().map(|url| Host {
+            url: url.into().trim_end_matches('/').to_string(),
+            in_flight: Arc::default(),
+            state: Mutex::new(HostState {
+                healthy: true,
+                loaded_models: vec![],
+                requests: 0,
+                failures: 0,
+            }),
+        });
+        Self {
+            hosts: hosts.collect(),
+            policy: Policy::default(),
+            probe_timeout: Duration::from_secs(5),
+            cli: reqwest::Client::new(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set the routing policy, [`Policy::RoundRobin`] by default.
+    #[inline]
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set how long a probe waits for a host before marking it unhealthy, 5 seconds by default.
+    #[inline]
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// The state of each host, in the order they were given.
+    pub fn hosts(&self) -> Vec<HostStatus> {
+        self.hosts
+            .iter()
+            .map(|host| {
+                let state = host.state.lock().unwrap();
+                HostStatus {
+                    url: host.url.clone(),
+                    healthy: state.healthy,
+                    in_flight: host.in_flight.load(Ordering::SeqCst),
+                    loaded_models: state.loaded_models.clone(),
+                    requests: state.requests,
+                    failures: state.failures,
+                }
+            })
+            .collect()
+    }
+
+    /// Probe every host at once, updating its health and, for [`Policy::ModelAffinity`], its
+    /// loaded models.
+    pub async fn probe(&self) {
+        futures::future::join_all(self.hosts.iter().map(|host| self.probe_host(host))).await;
+    }
+
+    /// Probe every host now, then every `interval`, until the pool is dropped or the task is
+    /// aborted. Must be called within a Tokio runtime.
+    pub fn spawn_probes(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(pool) = pool.upgrade() {
+                pool.probe().await;
+                drop(pool);
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// The URL of the first host.
+    pub(crate) fn first_url(&self) -> &str {
+        self.hosts.first().map_or("", |host| &host.url)
+    }
+
+    /// Send the request to `path` built by `request` from a host URL to each host in order
+    /// of preference until one responds without a 5xx status, or to the first host only if
+    /// it manages models, see the [module](self) docs.
+    pub(crate) async fn send(
+        &self,
+        path: &str,
+        body: Option<&[u8]>,
+        request: impl Fn(&str) -> reqwest::RequestBuilder,
+        answered: impl Fn(&str),
+    ) -> Result<reqwest::Response, OllamaError> {
+        if manages_models(path) {
+            let host = self.hosts.first().ok_or(OllamaError::NoHostAvailable)?;
+            let result = self.send_to(host, &request).await;
+            answered(&host.url);
+            return result.map_err(OllamaError::RequestError);
+        }
+
+        let model = match self.policy {
+            Policy::ModelAffinity => body
+                .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+                .and_then(|body| body["model"].as_str().map(normalize)),
+            _ => None,
+        };
+
+        let mut last = Err(OllamaError::NoHostAvailable);
+        for host in self.order(model.as_deref()) {
+            match self.send_to(host, &request).await {
+                Ok(response) if !response.status().is_server_error() => {
+                    answered(&host.url);
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    answered(&host.url);
+                    last = Ok(response);
+                }
+                Err(e) => {
+                    if last.is_err() {
+                        answered(&host.url);
+                        last = Err(OllamaError::RequestError(e));
+                    }
+                }
+            }
+        }
+        last
+    }
+
+    /// Send the request to `host`, updating its health and counters.
+    async fn send_to(
+        &self,
+        host: &Host,
+        request: impl Fn(&str) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let in_flight = InFlight::new(&host.in_flight);
+        let result = request(&host.url).send().await;
+
+        let mut state = host.state.lock().unwrap();
+        state.requests += 1;
+        match result {
+            Ok(response) if !response.status().is_server_error() => {
+                state.healthy = true;
+                Ok(in_flight.hold(response))
+            }
+            Ok(response) => {
+                state.failures += 1;
+                Ok(response)
+            }
+            Err(e) => {
+                state.failures += 1;
+                state.healthy = false;
+                Err(e)
+            }
+        }
+    }
+
+    /// The hosts in order of preference: healthy first, then by policy. Ties are broken in
+    /// turn, starting one host further at each request.
+    fn order(&self, model: Option<&str>) -> Vec<&Host> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let count = self.hosts.len();
+        let mut hosts: Vec<_> = (0..count)
+            .map(|i| {
+                let host = &self.hosts[(start + i) % count];
+                let state = host.state.lock().unwrap();
+                let elsewhere = self.policy == Policy::ModelAffinity
+                    && !model.is_some_and(|model| state.loaded_models.iter().any(|m| m == model));
+                let load = match self.policy {
+                    Policy::RoundRobin => 0,
+                    _ => host.in_flight.load(Ordering::SeqCst),
+                };
+                (!state.healthy, elsewhere, load, host)
+            })
+            .collect();
+
+        // The sort is stable, so ties keep their turn.
+        hosts.sort_by_key(|(unhealthy, elsewhere, load, _)| (*unhealthy, *elsewhere, *load));
+        hosts.into_iter().map(|(_, _, _, host)| host).collect()
+    }
+
+    async fn probe_host(&self, host: &Host) {
+        let get = |path: &str| {
+            self.cli
+                .get(format!("{}{path}", host.url))
+                .timeout(self.probe_timeout)
+                .send()
+        };
+
+        let healthy = matches!(get("/api/version").await, Ok(r) if r.status().is_success());
+        let loaded_models = match (healthy, self.policy) {
+            (true, Policy::ModelAffinity) => match get("/api/ps").await {
+                Ok(response) => response.json::<Value>().await.ok().map(|body| {
+                    let models = body["models"].as_array().into_iter().flatten();
+                    let names = models.filter_map(|m| m["model"].as_str().or(m["name"].as_str()));
+                    names.map(normalize).collect()
+                }),
+                Err(_) => None,
+            },
+            _ => None,
+        };
+
+        let mut state = host.state.lock().unwrap();
+        state.healthy = healthy;
+        if let Some(loaded_models) = loaded_models {
+            state.loaded_models = loaded_models;
+        }
+    }
+}
+
+/// Whether a request to `path` creates, changes or deletes models on the host.
+fn manages_models(path: &str) -> bool {
+    matches!(
+        path,
+        "/api/create" | "/api/pull" | "/api/push" | "/api/copy" | "/api/delete"
+    ) || path.starts_with("/api/blobs/")
+}
+
+/// Counts a request as in flight until dropped.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn new(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count.clone())
+    }
+
+    /// Keep counting the request as in flight until the body of the response is dropped.
+    fn hold(self, response: reqwest::Response) -> reqwest::Response {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+
+        let mut bytes = response.bytes_stream();
+        let body = stream! {
+            let _in_flight = self;
+            while let Some(item) = bytes.next().await {
+                yield item;
+            }
+        };
+
+        // The status and headers are those of a response, so they are valid.
+        build_response(status, headers, reqwest::Body::wrap_stream(body)).unwrap()
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use super::{HostPool, Policy};
+    use crate::Ollama;
+    use crate::action::IntoStream;
+    use crate::error::OllamaError;
+    use crate::mock::MockOllama;
+
+    /// The URL of a port nothing listens on.
+    async fn dead_url() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[tokio::test]
+    async fn round_robin_should_fail_over_to_healthy_hosts() {
+        let (a, b) = (MockOllama::start().await, MockOllama::start().await);
+        let pool = Arc::new(HostPool::new([
+            a.url.clone(),
+            dead_url().await,
+            b.url.clone(),
+        ]));
+        let ollama = Ollama::with_pool(pool.clone());
+
+        for _ in 0..4 {
+            ollama.generate("llama3.1:8b").prompt("hi").await.unwrap();
+        }
+        let hosts = pool.hosts();
+        assert!(hosts[0].healthy && !hosts[1].healthy && hosts[2].healthy);
+        assert_eq!((hosts[1].requests, hosts[1].failures), (1, 1));
+        assert_eq!(a.requests("/api/generate").len(), 2);
+        assert_eq!(b.requests("/api/generate").len(), 2);
+
+        // A 5xx fails over too, but the host stays healthy as it answered.
+        a.fail_next(1);
+        b.fail_next(1);
+        let response = ollama.generate("llama3.1:8b").prompt("hi").await;
+        assert!(matches!(response, Err(OllamaError::OllamaServerError(_))));
+        b.fail_next(1);
+        ollama.generate("llama3.1:8b").prompt("hi").await.unwrap();
+        let hosts = pool.hosts();
+        assert!(hosts[2].healthy);
+        assert_eq!((hosts[2].requests, hosts[2].failures), (4, 2));
+        assert_eq!(a.requests("/api/generate").len(), 4);
+    }
+
+    #[tokio::test]
+    async fn least_in_flight_should_avoid_busy_hosts() {
+        let (a, b) = (MockOllama::start().await, MockOllama::start().await);
+        let pool = HostPool::new([&a.url, &b.url]).policy(Policy::LeastInFlight);
+        let pool = Arc::new(pool);
+        let ollama = Ollama::with_pool(pool.clone());
+
+        let stream = ollama.chat("llama3.1:8b").user_message("hi").stream().await;
+        let stream = stream.unwrap();
+        for _ in 0..3 {
+            ollama.chat("llama3.1:8b").user_message("hi").await.unwrap();
+        }
+        assert_eq!(a.requests("/api/chat").len(), 1);
+        assert_eq!(b.requests("/api/chat").len(), 3);
+        assert_eq!(pool.hosts()[0].in_flight, 1);
+
+        drop(stream);
+        assert_eq!(pool.hosts()[0].in_flight, 0);
+        assert_eq!(pool.hosts()[1].in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn model_affinity_should_prefer_hosts_with_the_model_loaded() {
+        let (a, b) = (MockOllama::start().await, MockOllama::start().await);
+        b.load("llama3.1:8b");
+        b.load("all-minilm");
+        let dead = dead_url().await;
+        let pool = HostPool::new([&a.url, &b.url, &dead]).policy(Policy::ModelAffinity);
+        let pool = Arc::new(pool);
+        pool.probe().await;
+
+        let hosts = pool.hosts();
+        assert_eq!(hosts[1].loaded_models, ["llama3.1:8b", "all-minilm:latest"]);
+        assert!(hosts[0].healthy && !hosts[2].healthy);
+
+        let ollama = Ollama::with_pool(pool.clone());
+        for _ in 0..3 {
+            ollama.chat("llama3.1:8b").user_message("hi").await.unwrap();
+        }
+        ollama.generate("all-minilm").prompt("hi").await.unwrap();
+        assert_eq!(b.requests("/api/chat").len(), 3);
+        assert_eq!(b.requests("/api/generate").len(), 1);
+        assert!(a.requests("/api/chat").is_empty());
+
+        // Other models are spread over the healthy hosts.
+        ollama.chat("qwen2.5").user_message("hi").await.unwrap();
+        ollama.chat("qwen2.5").user_message("hi").await.unwrap();
+        assert_eq!(a.requests("/api/chat").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_pools_should_return_the_last_error() {
+        let pool = HostPool::new([dead_url().await, dead_url().await]);
+        let ollama = Ollama::with_pool(Arc::new(pool));
+        let response = ollama.generate("llama3.1:8b").prompt("hi").await;
+        assert!(matches!(response, Err(OllamaError::RequestError(_))));
+
+        let ollama = Ollama::with_pool(Arc::new(HostPool::new(Vec::<String>::new())));
+        let response = ollama.generate("llama3.1:8b").prompt("hi").await;
+        assert!(matches!(response, Err(OllamaError::NoHostAvailable)));
+    }
+
+    #[tokio::test]
+    async fn model_management_should_stay_on_the_first_host() {
+        let (a, b) = (MockOllama::start().await, MockOllama::start().await);
+        let pool = Arc::new(HostPool::new([&a.url, &b.url]));
+        let ollama = Ollama::with_pool(pool.clone());
+
+        let path = std::env::temp_dir().join(format!("ollama-native-pool-{}", std::process::id()));
+        std::fs::write(&path, b"GGUF").unwrap();
+        let digest = "sha256:0bc6ff5f4d3b5e33ee9ab4cb6e2b3fbd52a1f5a4cb1d2b3d35e0fb5ec4f8cbd2";
+        let files = HashMap::from([("model.gguf", digest)]);
+
+        ollama
+            .push_blob(path.to_str().unwrap(), digest)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for _ in 0..2 {
+            ollama.version().await.unwrap();
+            let create = ollama.create_model("tiny").files(files.clone()).await;
+            assert_eq!(create.unwrap().status, "success");
+        }
+        assert_eq!(a.requests("/api/create").len(), 2);
+        assert!(b.requests("/api/create").is_empty());
+
+        // A failed create is not started again on another host.
+        a.fail_next(1);
+        let create = ollama.create_model("tiny").files(files).await;
+        assert!(matches!(create, Err(OllamaError::OllamaServerError(_))));
+        assert!(b.requests("/api/create").is_empty());
+        assert_eq!(pool.hosts()[0].failures, 1);
+    }
+}
@@ -0,0 +1,329 @@
+//! Retrieval-augmented chat: embed a question, retrieve the closest chunks, and answer it
+//! with the chunks rendered into the conversation as numbered sources.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Ollama,
+    abi::{Message, completion::chat::ChatCompletionResponse},
+    embeddings::{Document, VectorIndex},
+    error::OllamaError,
+};
+
+const DEFAULT_TOP_K: usize = 4;
+
+const DEFAULT_INSTRUCTIONS: &str = "Answer the question using the numbered sources below. \
+Cite the sources you use by their number, e.g. [1]. \
+If the sources do not contain the answer, say that you do not know.";
+
+/// A retrieved chunk along with its relevance to the question.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub document: Document,
+    /// The score given by the retriever, its scale depends on the retriever.
+    pub score: f64,
+}
+
+/// Finds the documents relevant to a question.
+#[async_trait]
+pub trait Retriever: Send + Sync {
+    /// The `k` most relevant documents, most relevant first. Both the question and its
+    /// embedding are given so retrievers may use either.
+    async fn retrieve(
+        &self,
+        query: &str,
+        embedding: &[f64],
+        k: usize,
+    ) -> Result<Vec<Source>, OllamaError>;
+}
+
+/// Retrieves from an in-memory index. Scores are those of the index metric, so lower is
+/// closer for [`Metric::Euclidean`][`crate::embeddings::Metric::Euclidean`].
+#[async_trait]
+impl Retriever for VectorIndex {
+    async fn retrieve(
+        &self,
+        _query: &str,
+        embedding: &[f64],
+        k: usize,
+    ) -> Result<Vec<Source>, OllamaError> {
+        let sources = self
+            .search(embedding, k)
+            .into_iter()
+            .map(|result| Source {
+                document: result.document.clone(),
+                score: result.score,
+            })
+            .collect();
+        Ok(sources)
+    }
+}
+
+/// Where the retrieved sources are placed in the conversation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextPlacement {
+    /// In a system message right before the question.
+    #[default]
+    System,
+
+    /// In the user message, before the question.
+    User,
+}
+
+/// The messages to send for a question along with the sources they contain, for callers
+/// who want to run the chat themselves, e.g. to stream the answer.
+#[derive(Debug, Clone)]
+pub struct RagContext {
+    pub messages: Vec<Message>,
+    pub sources: Vec<Source>,
+}
+
+/// The answer to a question along with the sources it was given.
+#[derive(Debug, Clone)]
+pub struct RagResponse {
+    pub response: ChatCompletionResponse,
+    pub sources: Vec<Source>,
+}
+
+impl RagResponse {
+    /// The content of the answer.
+    pub fn answer(&self) -> &str {
+        self.response
+            .message
+            .as_ref()
+            .map_or("", |m| m.content.as_str())
+    }
+}
+
+/// A retrieval-augmented chat pipeline.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::rag::Rag;
+///
+/// let rag = Rag::new(&ollama, &index, "all-minilm", "llama3.1:8b")
+///     .top_k(3)
+///     .system_prompt("You answer questions about our internal docs.");
+///
+/// let resp = rag.ask("How do I rotate the API keys?").await?;
+/// println!("{}", resp.answer());
+/// for source in resp.sources {
+///     println!("- {}", source.document.id);
+/// }
+/// ```
+pub struct Rag<'a> {
+    ollama: &'a Ollama,
+    retriever: &'a dyn Retriever,
+    embedding_model: &'a str,
+    chat_model: &'a str,
+    top_k: usize,
+    min_score: Option<f64>,
+    system_prompt: Option<&'a str>,
+    instructions: &'a str,
+    placement: ContextPlacement,
+    history: Vec<Message>,
+}
+
+impl<'a> Rag<'a> {
+    pub fn new(
+        ollama: &'a Ollama,
+        retriever: &'a dyn Retriever,
+        embedding_model: &'a str,
+        chat_model: &'a str,
+    ) -> Self {
+        Self {
+            ollama,
+            retriever,
+            embedding_model,
+            chat_model,
+            top_k: DEFAULT_TOP_K,
+            min_score: None,
+            system_prompt: None,
+            instructions: DEFAULT_INSTRUCTIONS,
+            placement: ContextPlacement::default(),
+            history: vec![],
+        }
+    }
+
+    /// Number of sources to retrieve (default: 4).
+    #[inline]
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Drop sources scoring below `min_score`. Only meaningful for retrievers where a
+    /// higher score is closer.
+    #[inline]
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// A system message sent before everything else.
+    #[inline]
+    pub fn system_prompt(mut self, system_prompt: &'a str) -> Self {
+        self.system_prompt = Some(system_prompt);
+        self
+    }
+
+    /// The text introducing the sources, replacing the default instructions to answer
+    /// from the sources and cite them by number.
+    #[inline]
+    pub fn instructions(mut self, instructions: &'a str) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    /// Where the sources are placed (default: a system message).
+    #[inline]
+    pub fn placement(mut self, placement: ContextPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Previous turns of the conversation, sent between the system prompt and the question.
+    #[inline]
+    pub fn history(mut self, history: Vec<Message>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Retrieve the sources for a question and build the messages to send.
+    pub async fn prepare(&self, question: &str) -> Result<RagContext, OllamaError> {
+        let embedding = self
+            .ollama
+            .generate_embeddings(self.embedding_model)
+            .input(question)
+            .await?
+            .embeddings
+            .into_iter()
+            .next()
+            .ok_or_else(|| OllamaError::InvalidFormat("no embedding for the question".into()))?;
+
+        let mut sources = self
+            .retriever
+            .retrieve(question, &embedding, self.top_k)
+            .await?;
+        if let Some(min_score) = self.min_score {
+            sources.retain(|s| s.score >= min_score);
+        }
+
+        let context = render_sources(self.instructions, &sources);
+        let mut messages: Vec<Message> = self
+            .system_prompt
+            .map(Message::system)
+            .into_iter()
+            .collect();
+        messages.extend(self.history.iter().cloned());
+        match self.placement {
+            ContextPlacement::System => {
+                messages.push(Message::system(&context));
+                messages.push(Message::user(question));
+            }
+            ContextPlacement::User => {
+                messages.push(Message::user(&format!("{context}\n\nQuestion: {question}")));
+            }
+        }
+
+        Ok(RagContext { messages, sources })
+    }
+
+    /// Answer a question from the retrieved sources.
+    pub async fn ask(&self, question: &str) -> Result<RagResponse, OllamaError> {
+        let RagContext { messages, sources } = self.prepare(question).await?;
+        let response = self.ollama.chat(self.chat_model).messages(messages).await?;
+        Ok(RagResponse { response, sources })
+    }
+}
+
+/// Render sources as `[n] (id) text` blocks after the instructions.
+pub fn render_sources(instructions: &str, sources: &[Source]) -> String {
+    let mut rendered = instructions.to_string();
+    for (i, source) in sources.iter().enumerate() {
+        rendered.push_str(&format!(
+            "\n\n[{}] ({}) {}",
+            i + 1,
+            source.document.id,
+            source.document.text.trim()
+        ));
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContextPlacement, Rag};
+    use crate::{
+        abi::Role,
+        embeddings::{Document, VectorIndex},
+        mock::MockOllama,
+    };
+
+    /// The mock embeds texts as `[chars, words, 1.0]`.
+    fn index() -> VectorIndex {
+        let mut index = VectorIndex::new();
+        for (id, text) in [
+            ("short", "Sharks"),
+            ("medium", "Sharks are fish"),
+            ("long", "Sharks are fish that live in every ocean"),
+        ] {
+            let embedding = vec![text.len() as f64, text.split(' ').count() as f64, 1.0];
+            index.add(Document::new(id, text), embedding).unwrap();
+        }
+        index
+    }
+
+    #[tokio::test]
+    async fn ask_should_cite_sources() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let index = index();
+
+        let rag = Rag::new(&ollama, &index, "all-minilm", "llama3.1:8b")
+            .top_k(2)
+            .system_prompt("Be brief.");
+        let resp = rag.ask("Are sharks fish").await.unwrap();
+
+        let ids: Vec<&str> = resp
+            .sources
+            .iter()
+            .map(|s| s.document.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["medium", "long"]);
+        assert_eq!(resp.answer(), "echo: Are sharks fish");
+
+        let chat = &mock.requests("/api/chat")[0];
+        let messages = chat["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["content"], "Be brief.");
+        let context = messages[1]["content"].as_str().unwrap();
+        assert!(context.contains("[1] (medium) Sharks are fish"));
+        assert!(context.contains("[2] (long) Sharks are fish that live in every ocean"));
+        assert_eq!(messages[2]["content"], "Are sharks fish");
+    }
+
+    #[tokio::test]
+    async fn prepare_should_place_context_in_user_message() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let index = index();
+
+        let context = Rag::new(&ollama, &index, "all-minilm", "llama3.1:8b")
+            .top_k(1)
+            .placement(ContextPlacement::User)
+            .instructions("Use these:")
+            .prepare("Sharks?")
+            .await
+            .unwrap();
+
+        assert_eq!(context.sources.len(), 1);
+        assert_eq!(context.messages.len(), 1);
+        assert_eq!(context.messages[0].role, Role::User);
+        let content = &context.messages[0].content;
+        assert!(content.starts_with("Use these:\n\n[1] "));
+        assert!(content.ends_with("Question: Sharks?"));
+        assert!(mock.requests("/api/chat").is_empty());
+    }
+}
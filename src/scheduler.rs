@@ -0,0 +1,573 @@
+//! A client-side scheduler for chat, generate and embedding requests.
+//!
+//! Ollama runs a limited number of requests per model at a time and queues the rest with no
+//! visibility. [`Scheduler`] queues them in the client instead, under a global limit and
+//! per-model limits, and lets interactive requests overtake batch ones.
+//!
+//! Requests are granted in order of priority, then in the order they were queued. A request
+//! whose model is at its limit does not hold back requests for other models. A streamed
+//! request holds its slot until the stream is dropped.
+//!
+//! Priority is strict by default: as long as interactive requests keep coming, batch ones
+//! wait. [`Scheduler::batch_aging`] bounds that wait by letting batch requests that have
+//! waited long enough go ahead of interactive ones.
+//!
+//! # Example
+//! ```rust,ignore
+//! use std::{sync::Arc, time::Duration};
+//! use ollama_native::{Ollama, scheduler::{Priority, Scheduler}};
+//!
+//! let scheduler = Scheduler::new()
+//!     .global_limit(4)
+//!     .default_model_limit(1)
+//!     .model_limit("llama3.1:8b", 2)
+//!     .queue_timeout(Duration::from_secs(30));
+//! let ollama = Ollama::new("http://localhost:11434").scheduler(Arc::new(scheduler));
+//!
+//! let response = ollama
+//!     .generate("llama3.1:8b")
+//!     .prompt("Summarize this document")
+//!     .priority(Priority::Batch)
+//!     .await?;
+//! ```
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use crate::abi::model::name::normalize;
+use crate::error::OllamaError;
+
+#[cfg(feature = "stream")]
+use {crate::action::OllamaStream, tokio_stream::StreamExt};
+
+/// The class of a request. Interactive requests are granted before batch ones, unless
+/// [`Scheduler::batch_aging`] is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    #[default]
+    Interactive,
+    Batch,
+}
+
+/// Statistics of one priority class.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriorityStats {
+    /// Requests waiting now.
+    pub queued: usize,
+
+    /// Requests granted so far.
+    pub granted: u64,
+
+    /// Requests that gave up waiting after the queue timeout.
+    pub timed_out: u64,
+
+    /// The time granted requests have waited, in total.
+    pub total_wait: Duration,
+
+    /// The longest time a granted request has waited.
+    pub max_wait: Duration,
+}
+
+impl PriorityStats {
+    /// The average time granted requests have waited.
+    pub fn mean_wait(&self) -> Option<Duration> {
+        let granted = u32::try_from(self.granted).ok().filter(|g| *g > 0)?;
+        Some(self.total_wait / granted)
+    }
+}
+
+/// A snapshot of the scheduler.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchedulerStats {
+    /// Requests running now.
+    pub running: usize,
+
+    /// Requests running now, per model.
+    pub running_by_model: BTreeMap<String, usize>,
+
+    /// Requests waiting now, per model.
+    pub queued_by_model: BTreeMap<String, usize>,
+
+    pub interactive: PriorityStats,
+    pub batch: PriorityStats,
+}
+
+impl SchedulerStats {
+    /// Requests waiting now.
+    pub fn queued(&self) -> usize {
+        self.interactive.queued + self.batch.queued
+    }
+
+    fn priority(&mut self, priority: Priority) -> &mut PriorityStats {
+        match priority {
+            Priority::Interactive => &mut self.interactive,
+            Priority::Batch => &mut self.batch,
+        }
+    }
+}
+
+struct Waiter {
+    ticket: u64,
+    model: String,
+    queued_at: Instant,
+    grant: oneshot::Sender<Permit>,
+}
+
+#[derive(Default)]
+struct State {
+    running: BTreeMap<String, usize>,
+    running_total: usize,
+    queues: [VecDeque<Waiter>; 2],
+    next_ticket: u64,
+    stats: SchedulerStats,
+}
+
+/// Limits and orders the requests of one or more clients, see the
+/// [module documentation](self).
+#[derive(Default)]
+pub struct Scheduler {
+    global_limit: Option<usize>,
+    default_model_limit: Option<usize>,
+    model_limits: HashMap<String, usize>,
+    queue_timeout: Option<Duration>,
+    batch_aging: Option<Duration>,
+    state: Mutex<State>,
+}
+
+impl Scheduler {
+    /// A scheduler without limits, to be configured with the methods below.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of requests that may run at once across all models (default: unlimited).
+    #[inline]
+    pub fn global_limit(mut self, limit: usize) -> Self {
+        self.global_limit = Some(limit.max(1));
+        self
+    }
+
+    /// The number of requests that may run at once for models without a limit of their own
+    /// (default: unlimited).
+    #[inline]
+    pub fn default_model_limit(mut self, limit: usize) -> Self {
+        self.default_model_limit = Some(limit.max(1));
+        self
+    }
+
+    /// The number of requests that may run at once for a model. Model names without a tag
+    /// are given the `latest` tag, as Ollama does.
+    #[inline]
+    pub fn model_limit(mut self, model: &str, limit: usize) -> Self {
+        self.model_limits.insert(normalize(model), limit.max(1));
+        self
+    }
+
+    /// How long a request may wait for its turn before failing with
+    /// [`OllamaError::QueueTimeout`] (default: forever).
+    #[inline]
+    pub fn queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = Some(timeout);
+        self
+    }
+
+    /// Let batch requests that have waited for `after` go ahead of interactive ones, so a
+    /// steady flow of interactive requests cannot starve them (default: never).
+    #[inline]
+    pub fn batch_aging(mut self, after: Duration) -> Self {
+        self.batch_aging = Some(after);
+        self
+    }
+
+    pub fn stats(&self) -> SchedulerStats {
+        self.state.lock().unwrap().stats.clone()
+    }
+
+    fn model_limit_of(&self, model: &str) -> Option<usize> {
+        self.model_limits
+            .get(model)
+            .copied()
+            .or(self.default_model_limit)
+    }
+
+    /// Wait for a slot for a request to `model`.
+    pub(crate) async fn acquire(
+        self: &Arc<Self>,
+        model: &str,
+        priority: Priority,
+    ) -> Result<Permit, OllamaError> {
+        let model = normalize(model);
+        let (grant, mut granted) = oneshot::channel();
+        let ticket = {
+            let mut state = self.state.lock().unwrap();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.queues[priority as usize].push_back(Waiter {
+                ticket,
+                model: model.clone(),
+                queued_at: Instant::now(),
+                grant,
+            });
+            state.stats.priority(priority).queued += 1;
+            *state.stats.queued_by_model.entry(model).or_default() += 1;
+            ticket
+        };
+        self.dispatch();
+
+        // Leave the queue if the caller stops waiting.
+        let queued = Queued {
+            scheduler: self,
+            ticket,
+            priority,
+        };
+        let granted = match self.queue_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, &mut granted).await {
+                Ok(granted) => granted,
+                Err(_) if queued.leave() => {
+                    let mut state = self.state.lock().unwrap();
+                    state.stats.priority(priority).timed_out += 1;
+                    return Err(OllamaError::QueueTimeout(timeout));
+                }
+                // Granted just as the timeout fired, the permit is on its way.
+                Err(_) => granted.await,
+            },
+            None => granted.await,
+        };
+        // Waiters leave the queue either through `dispatch`, which sends them a permit, or
+        // through `leave`, after which they stop waiting.
+        Ok(granted.expect("a waiter left the queue without a permit"))
+    }
+
+    /// Grant slots to the waiters that fit, by priority then in order, aged batch waiters
+    /// first. The permits are sent once the lock is released, as dropping one that its
+    /// waiter gave up on takes the lock.
+    fn dispatch(self: &Arc<Self>) {
+        // Queues are in the order waiters arrived, so the aged batch waiters come first.
+        let passes = [
+            (Priority::Batch, true),
+            (Priority::Interactive, false),
+            (Priority::Batch, false),
+        ];
+        let aged = |waiter: &Waiter| {
+            self.batch_aging
+                .is_some_and(|after| waiter.queued_at.elapsed() >= after)
+        };
+
+        let mut grants = vec![];
+        {
+            let mut state = self.state.lock().unwrap();
+            let state = &mut *state;
+            for (priority, aged_only) in passes {
+                let mut i = 0;
+                while i < state.queues[priority as usize].len() {
+                    if self.global_limit.is_some_and(|l| state.running_total >= l) {
+                        break;
+                    }
+
+                    let waiter = &state.queues[priority as usize][i];
+                    if aged_only && !aged(waiter) {
+                        break;
+                    }
+                    let running = state.running.get(&waiter.model).copied().unwrap_or(0);
+                    if self
+                        .model_limit_of(&waiter.model)
+                        .is_some_and(|l| running >= l)
+                    {
+                        i += 1;
+                        continue;
+                    }
+
+                    let waiter = state.queues[priority as usize].remove(i).unwrap();
+                    let wait = waiter.queued_at.elapsed();
+                    *state.running.entry(waiter.model.clone()).or_default() += 1;
+                    state.running_total += 1;
+
+                    let stats = &mut state.stats;
+                    stats.running += 1;
+                    *stats
+                        .running_by_model
+                        .entry(waiter.model.clone())
+                        .or_default() += 1;
+                    decrement(&mut stats.queued_by_model, &waiter.model);
+                    let class = stats.priority(priority);
+                    class.queued -= 1;
+                    class.granted += 1;
+                    class.total_wait += wait;
+                    class.max_wait = class.max_wait.max(wait);
+
+                    let permit = Permit {
+                        scheduler: Some(self.clone()),
+                        model: waiter.model,
+                    };
+                    grants.push((waiter.grant, permit));
+                }
+            }
+        }
+
+        for (grant, permit) in grants {
+            // A waiter that gave up drops the permit, which frees the slot again.
+            let _ = grant.send(permit);
+        }
+    }
+
+    fn release(self: &Arc<Self>, model: &str) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.running_total -= 1;
+            decrement(&mut state.running, model);
+            state.stats.running -= 1;
+            decrement(&mut state.stats.running_by_model, model);
+        }
+        self.dispatch();
+    }
+}
+
+/// A request in the queue, removed from it when dropped.
+struct Queued<'a> {
+    scheduler: &'a Arc<Scheduler>,
+    ticket: u64,
+    priority: Priority,
+}
+
+impl Queued<'_> {
+    /// Remove the request from the queue, returning whether it was still there.
+    fn leave(&self) -> bool {
+        let mut state = self.scheduler.state.lock().unwrap();
+        let queue = &mut state.queues[self.priority as usize];
+        let Some(i) = queue.iter().position(|w| w.ticket == self.ticket) else {
+            return false;
+        };
+        let waiter = queue.remove(i).unwrap();
+        decrement(&mut state.stats.queued_by_model, &waiter.model);
+        state.stats.priority(self.priority).queued -= 1;
+        true
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+/// A slot for a request, freed when dropped. Clients without a scheduler get empty permits.
+#[derive(Default)]
+pub(crate) struct Permit {
+    scheduler: Option<Arc<Scheduler>>,
+    model: String,
+}
+
+impl Permit {
+    /// Keep the slot until the stream is dropped.
+    #[cfg(feature = "stream")]
+    pub(crate) fn hold<T: Send + 'static>(self, stream: OllamaStream<T>) -> OllamaStream<T> {
+        if self.scheduler.is_none() {
+            return stream;
+        }
+        Box::pin(stream.map(move |item| {
+            let _ = &self;
+            item
+        }))
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.release(&self.model);
+        }
+    }
+}
+
+fn decrement(counts: &mut BTreeMap<String, usize>, model: &str) {
+    if let Some(count) = counts.get_mut(model) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(model);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::task::Poll;
+    use std::time::Duration;
+
+    use futures::poll;
+    use tokio_stream::StreamExt;
+
+    use super::{Priority, Scheduler};
+    use crate::action::IntoStream;
+    use crate::error::OllamaError;
+    use crate::mock::MockOllama;
+
+    #[tokio::test]
+    async fn limits_should_be_enforced() {
+        let scheduler = Arc::new(
+            Scheduler::new()
+                .global_limit(2)
+                .default_model_limit(1)
+                .model_limit("llama3.1:8b", 2),
+        );
+
+        let first = scheduler.acquire("all-minilm", Priority::Interactive).await;
+        let mut second = Box::pin(scheduler.acquire("all-minilm:latest", Priority::Interactive));
+        assert!(poll!(&mut second).is_pending());
+
+        // Another model is not held back by the queued request.
+        let third = scheduler
+            .acquire("llama3.1:8b", Priority::Interactive)
+            .await;
+        let mut fourth = Box::pin(scheduler.acquire("llama3.1:8b", Priority::Interactive));
+        assert!(poll!(&mut fourth).is_pending());
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.running, 2);
+        assert_eq!(stats.queued(), 2);
+        assert_eq!(stats.queued_by_model["all-minilm:latest"], 1);
+
+        // Freeing a llama slot lets the llama request in, within the global limit.
+        drop(third);
+        assert!(matches!(poll!(&mut fourth), Poll::Ready(Ok(_))));
+        assert!(poll!(&mut second).is_pending());
+
+        drop(first);
+        let second = second.await.unwrap();
+        let stats = scheduler.stats();
+        assert_eq!(stats.queued(), 0);
+        assert_eq!(stats.interactive.granted, 4);
+        assert_eq!(stats.running_by_model["all-minilm:latest"], 1);
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn interactive_requests_should_overtake_batch_ones() {
+        let scheduler = Arc::new(Scheduler::new().global_limit(1));
+        let running = scheduler.acquire("m", Priority::Batch).await.unwrap();
+
+        let mut batch = Box::pin(scheduler.acquire("m", Priority::Batch));
+        let mut interactive = Box::pin(scheduler.acquire("m", Priority::Interactive));
+        let mut later = Box::pin(scheduler.acquire("m", Priority::Interactive));
+        assert!(poll!(&mut batch).is_pending());
+        assert!(poll!(&mut interactive).is_pending());
+        assert!(poll!(&mut later).is_pending());
+        assert_eq!(scheduler.stats().batch.queued, 1);
+        assert_eq!(scheduler.stats().interactive.queued, 2);
+
+        drop(running);
+        let Poll::Ready(Ok(permit)) = poll!(&mut interactive) else {
+            panic!("the first interactive request should run first");
+        };
+        assert!(poll!(&mut later).is_pending());
+        assert!(poll!(&mut batch).is_pending());
+
+        drop(permit);
+        let permit = later.await.unwrap();
+        assert!(poll!(&mut batch).is_pending());
+        drop(permit);
+        batch.await.unwrap();
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.batch.granted, 2);
+        assert!(stats.batch.max_wait >= stats.interactive.max_wait);
+        assert!(stats.batch.mean_wait().is_some());
+    }
+
+    #[tokio::test]
+    async fn aged_batch_requests_should_go_first() {
+        let scheduler = Arc::new(
+            Scheduler::new()
+                .global_limit(1)
+                .batch_aging(Duration::from_millis(20)),
+        );
+        let running = scheduler.acquire("m", Priority::Interactive).await.unwrap();
+
+        let mut batch = Box::pin(scheduler.acquire("m", Priority::Batch));
+        assert!(poll!(&mut batch).is_pending());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let mut interactive = Box::pin(scheduler.acquire("m", Priority::Interactive));
+        assert!(poll!(&mut interactive).is_pending());
+
+        drop(running);
+        let Poll::Ready(Ok(permit)) = poll!(&mut batch) else {
+            panic!("the aged batch request should run first");
+        };
+        assert!(poll!(&mut interactive).is_pending());
+        drop(permit);
+        interactive.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn waiting_should_time_out() {
+        let scheduler = Arc::new(
+            Scheduler::new()
+                .global_limit(1)
+                .queue_timeout(Duration::from_millis(20)),
+        );
+        let running = scheduler.acquire("m", Priority::Interactive).await.unwrap();
+
+        let result = scheduler.acquire("m", Priority::Interactive).await;
+        assert!(matches!(result, Err(OllamaError::QueueTimeout(_))));
+
+        // A request that is dropped while waiting leaves the queue as well.
+        let mut dropped = Box::pin(scheduler.acquire("m", Priority::Batch));
+        assert!(poll!(&mut dropped).is_pending());
+        drop(dropped);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.queued(), 0);
+        assert!(stats.queued_by_model.is_empty());
+        assert_eq!(stats.interactive.timed_out, 1);
+
+        drop(running);
+        assert_eq!(scheduler.stats().running, 0);
+        assert!(scheduler.acquire("m", Priority::Batch).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn actions_should_hold_slots_until_done() {
+        let mock = MockOllama::start().await;
+        let scheduler = Arc::new(
+            Scheduler::new()
+                .global_limit(1)
+                .queue_timeout(Duration::from_millis(20)),
+        );
+        let ollama = mock.ollama().scheduler(scheduler.clone());
+
+        let mut stream = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .stream()
+            .await
+            .unwrap();
+        assert_eq!(scheduler.stats().running_by_model["llama3.1:8b"], 1);
+
+        let embed = ollama
+            .generate_embeddings("all-minilm")
+            .input("hi")
+            .priority(Priority::Batch)
+            .await;
+        assert!(matches!(embed, Err(OllamaError::QueueTimeout(_))));
+
+        while stream.next().await.is_some() {}
+        drop(stream);
+        assert_eq!(scheduler.stats().running, 0);
+
+        ollama
+            .generate_embeddings("all-minilm")
+            .input("hi")
+            .priority(Priority::Batch)
+            .await
+            .unwrap();
+        ollama.generate("llama3.1:8b").prompt("hi").await.unwrap();
+
+        let stats = scheduler.stats();
+        assert_eq!((stats.interactive.granted, stats.batch.granted), (2, 1));
+        assert_eq!(stats.batch.timed_out, 1);
+        assert_eq!(stats.running, 0);
+    }
+}
@@ -0,0 +1,788 @@
+//! An OpenAI-compatible HTTP front for an Ollama server, so off-the-shelf OpenAI SDKs can
+//! be pointed at it. `/v1/chat/completions` (with SSE streaming), `/v1/embeddings` and
+//! `/v1/models` are translated into [`Ollama::chat`], [`Ollama::generate_embeddings`] and
+//! [`Ollama::list_local_models`].
+//!
+//! The [`Router`] can be served as is, as the `ollama-proxy` binary does, or wrapped in
+//! further layers such as authentication and logging:
+//!
+//! ```rust,ignore
+//! use ollama_native::{Ollama, server::Server};
+//!
+//! let router = Server::new(Ollama::new("http://localhost:11434"))
+//!     .api_key("secret")
+//!     .router();
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
+//! axum::serve(listener, router).await?;
+//! ```
+//!
+//! Only `temperature`, `top_p`, `seed`, a single `stop` sequence and `max_tokens` are
+//! forwarded as model options, other sampling parameters are ignored. More than one `stop`
+//! sequence is rejected, as are images given as anything but a `data:` URI: the server does
+//! not fetch URLs or read files on behalf of its clients.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_stream::stream;
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Request, State},
+    http::{StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, Sse},
+    },
+    routing::{get, post},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio_stream::StreamExt;
+
+use crate::abi::model::name::ModelName;
+use crate::abi::{Image, Message, Options, Role, Usage};
+use crate::action::IntoStream;
+use crate::error::OllamaError;
+use crate::{ChatCompletionResponse, Ollama};
+
+/// Builds the [`Router`] serving the OpenAI-compatible API.
+pub struct Server {
+    ollama: Ollama,
+    api_key: Option<String>,
+}
+
+impl Server {
+    pub fn new(ollama: Ollama) -> Self {
+        Self {
+            ollama,
+            api_key: None,
+        }
+    }
+
+    /// Reject requests without an `Authorization: Bearer <api_key>` header.
+    #[inline]
+    pub fn api_key(mut self, api_key: impl ToString) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    pub fn router(self) -> Router {
+        let state = Arc::new(ServerState {
+            ollama: self.ollama,
+            api_key: self.api_key,
+            next_id: AtomicU64::new(1),
+        });
+
+        Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/v1/embeddings", post(embeddings))
+            .route("/v1/models", get(models))
+            .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+            .with_state(state)
+    }
+}
+
+struct ServerState {
+    ollama: Ollama,
+    api_key: Option<String>,
+    next_id: AtomicU64,
+}
+
+impl ServerState {
+    fn id(&self, prefix: &str) -> String {
+        format!("{prefix}-{}", self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// An error in the OpenAI format, `{"error": {"message": ..., "type": ...}}`.
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let kind = match self.status {
+            StatusCode::UNAUTHORIZED => "authentication_error",
+            status if status.is_client_error() => "invalid_request_error",
+            _ => "api_error",
+        };
+        json!({ "error": { "message": self.message, "type": kind, "param": null, "code": null } })
+    }
+}
+
+impl From<OllamaError> for ApiError {
+    fn from(error: OllamaError) -> Self {
+        let status = match &error {
+            OllamaError::OllamaServerError(message) if message.contains("not found") => {
+                StatusCode::NOT_FOUND
+            }
+            OllamaError::ModelDoesNotExist => StatusCode::NOT_FOUND,
+            OllamaError::InvalidFormat(_) | OllamaError::ImageError(_) => StatusCode::BAD_REQUEST,
+            OllamaError::RequestError(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let message = match error {
+            OllamaError::OllamaServerError(message) => message,
+            error => error.to_string(),
+        };
+        Self { status, message }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.to_json())).into_response()
+    }
+}
+
+async fn authorize(
+    State(state): State<Arc<ServerState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(api_key) = &state.api_key {
+        let expected = format!("Bearer {api_key}");
+        let header = request.headers().get(AUTHORIZATION);
+        if !header.is_some_and(|h| constant_time_eq(h.as_bytes(), expected.as_bytes())) {
+            return ApiError::new(StatusCode::UNAUTHORIZED, "invalid API key").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Compares two byte strings in a time that depends on their length only, so that the API
+/// key cannot be guessed one byte at a time from response times.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Bodies are parsed by hand so that clients which omit the JSON content type are served.
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    tools: Vec<Value>,
+    #[serde(default)]
+    response_format: Option<Value>,
+    #[serde(flatten)]
+    sampling: Sampling,
+    #[serde(default)]
+    stream: bool,
+    #[serde(default)]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    role: Role,
+    #[serde(default)]
+    content: Option<Content>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ToolCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Content {
+    Text(String),
+    Parts(Vec<Part>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Part {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCall {
+    function: FunctionCall,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionCall {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Sampling {
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    seed: Option<i64>,
+    stop: Option<Stop>,
+    max_tokens: Option<i64>,
+    max_completion_tokens: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Stop {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamOptions {
+    #[serde(default)]
+    include_usage: bool,
+}
+
+impl Sampling {
+    /// The sampling parameters as options. [`Options`] holds a single stop sequence, so
+    /// more than one is a bad request rather than silently dropped.
+    fn to_options(&self) -> Result<Options, ApiError> {
+        let mut options = Options::default();
+        if let Some(temperature) = self.temperature {
+            options.temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            options.top_p(top_p);
+        }
+        if let Some(seed) = self.seed {
+            options.seed(seed);
+        }
+        let stop = match &self.stop {
+            Some(Stop::One(stop)) => Some(stop),
+            Some(Stop::Many(stops)) if stops.len() > 1 => {
+                let message = "only one stop sequence is supported";
+                return Err(ApiError::new(StatusCode::BAD_REQUEST, message));
+            }
+            Some(Stop::Many(stops)) => stops.first(),
+            None => None,
+        };
+        if let Some(stop) = stop {
+            options.stop(stop);
+        }
+        if let Some(max_tokens) = self.max_completion_tokens.or(self.max_tokens) {
+            options.num_predict(max_tokens);
+        }
+        Ok(options)
+    }
+}
+
+/// Only `data:` URIs are accepted for images: URLs would be downloaded and any other
+/// string read as a path by the server, on behalf of any client.
+impl TryFrom<ChatMessage> for Message {
+    type Error = ApiError;
+
+    fn try_from(message: ChatMessage) -> Result<Self, ApiError> {
+        let mut content = String::new();
+        let mut images = vec![];
+        match message.content {
+            Some(Content::Text(text)) => content = text,
+            Some(Content::Parts(parts)) => {
+                for part in parts {
+                    match part {
+                        Part::Text { text } => content.push_str(&text),
+                        Part::ImageUrl { image_url } if image_url.url.starts_with("data:") => {
+                            images.push(Image::DataUri(image_url.url))
+                        }
+                        Part::ImageUrl { .. } => {
+                            let message = "image_url must be a data: URI";
+                            return Err(ApiError::new(StatusCode::BAD_REQUEST, message));
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+
+        let tool_calls = message.tool_calls.map(|calls| {
+            calls
+                .into_iter()
+                .map(|call| {
+                    let arguments = serde_json::from_str(&call.function.arguments)
+                        .unwrap_or(Value::String(call.function.arguments));
+                    json!({ "function": { "name": call.function.name, "arguments": arguments } })
+                })
+                .collect()
+        });
+
+        Ok(Message {
+            role: message.role,
+            content,
+            thinking: None,
+            images: (!images.is_empty()).then_some(images),
+            tool_calls,
+        })
+    }
+}
+
+async fn chat_completions(
+    State(state): State<Arc<ServerState>>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let request: ChatRequest = parse_body(&body)?;
+
+    let format = match &request.response_format {
+        Some(format) if format["type"] == "json_object" => Some("json".to_string()),
+        Some(format) if format["type"] == "json_schema" => match &format["json_schema"]["schema"] {
+            Value::Null => {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "response_format.json_schema.schema is required",
+                ));
+            }
+            schema => Some(schema.to_string()),
+        },
+        _ => None,
+    };
+    let tools: Vec<String> = request.tools.iter().map(Value::to_string).collect();
+    let include_usage = request
+        .stream_options
+        .as_ref()
+        .is_some_and(|o| o.include_usage);
+    let messages = request
+        .messages
+        .into_iter()
+        .map(Message::try_from)
+        .collect::<Result<_, _>>()?;
+
+    let mut action = state
+        .ollama
+        .chat(&request.model)
+        .messages(messages)
+        .options(request.sampling.to_options()?);
+    if !tools.is_empty() {
+        action = action.tools(tools.iter().map(String::as_str).collect());
+    }
+    if let Some(format) = &format {
+        action = action.format(format);
+    }
+
+    let id = state.id("chatcmpl");
+    if !request.stream {
+        let response = action.await?;
+        let mut completion = chat_completion(&id, &response, false);
+        completion["usage"] = usage(&response.usage);
+        return Ok(Json(completion).into_response());
+    }
+
+    let mut chunks = action.stream().await?;
+    let events = stream! {
+        let mut tool_calls = false;
+        while let Some(chunk) = chunks.next().await {
+            let mut chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    yield Event::default().data(ApiError::from(e).to_json().to_string());
+                    break;
+                }
+            };
+
+            let calls = chunk.message.as_ref().and_then(|m| m.tool_calls.as_ref());
+            tool_calls |= calls.is_some_and(|c| !c.is_empty());
+            if chunk.done && tool_calls {
+                chunk.done_reason = Some("tool_calls".to_string());
+            }
+            yield Event::default().data(chat_completion(&id, &chunk, true).to_string());
+
+            if chunk.done && include_usage {
+                let mut usage_chunk = chat_completion(&id, &chunk, true);
+                usage_chunk["choices"] = json!([]);
+                usage_chunk["usage"] = usage(&chunk.usage);
+                yield Event::default().data(usage_chunk.to_string());
+            }
+        }
+        yield Event::default().data("[DONE]");
+    };
+
+    Ok(Sse::new(events.map(Ok::<_, Infallible>)).into_response())
+}
+
+/// A response as a `chat.completion` or, for streams, a `chat.completion.chunk` object.
+fn chat_completion(id: &str, response: &ChatCompletionResponse, chunk: bool) -> Value {
+    let message = response.message.clone().unwrap_or(Message::assistant(""));
+    let mut value = json!({ "role": "assistant", "content": message.content });
+    if let Some(thinking) = message.thinking.filter(|t| !t.is_empty()) {
+        value["reasoning"] = json!(thinking);
+    }
+    let tool_calls = message.tool_calls.unwrap_or_default();
+    if !tool_calls.is_empty() {
+        let tool_calls: Vec<Value> = tool_calls
+            .iter()
+            .enumerate()
+            .map(|(i, call)| {
+                let function = &call["function"];
+                json!({
+                    "index": i,
+                    "id": format!("call_{i}"),
+                    "type": "function",
+                    "function": {
+                        "name": function["name"],
+                        "arguments": function["arguments"].to_string(),
+                    },
+                })
+            })
+            .collect();
+        value["tool_calls"] = Value::Array(tool_calls);
+    }
+
+    let finish_reason = match (response.done, response.done_reason.as_deref()) {
+        (false, _) => None,
+        (true, _) if !tool_calls.is_empty() => Some("tool_calls"),
+        (true, reason) => Some(reason.unwrap_or("stop")),
+    };
+    let (object, key) = match chunk {
+        true => ("chat.completion.chunk", "delta"),
+        false => ("chat.completion", "message"),
+    };
+
+    json!({
+        "id": id,
+        "object": object,
+        "created": response.created_at.timestamp(),
+        "model": response.model,
+        "choices": [{ "index": 0, key: value, "finish_reason": finish_reason }],
+    })
+}
+
+fn usage(usage: &Usage) -> Value {
+    let prompt_tokens = usage.prompt_eval_count.unwrap_or_default();
+    let completion_tokens = usage.eval_count.unwrap_or_default();
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingRequest {
+    model: String,
+    input: Input,
+    #[serde(default)]
+    encoding_format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Input {
+    One(String),
+    Many(Vec<String>),
+}
+
+async fn embeddings(
+    State(state): State<Arc<ServerState>>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let request: EmbeddingRequest = parse_body(&body)?;
+    let inputs: Vec<&str> = match &request.input {
+        Input::One(input) => vec![input],
+        Input::Many(inputs) => inputs.iter().map(String::as_str).collect(),
+    };
+    // The official SDKs ask for base64 by default: little-endian `f32`s.
+    let base64 = match request.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(format) => {
+            let message = format!("unsupported encoding format: {format}");
+            return Err(ApiError::new(StatusCode::BAD_REQUEST, message));
+        }
+    };
+
+    let response = state
+        .ollama
+        .generate_embeddings(&request.model)
+        .inputs(inputs)
+        .await?;
+
+    let data: Vec<Value> = response
+        .embeddings
+        .iter()
+        .enumerate()
+        .map(|(index, embedding)| {
+            let embedding = match base64 {
+                true => {
+                    let bytes: Vec<u8> = embedding
+                        .iter()
+                        .flat_map(|x| (*x as f32).to_le_bytes())
+                        .collect();
+                    json!(STANDARD.encode(bytes))
+                }
+                false => json!(embedding),
+            };
+            json!({ "object": "embedding", "index": index, "embedding": embedding })
+        })
+        .collect();
+
+    let prompt_tokens = response.usage.prompt_eval_count.unwrap_or_default();
+    Ok(Json(json!({
+        "object": "list",
+        "data": data,
+        "model": response.model,
+        "usage": { "prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens },
+    }))
+    .into_response())
+}
+
+async fn models(State(state): State<Arc<ServerState>>) -> Result<Response, ApiError> {
+    let models = state.ollama.list_local_models().await?.models;
+    let data: Vec<Value> = models
+        .into_iter()
+        .map(|model| {
+            let created = chrono::DateTime::parse_from_rfc3339(&model.modified_at)
+                .map_or(0, |t| t.timestamp());
+            let owned_by = model
+                .name
+                .parse::<ModelName>()
+                .map_or_else(|_| "library".to_string(), |name| name.namespace);
+            json!({ "id": model.name, "object": "model", "created": created, "owned_by": owned_by })
+        })
+        .collect();
+
+    Ok(Json(json!({ "object": "list", "data": data })).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde_json::{Value, json};
+    use tokio_stream::StreamExt;
+
+    use super::Server;
+    use crate::action::IntoStream;
+    use crate::config::WireFormat;
+    use crate::mock::MockOllama;
+    use crate::{Ollama, error::OllamaError};
+
+    /// Serve `server` on a free port and return its URL.
+    async fn start(server: Server) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = server.router();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn openai_client_should_round_trip_through_server() {
+        let mock = MockOllama::start().await;
+        let url = start(Server::new(mock.ollama())).await;
+        let ollama = Ollama::new(&url).wire_format(WireFormat::OpenAi);
+
+        let response = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .temperature(0.5)
+            .await
+            .unwrap();
+        assert_eq!(response.message.unwrap().content, "echo: hello there");
+        assert_eq!(response.done_reason.as_deref(), Some("stop"));
+        assert_eq!(response.usage.prompt_eval_count, Some(10));
+        assert_eq!(response.usage.eval_count, Some(3));
+        assert_eq!(
+            mock.requests("/api/chat")[0]["options"],
+            json!({ "temperature": 0.5 })
+        );
+
+        let chunks: Vec<_> = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .stream()
+            .await
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        let content: String = chunks
+            .iter()
+            .map(|c| c.message.as_ref().unwrap().content.as_str())
+            .collect();
+        assert_eq!(content, "echo: hello there");
+        let last = chunks.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.usage.eval_count, Some(3));
+
+        let embeddings = ollama
+            .generate_embeddings("all-minilm")
+            .inputs(vec!["a b", "c"])
+            .await
+            .unwrap();
+        assert_eq!(
+            embeddings.embeddings,
+            vec![vec![3.0, 2.0, 1.0], vec![1.0, 1.0, 1.0]]
+        );
+
+        let models = ollama.list_local_models().await.unwrap().models;
+        assert_eq!(models[1].name, "llama3.1:8b");
+        assert_eq!(models[1].modified_at, "2025-01-01T00:00:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn chat_should_translate_openai_messages() {
+        let mock = MockOllama::start().await;
+        let url = start(Server::new(mock.ollama())).await;
+
+        let body = json!({
+            "model": "llama3.1:8b",
+            "messages": [
+                { "role": "user", "content": [
+                    { "type": "text", "text": "what is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } },
+                ] },
+                { "role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_0",
+                    "type": "function",
+                    "function": { "name": "look", "arguments": "{\"zoom\":2}" },
+                }] },
+                { "role": "tool", "content": "a cat" },
+            ],
+            "tools": [{ "type": "function", "function": { "name": "look", "parameters": {} } }],
+            "response_format": { "type": "json_schema", "json_schema": { "name": "r", "schema": { "type": "object" } } },
+            "stop": ["\n\n"],
+            "max_tokens": 32,
+        });
+        let response: Value = reqwest::Client::new()
+            .post(format!("{url}/v1/chat/completions"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["object"], "chat.completion");
+        assert_eq!(response["choices"][0]["finish_reason"], "stop");
+        assert_eq!(response["usage"]["total_tokens"], 14);
+
+        let forwarded = &mock.requests("/api/chat")[0];
+        let messages = &forwarded["messages"];
+        assert_eq!(messages[0]["content"], "what is this?");
+        assert_eq!(messages[0]["images"], json!(["iVBORw0KGgo="]));
+        assert_eq!(
+            messages[1]["tool_calls"],
+            json!([{ "function": { "name": "look", "arguments": { "zoom": 2 } } }])
+        );
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(forwarded["tools"][0]["function"]["name"], "look");
+        assert_eq!(forwarded["format"], json!({ "type": "object" }));
+        assert_eq!(
+            forwarded["options"],
+            json!({ "stop": "\n\n", "num_predict": 32 })
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_should_reject_unsupported_requests() {
+        let mock = MockOllama::start().await;
+        let url = start(Server::new(mock.ollama())).await;
+
+        let image = |url: &str| {
+            json!({
+                "model": "llama3.1:8b",
+                "messages": [{ "role": "user", "content": [
+                    { "type": "image_url", "image_url": { "url": url } },
+                ] }],
+            })
+        };
+        let stops = json!({
+            "model": "llama3.1:8b",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "stop": ["\n\n", "END"],
+        });
+        let schemaless = json!({
+            "model": "llama3.1:8b",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "response_format": { "type": "json_schema", "json_schema": { "name": "r" } },
+        });
+        for body in [
+            image("http://169.254.169.254/latest/meta-data"),
+            image("/etc/passwd"),
+            stops,
+            schemaless,
+        ] {
+            let response = reqwest::Client::new()
+                .post(format!("{url}/v1/chat/completions"))
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400);
+            let error: Value = response.json().await.unwrap();
+            assert_eq!(error["error"]["type"], "invalid_request_error");
+        }
+        assert!(mock.requests("/api/chat").is_empty());
+    }
+
+    #[tokio::test]
+    async fn embeddings_should_encode_base64() {
+        let mock = MockOllama::start().await;
+        let url = start(Server::new(mock.ollama())).await;
+
+        let response: Value = reqwest::Client::new()
+            .post(format!("{url}/v1/embeddings"))
+            .json(&json!({ "model": "all-minilm", "input": "a b", "encoding_format": "base64" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        let expected: Vec<u8> = [3.0f32, 2.0, 1.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        assert_eq!(response["data"][0]["embedding"], STANDARD.encode(expected));
+        assert_eq!(response["usage"]["prompt_tokens"], 1);
+    }
+
+    #[tokio::test]
+    async fn server_should_check_api_key_and_forward_errors() {
+        let mock = MockOllama::start().await;
+        let url = start(Server::new(mock.ollama()).api_key("secret")).await;
+
+        let ollama = Ollama::new(&url).wire_format(WireFormat::OpenAi);
+        let error = ollama.list_local_models().await.unwrap_err();
+        assert!(matches!(error, OllamaError::OllamaServerError(m) if m == "invalid API key"));
+
+        for key in ["secreT", "secret2", ""] {
+            let response = reqwest::Client::new()
+                .get(format!("{url}/v1/models"))
+                .bearer_auth(key)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 401);
+        }
+
+        mock.fail_next(1);
+        let response = reqwest::Client::new()
+            .post(format!("{url}/v1/chat/completions"))
+            .bearer_auth("secret")
+            .json(&json!({ "model": "llama3.1:8b", "messages": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["message"], "mock failure");
+        assert_eq!(body["error"]["type"], "api_error");
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    Ollama,
+    abi::{Message, Options, Role},
+    error::OllamaError,
+};
+
+pub use crate::tokens::estimate_tokens;
+
+/// The context window Ollama uses when `num_ctx` is not set.
+pub const DEFAULT_NUM_CTX: usize = 2048;
+
+/// Tokens taken by the chat template around each message.
+const MESSAGE_OVERHEAD: usize = 4;
+
+const SUMMARY_PROMPT: &str = "Summarize the following conversation in a few sentences. \
+Keep names, facts, decisions and open questions. Reply with the summary only.";
+
+/// Starts the system message holding the summary, which is how an earlier summary is found.
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation: ";
+
+/// Estimated number of tokens a message takes in the context window. Images are not counted.
+pub fn estimate_message_tokens(message: &Message) -> usize {
+    let thinking = message.thinking.as_deref().map_or(0, estimate_tokens);
+    let tool_calls = message.tool_calls.as_ref().map_or(0, |calls| {
+        calls
+            .iter()
+            .map(|call| estimate_tokens(&call.to_string()))
+            .sum()
+    });
+    MESSAGE_OVERHEAD + estimate_tokens(&message.content) + thinking + tool_calls
+}
+
+fn estimate_all(messages: &[Message]) -> usize {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+/// What to do when the history does not fit in the budget. The leading system messages
+/// and the last turn are always kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TruncationStrategy {
+    /// Drop the oldest turns until the history fits.
+    #[default]
+    DropOldest,
+
+    /// Keep at most the last `turns` turns, dropping more if they still do not fit.
+    SlidingWindow { turns: usize },
+
+    /// Replace the older turns with a summary written by `model`, keeping the last
+    /// `keep_turns` turns verbatim. Only [`ContextManager::apply`] summarizes,
+    /// [`ContextManager::truncate`] drops the older turns instead.
+    Summarize { model: String, keep_turns: usize },
+}
+
+/// The messages to send once the strategy has been applied, and what was left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Truncation {
+    pub messages: Vec<Message>,
+
+    /// The messages dropped or summarized, in their original order.
+    pub dropped: Vec<Message>,
+
+    /// The summary of the dropped messages, for [`TruncationStrategy::Summarize`].
+    pub summary: Option<String>,
+
+    /// Estimated number of tokens of `messages`.
+    pub tokens: usize,
+
+    /// `false` if the messages are still over budget, e.g. because the last turn alone is.
+    pub fits: bool,
+}
+
+impl Truncation {
+    pub fn is_truncated(&self) -> bool {
+        !self.dropped.is_empty()
+    }
+}
+
+/// Keeps a chat history within a token budget, pinning the system prompt.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::session::{ContextManager, TruncationStrategy};
+///
+/// let manager = ContextManager::from_num_ctx(8192)
+///     .reserve_tokens(512)
+///     .strategy(TruncationStrategy::SlidingWindow { turns: 20 });
+///
+/// let truncation = manager.truncate(&history);
+/// if truncation.is_truncated() {
+///     println!("dropped {} messages", truncation.dropped.len());
+/// }
+/// let resp = ollama.chat("llama3.1:8b").messages(truncation.messages).await?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextManager {
+    budget: usize,
+    reserve: usize,
+    strategy: TruncationStrategy,
+}
+
+impl ContextManager {
+    /// Creates a manager keeping the history within `budget` estimated tokens.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            reserve: 0,
+            strategy: TruncationStrategy::default(),
+        }
+    }
+
+    /// Creates a manager for a context window of `num_ctx` tokens.
+    pub fn from_num_ctx(num_ctx: usize) -> Self {
+        Self::new(num_ctx)
+    }
+
+    /// Creates a manager for the `num_ctx` of the options, or [`DEFAULT_NUM_CTX`] if unset.
+    pub fn from_options(options: &Options) -> Self {
+        let num_ctx = options
+            .num_ctx
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(DEFAULT_NUM_CTX);
+        Self::from_num_ctx(num_ctx)
+    }
+
+    /// Creates a manager for the full context length of the model, as reported by `model_info`.
+    /// Only use it when requests set `num_ctx` to that length, the server uses
+    /// [`DEFAULT_NUM_CTX`] otherwise.
+    #[cfg(feature = "model")]
+    pub async fn for_model(ollama: &Ollama, model: &str) -> Result<Self, OllamaError> {
+        let info = ollama.show_model_information(model).await?;
+        let context_length = info.context_length().ok_or_else(|| {
+            OllamaError::InvalidFormat(format!("no context length in model info of {model}"))
+        })?;
+        Ok(Self::from_num_ctx(context_length as usize))
+    }
+
+    /// Tokens kept free for the reply.
+    #[inline]
+    pub fn reserve_tokens(mut self, reserve: usize) -> Self {
+        self.reserve = reserve;
+        self
+    }
+
+    #[inline]
+    pub fn strategy(mut self, strategy: TruncationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// The number of tokens available for the messages, once the reserve is taken out.
+    pub fn available(&self) -> usize {
+        self.budget.saturating_sub(self.reserve)
+    }
+
+    /// Apply the strategy without calling any model, a summarizing strategy drops the
+    /// turns it would have summarized.
+    pub fn truncate(&self, messages: &[Message]) -> Truncation {
+        let (pinned, turns) = split_turns(messages);
+        let start = match &self.strategy {
+            TruncationStrategy::DropOldest => 0,
+            TruncationStrategy::SlidingWindow { turns: window } => {
+                turns.len().saturating_sub((*window).max(1))
+            }
+            TruncationStrategy::Summarize { .. } => 0,
+        };
+        self.drop_oldest(pinned, &turns, start, turns[..start].concat())
+    }
+
+    /// Apply the strategy, summarizing the older turns with a model call for
+    /// [`TruncationStrategy::Summarize`]. Nothing is summarized if the history fits.
+    pub async fn apply(
+        &self,
+        ollama: &Ollama,
+        messages: &[Message],
+    ) -> Result<Truncation, OllamaError> {
+        let TruncationStrategy::Summarize { model, keep_turns } = &self.strategy else {
+            return Ok(self.truncate(messages));
+        };
+        if estimate_all(messages) <= self.available() {
+            return Ok(self.truncate(messages));
+        }
+
+        let (pinned, turns) = split_turns(messages);
+        let keep = turns.len().min((*keep_turns).max(1));
+        let older_turns: Vec<Message> = turns[..turns.len() - keep].concat();
+        if older_turns.is_empty() {
+            return Ok(self.truncate(messages));
+        }
+
+        // An earlier summary is summarized again along with the older turns, so that a
+        // compacted history keeps a single summary.
+        let (summaries, mut summarized): (Vec<Message>, Vec<Message>) = pinned
+            .iter()
+            .cloned()
+            .partition(|m| m.role == Role::System && m.content.starts_with(SUMMARY_PREFIX));
+        let older = [summaries, older_turns].concat();
+
+        let summary = summarize(ollama, model, &older).await?;
+        summarized.push(Message::system(&format!("{SUMMARY_PREFIX}{summary}")));
+
+        let mut truncation = self.drop_oldest(&summarized, &turns, turns.len() - keep, older);
+        truncation.summary = Some(summary);
+        Ok(truncation)
+    }
+
+    /// Keep `pinned` and the turns from `start`, dropping more of the oldest turns while
+    /// over budget, but never the last one. `dropped` holds what was left out before `start`.
+    fn drop_oldest(
+        &self,
+        pinned: &[Message],
+        turns: &[&[Message]],
+        start: usize,
+        mut dropped: Vec<Message>,
+    ) -> Truncation {
+        let available = self.available();
+        let turn_tokens: Vec<usize> = turns.iter().map(|t| estimate_all(t)).collect();
+
+        let mut end = start;
+        let mut tokens = estimate_all(pinned) + turn_tokens[start..].iter().sum::<usize>();
+        while tokens > available && end + 1 < turns.len() {
+            tokens -= turn_tokens[end];
+            end += 1;
+        }
+        dropped.extend(turns[start..end].concat());
+
+        let mut messages = pinned.to_vec();
+        messages.extend(turns[end..].concat());
+        Truncation {
+            messages,
+            dropped,
+            summary: None,
+            tokens,
+            fits: tokens <= available,
+        }
+    }
+}
+
+/// Split messages into the leading system messages and turns, each turn starting with a
+/// user message. Messages before the first user message form a turn of their own.
+fn split_turns(messages: &[Message]) -> (&[Message], Vec<&[Message]>) {
+    let pinned = messages
+        .iter()
+        .position(|m| m.role != Role::System)
+        .unwrap_or(messages.len());
+    let (pinned, rest) = messages.split_at(pinned);
+
+    let mut turns = vec![];
+    let mut start = 0;
+    for (i, message) in rest.iter().enumerate() {
+        if message.role == Role::User && i > start {
+            turns.push(&rest[start..i]);
+            start = i;
+        }
+    }
+    if start < rest.len() {
+        turns.push(&rest[start..]);
+    }
+    (pinned, turns)
+}
+
+async fn summarize(
+    ollama: &Ollama,
+    model: &str,
+    messages: &[Message],
+) -> Result<String, OllamaError> {
+    let transcript = messages
+        .iter()
+        .map(|m| {
+            let role = serde_json::to_value(&m.role).unwrap_or_default();
+            format!("{}: {}", role.as_str().unwrap_or_default(), m.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let response = ollama
+        .chat(model)
+        .system_message(SUMMARY_PROMPT)
+        .user_message(&transcript)
+        .await?;
+    Ok(response.message.map(|m| m.content).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContextManager, TruncationStrategy, estimate_message_tokens};
+    use crate::{
+        abi::{Message, Options},
+        mock::MockOllama,
+    };
+
+    /// A system prompt followed by four turns, each message taking 4 + 5 = 9 tokens.
+    fn history() -> Vec<Message> {
+        let mut messages = vec![Message::system("system prompt text..")];
+        for i in 0..4 {
+            messages.push(Message::user(&format!("question {i} here..")));
+            messages.push(Message::assistant(&format!("answer {i} is here..")));
+        }
+        messages
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn estimate_should_work() {
+        assert_eq!(estimate_message_tokens(&Message::user("")), 4);
+        assert_eq!(estimate_message_tokens(&Message::user("12345")), 6);
+        assert_eq!(
+            ContextManager::from_options(&Options::default()).available(),
+            2048
+        );
+    }
+
+    #[test]
+    fn drop_oldest_should_pin_system_prompt() {
+        let history = history();
+        assert!(history.iter().all(|m| estimate_message_tokens(m) == 9));
+
+        let truncation = ContextManager::new(1000).truncate(&history);
+        assert!(!truncation.is_truncated());
+        assert_eq!(truncation.tokens, 81);
+
+        // Room for the system prompt and two turns.
+        let truncation = ContextManager::new(50).reserve_tokens(5).truncate(&history);
+        assert!(truncation.fits);
+        assert_eq!(truncation.tokens, 45);
+        assert_eq!(
+            contents(&truncation.messages),
+            vec![
+                "system prompt text..",
+                "question 2 here..",
+                "answer 2 is here..",
+                "question 3 here..",
+                "answer 3 is here.."
+            ]
+        );
+        assert_eq!(truncation.dropped.len(), 4);
+        assert_eq!(truncation.dropped[0].content, "question 0 here..");
+
+        // The last turn is kept even if it does not fit.
+        let truncation = ContextManager::new(10).truncate(&history);
+        assert!(!truncation.fits);
+        assert_eq!(truncation.messages.len(), 3);
+    }
+
+    #[test]
+    fn sliding_window_should_keep_last_turns() {
+        let truncation = ContextManager::new(1000)
+            .strategy(TruncationStrategy::SlidingWindow { turns: 1 })
+            .truncate(&history());
+        assert_eq!(
+            contents(&truncation.messages),
+            vec![
+                "system prompt text..",
+                "question 3 here..",
+                "answer 3 is here.."
+            ]
+        );
+        assert_eq!(truncation.dropped.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn summarize_should_replace_older_turns() {
+        let mock = MockOllama::start().await;
+        let manager = ContextManager::new(50).strategy(TruncationStrategy::Summarize {
+            model: "llama3.1:8b".to_string(),
+            keep_turns: 1,
+        });
+
+        let truncation = manager.apply(&mock.ollama(), &history()).await.unwrap();
+        assert_eq!(truncation.dropped.len(), 6);
+        assert_eq!(truncation.messages.len(), 4);
+        assert_eq!(truncation.messages[0].content, "system prompt text..");
+        assert!(
+            truncation.messages[1]
+                .content
+                .starts_with("Summary of the earlier conversation: echo: user: question 0")
+        );
+        assert!(
+            truncation
+                .summary
+                .unwrap()
+                .contains("assistant: answer 2 is here..")
+        );
+
+        // Nothing is summarized when the history fits.
+        let manager = manager.clone();
+        let fits = ContextManager::new(1000).strategy(manager.strategy.clone());
+        let truncation = fits.apply(&mock.ollama(), &history()).await.unwrap();
+        assert!(!truncation.is_truncated());
+        assert_eq!(mock.requests("/api/chat").len(), 1);
+    }
+}
@@ -0,0 +1,524 @@
+//! Chat sessions that keep their own history.
+//!
+//! A [`Conversation`] is plain data: it holds the model, system prompt, options and
+//! messages, and is given the [`Ollama`] client on each call, so it can be saved, loaded,
+//! cloned and forked freely.
+//!
+//! A [`ContextManager`] keeps long conversations within the model's context window.
+
+pub mod context;
+
+pub use context::{ContextManager, Truncation, TruncationStrategy};
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Ollama,
+    abi::{
+        ImageOptions, KeepAlive, Message, Options, Role, completion::chat::ChatCompletionResponse,
+        image::resolve_images,
+    },
+    action::completion::chat::ChatAction,
+    error::OllamaError,
+};
+
+#[cfg(feature = "stream")]
+use {
+    crate::action::IntoStream, async_stream::stream, futures::Stream, std::pin::Pin,
+    tokio_stream::StreamExt,
+};
+
+/// A stream of reply chunks borrowing the conversation it will append the reply to.
+#[cfg(feature = "stream")]
+pub type ConversationStream<'c> =
+    Pin<Box<dyn Stream<Item = Result<ChatCompletionResponse, OllamaError>> + 'c>>;
+
+/// A chat with a model, along with its history.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::session::Conversation;
+///
+/// let mut conversation = Conversation::new("llama3.1:8b").system_prompt("You are a pirate.");
+/// conversation.send(&ollama, "Why is the sky blue?").await?;
+/// conversation.send(&ollama, "Say it shorter").await?;
+///
+/// // Try another answer to the last question.
+/// conversation.undo();
+/// conversation.send(&ollama, "Say it in one word").await?;
+///
+/// conversation.save("pirate.json")?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub model: String,
+
+    /// Sent as the first message of every request, not stored in `messages`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    /// The user and assistant messages exchanged so far.
+    #[serde(default)]
+    pub messages: Vec<Message>,
+
+    #[serde(default, skip_serializing_if = "Options::is_default")]
+    pub options: Options,
+
+    #[serde(default, skip_serializing_if = "KeepAlive::is_default")]
+    pub keep_alive: KeepAlive,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+
+    /// Trims the messages of each request to fit the context window, the history itself
+    /// is kept whole. Not saved.
+    #[serde(skip)]
+    pub context: Option<ContextManager>,
+
+    #[serde(skip)]
+    last_truncation: Option<Truncation>,
+}
+
+impl Conversation {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn system_prompt(mut self, system: &str) -> Self {
+        self.system = Some(system.to_string());
+        self
+    }
+
+    /// Model parameters sent with every request.
+    #[inline]
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[inline]
+    pub fn keep_alive(mut self, keep_alive: impl Into<KeepAlive>) -> Self {
+        self.keep_alive = keep_alive.into();
+        self
+    }
+
+    #[inline]
+    pub fn think(mut self, think: bool) -> Self {
+        self.think = Some(think);
+        self
+    }
+
+    /// Fit the messages of each request in the context window, see [`ContextManager`].
+    #[inline]
+    pub fn context_manager(mut self, manager: ContextManager) -> Self {
+        self.context = Some(manager);
+        self
+    }
+
+    /// What the context manager left out of the last request, if a manager is set.
+    pub fn last_truncation(&self) -> Option<&Truncation> {
+        self.last_truncation.as_ref()
+    }
+
+    /// The messages sent to the model: the system prompt followed by the history.
+    pub fn request_messages(&self) -> Vec<Message> {
+        self.system
+            .as_deref()
+            .map(Message::system)
+            .into_iter()
+            .chain(self.messages.iter().cloned())
+            .collect()
+    }
+
+    /// The last assistant message, if any.
+    pub fn last_reply(&self) -> Option<&Message> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::Assistant)
+    }
+
+    /// Number of user messages in the history.
+    pub fn turns(&self) -> usize {
+        self.messages
+            .iter()
+            .filter(|m| m.role == Role::User)
+            .count()
+    }
+
+    /// Send a user message and append it along with the reply to the history.
+    /// The history is left untouched if the request fails.
+    pub async fn send(
+        &mut self,
+        ollama: &Ollama,
+        content: &str,
+    ) -> Result<ChatCompletionResponse, OllamaError> {
+        self.send_message(ollama, Message::user(content)).await
+    }
+
+    /// Send a message, e.g. a user message with images or a tool result, and append it
+    /// along with the reply to the history.
+    pub async fn send_message(
+        &mut self,
+        ollama: &Ollama,
+        mut message: Message,
+    ) -> Result<ChatCompletionResponse, OllamaError> {
+        resolve_message_images(ollama, &mut message).await?;
+        let messages = self.prepare(ollama, &message).await?;
+        let response = self.chat(ollama, messages).await?;
+
+        self.messages.push(message);
+        if let Some(reply) = &response.message {
+            self.messages.push(reply.clone());
+        }
+        Ok(response)
+    }
+
+    /// Send a user message and stream the reply. The message and the reply are appended
+    /// to the history once the last chunk has been received, so dropping the stream early
+    /// leaves the history untouched.
+    #[cfg(feature = "stream")]
+    pub async fn send_stream<'c>(
+        &'c mut self,
+        ollama: &Ollama,
+        content: &str,
+    ) -> Result<ConversationStream<'c>, OllamaError> {
+        self.send_message_stream(ollama, Message::user(content))
+            .await
+    }
+
+    /// Send a message and stream the reply, see [`Conversation::send_stream`].
+    #[cfg(feature = "stream")]
+    pub async fn send_message_stream<'c>(
+        &'c mut self,
+        ollama: &Ollama,
+        mut message: Message,
+    ) -> Result<ConversationStream<'c>, OllamaError> {
+        resolve_message_images(ollama, &mut message).await?;
+        let messages = self.prepare(ollama, &message).await?;
+        let mut chunks = self.chat(ollama, messages).stream().await?;
+
+        let s = stream! {
+            let mut reply = Message::assistant("");
+            while let Some(chunk) = chunks.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+
+                if let Some(delta) = &chunk.message {
+                    reply.content.push_str(&delta.content);
+                    if let Some(thinking) = &delta.thinking {
+                        reply.thinking.get_or_insert_default().push_str(thinking);
+                    }
+                    if let Some(tool_calls) = &delta.tool_calls {
+                        reply.tool_calls.get_or_insert_default().extend(tool_calls.iter().cloned());
+                    }
+                }
+
+                if chunk.done {
+                    self.messages.push(message.clone());
+                    self.messages.push(reply.clone());
+                }
+                yield Ok(chunk);
+            }
+        };
+
+        Ok(Box::pin(s))
+    }
+
+    /// Remove the last turn: the last user message and everything after it.
+    /// Returns the removed messages, empty if there was no user message.
+    pub fn undo(&mut self) -> Vec<Message> {
+        match self.messages.iter().rposition(|m| m.role == Role::User) {
+            Some(position) => self.messages.split_off(position),
+            None => vec![],
+        }
+    }
+
+    /// A copy of the conversation to continue independently.
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// A copy of the conversation keeping only its first `turns` turns, to explore
+    /// another continuation from that point.
+    pub fn branch(&self, turns: usize) -> Self {
+        let end = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.role == Role::User)
+            .nth(turns)
+            .map_or(self.messages.len(), |(i, _)| i);
+
+        Self {
+            messages: self.messages[..end].to_vec(),
+            ..self.clone()
+        }
+    }
+
+    /// Replace the history with what the context manager keeps of it, making a summary
+    /// permanent instead of writing it again on every request. Does nothing without a manager.
+    pub async fn compact(&mut self, ollama: &Ollama) -> Result<Option<Truncation>, OllamaError> {
+        let Some(manager) = &self.context else {
+            return Ok(None);
+        };
+
+        let truncation = manager.apply(ollama, &self.request_messages()).await?;
+        let pinned = usize::from(self.system.is_some());
+        self.messages = truncation.messages[pinned..].to_vec();
+        Ok(Some(truncation))
+    }
+
+    /// Save the conversation as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), OllamaError> {
+        let serialized = serde_json::to_vec_pretty(self)
+            .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+        std::fs::write(path, serialized).map_err(OllamaError::FileError)
+    }
+
+    /// Load a conversation saved with [`Conversation::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, OllamaError> {
+        let serialized = std::fs::read(path).map_err(OllamaError::FileError)?;
+        serde_json::from_slice(&serialized).map_err(|e| OllamaError::InvalidFormat(e.to_string()))
+    }
+
+    /// The messages to send along with `message`, trimmed by the context manager if any.
+    async fn prepare(
+        &mut self,
+        ollama: &Ollama,
+        message: &Message,
+    ) -> Result<Vec<Message>, OllamaError> {
+        let mut messages = self.request_messages();
+        messages.push(message.clone());
+        let Some(manager) = &self.context else {
+            return Ok(messages);
+        };
+
+        let truncation = manager.apply(ollama, &messages).await?;
+        let messages = truncation.messages.clone();
+        self.last_truncation = Some(truncation);
+        Ok(messages)
+    }
+
+    fn chat<'a>(
+        &'a self,
+        ollama: &Ollama,
+        messages: Vec<Message>,
+    ) -> ChatAction<'a, ChatCompletionResponse> {
+        let mut action = ollama
+            .chat(&self.model)
+            .messages(messages)
+            .options(self.options.clone())
+            .keep_alive(self.keep_alive);
+        if let Some(think) = self.think {
+            action = action.think(think);
+        }
+        action
+    }
+}
+
+/// Images are stored resolved so the conversation can be saved.
+async fn resolve_message_images(ollama: &Ollama, message: &mut Message) -> Result<(), OllamaError> {
+    if let Some(images) = message.images.as_mut() {
+        resolve_images(&ollama.client().cli, images, &ImageOptions::default()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContextManager, Conversation, TruncationStrategy};
+    use crate::{
+        abi::{Message, Role},
+        mock::MockOllama,
+    };
+
+    fn conversation() -> Conversation {
+        let mut conversation = Conversation::new("llama3.1:8b").system_prompt("Be brief.");
+        for (question, answer) in [("one", "1"), ("two", "2"), ("three", "3")] {
+            conversation.messages.push(Message::user(question));
+            conversation.messages.push(Message::assistant(answer));
+        }
+        conversation
+    }
+
+    #[tokio::test]
+    async fn send_should_append_reply() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let mut conversation = Conversation::new("llama3.1:8b")
+            .system_prompt("Be brief.")
+            .think(false);
+
+        conversation.send(&ollama, "hello").await.unwrap();
+        conversation.send(&ollama, "again").await.unwrap();
+
+        assert_eq!(conversation.turns(), 2);
+        assert_eq!(conversation.last_reply().unwrap().content, "echo: again");
+
+        let requests = mock.requests("/api/chat");
+        let messages = requests[1]["messages"].as_array().unwrap();
+        let contents: Vec<&str> = messages
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(contents, vec!["Be brief.", "hello", "echo: hello", "again"]);
+        assert_eq!(requests[1]["think"], false);
+
+        // A failed request leaves the history untouched.
+        mock.fail_next(1);
+        assert!(conversation.send(&ollama, "fails").await.is_err());
+        assert_eq!(conversation.messages.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn send_stream_should_append_reply() {
+        use crate::action::IntoStream;
+        use futures::StreamExt;
+
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let mut conversation = Conversation::new("llama3.1:8b");
+
+        let mut stream = conversation.send_stream(&ollama, "hi there").await.unwrap();
+        let mut chunks = 0;
+        while let Some(chunk) = stream.next().await {
+            chunk.unwrap();
+            chunks += 1;
+        }
+        drop(stream);
+
+        assert_eq!(chunks, 4);
+        assert_eq!(conversation.messages.len(), 2);
+        assert_eq!(conversation.last_reply().unwrap().content, "echo: hi there");
+
+        // A stream dropped before the end leaves the history untouched.
+        let mut stream = conversation.send_stream(&ollama, "hello").await.unwrap();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+        assert_eq!(conversation.messages.len(), 2);
+
+        // The chat stream itself decodes several lines per network chunk.
+        let chunks: Vec<_> = ollama
+            .chat("llama3.1:8b")
+            .user_message("a b c")
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 5);
+        assert!(chunks.iter().all(|c| c.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn send_should_fit_context_window() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let mut conversation = conversation().context_manager(ContextManager::new(25));
+
+        conversation.send(&ollama, "four").await.unwrap();
+        let requests = mock.requests("/api/chat");
+        let contents: Vec<&str> = requests[0]["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(contents, vec!["Be brief.", "three", "3", "four"]);
+
+        let truncation = conversation.last_truncation().unwrap();
+        assert_eq!(truncation.dropped.len(), 4);
+        assert_eq!(conversation.turns(), 4);
+
+        // Compacting keeps what the last request would have sent.
+        let truncation = conversation.compact(&ollama).await.unwrap().unwrap();
+        assert_eq!(truncation.dropped.len(), 6);
+        assert_eq!(conversation.turns(), 1);
+        assert_eq!(conversation.system.as_deref(), Some("Be brief."));
+    }
+
+    #[tokio::test]
+    async fn compact_should_replace_the_previous_summary() {
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+        let manager = ContextManager::new(25).strategy(TruncationStrategy::Summarize {
+            model: "llama3.1:8b".to_string(),
+            keep_turns: 1,
+        });
+        let mut conversation = conversation().context_manager(manager);
+
+        conversation.compact(&ollama).await.unwrap();
+        conversation.send(&ollama, "four").await.unwrap();
+        let truncation = conversation.compact(&ollama).await.unwrap().unwrap();
+        assert!(
+            truncation
+                .summary
+                .unwrap()
+                .contains("Summary of the earlier")
+        );
+
+        let summaries: Vec<&Message> = conversation
+            .messages
+            .iter()
+            .filter(|m| m.role == Role::System)
+            .collect();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(conversation.turns(), 1);
+        assert_eq!(conversation.system.as_deref(), Some("Be brief."));
+    }
+
+    #[test]
+    fn undo_should_remove_last_turn() {
+        let mut conversation = conversation();
+        let removed = conversation.undo();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].content, "three");
+        assert_eq!(conversation.turns(), 2);
+        assert_eq!(conversation.last_reply().unwrap().content, "2");
+
+        conversation.messages.truncate(0);
+        assert!(conversation.undo().is_empty());
+    }
+
+    #[test]
+    fn branch_should_keep_first_turns() {
+        let conversation = conversation();
+        let branch = conversation.branch(1);
+        assert_eq!(branch.turns(), 1);
+        assert_eq!(branch.messages.len(), 2);
+        assert_eq!(branch.system.as_deref(), Some("Be brief."));
+        assert_eq!(conversation.branch(10), conversation);
+        assert!(conversation.branch(0).messages.is_empty());
+
+        let mut fork = conversation.fork();
+        fork.undo();
+        assert_eq!(conversation.turns(), 3);
+    }
+
+    #[test]
+    fn save_and_load_should_work() {
+        let mut conversation = conversation();
+        conversation.options.temperature(0.2);
+        conversation.messages[0] = Message::user("one").image("aGVsbG8=");
+
+        let path = std::env::temp_dir().join("ollama-native-conversation.json");
+        conversation.save(&path).unwrap();
+        let loaded = Conversation::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, conversation);
+        assert_eq!(loaded.request_messages()[0].role, Role::System);
+    }
+}
@@ -0,0 +1,513 @@
+//! Splitting long texts into chunks that fit the context window of a model.
+//!
+//! Texts are split recursively, first by the coarsest separators (Markdown headings,
+//! paragraphs), then by lines, sentences and words, and as a last resort by characters.
+//! The resulting pieces are merged back into chunks as large as the budget allows, each
+//! chunk ending on the coarsest boundary that keeps it within the budget.
+
+use std::ops::Range;
+
+use crate::{Ollama, error::OllamaError};
+
+#[cfg(feature = "embeddings")]
+use crate::embeddings::Document;
+
+pub use crate::tokens::CHARS_PER_TOKEN;
+
+/// Controls which boundaries are preferred when splitting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Paragraphs, lines, sentences then words.
+    #[default]
+    Plain,
+
+    /// Headings from `#` to `######` before paragraphs, without splitting fenced code
+    /// blocks unless they do not fit on their own.
+    Markdown,
+
+    /// Blank line runs, lines then words, never sentences.
+    Code,
+}
+
+/// A set of separators tried together. Each pattern comes with the offset of the split
+/// point from the start of the match, so headings start a chunk while whitespace ends one.
+struct Level {
+    patterns: &'static [(&'static str, usize)],
+    /// Ignore matches inside fenced code blocks.
+    respect_fences: bool,
+}
+
+const fn level(patterns: &'static [(&'static str, usize)]) -> Level {
+    Level {
+        patterns,
+        respect_fences: false,
+    }
+}
+
+const fn block(patterns: &'static [(&'static str, usize)]) -> Level {
+    Level {
+        patterns,
+        respect_fences: true,
+    }
+}
+
+const PARAGRAPHS: &[(&str, usize)] = &[("\n\n", 2)];
+const LINES: &[(&str, usize)] = &[("\n", 1)];
+const SENTENCES: &[(&str, usize)] = &[(". ", 2), ("! ", 2), ("? ", 2), ("; ", 2)];
+const WORDS: &[(&str, usize)] = &[(" ", 1)];
+
+const PLAIN: &[Level] = &[
+    level(PARAGRAPHS),
+    level(LINES),
+    level(SENTENCES),
+    level(WORDS),
+];
+
+const MARKDOWN: &[Level] = &[
+    block(&[("\n# ", 1)]),
+    block(&[("\n## ", 1)]),
+    block(&[("\n### ", 1)]),
+    block(&[("\n#### ", 1), ("\n##### ", 1), ("\n###### ", 1)]),
+    block(PARAGRAPHS),
+    level(LINES),
+    level(SENTENCES),
+    level(WORDS),
+];
+
+const CODE: &[Level] = &[
+    level(&[("\n\n\n", 3)]),
+    level(PARAGRAPHS),
+    level(LINES),
+    level(WORDS),
+];
+
+/// A chunk of the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'t> {
+    pub text: &'t str,
+    /// Byte offset of the chunk in the source text.
+    pub start: usize,
+    /// Byte offset of the end of the chunk in the source text.
+    pub end: usize,
+}
+
+/// Splits texts into chunks of a bounded number of characters, optionally overlapping.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::splitter::TextSplitter;
+///
+/// let splitter = TextSplitter::for_model(&ollama, "all-minilm")
+///     .await?
+///     .overlap_tokens(32)
+///     .markdown();
+///
+/// let chunks = splitter.split(&readme);
+/// let resp = ollama
+///     .generate_embeddings("all-minilm")
+///     .inputs(chunks.iter().map(|c| c.text).collect())
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSplitter {
+    chunk_size: usize,
+    overlap: usize,
+    reserve: usize,
+    format: TextFormat,
+}
+
+impl TextSplitter {
+    /// Creates a splitter producing chunks of at most `chunk_size` characters.
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            overlap: 0,
+            reserve: 0,
+            format: TextFormat::Plain,
+        }
+    }
+
+    /// Creates a splitter producing chunks of at most `tokens` estimated tokens.
+    pub fn from_tokens(tokens: usize) -> Self {
+        Self::new(tokens * CHARS_PER_TOKEN)
+    }
+
+    /// Creates a splitter whose budget is the context length of the model, as reported
+    /// by `model_info` in [`show_model_information`][`crate::Ollama::show_model_information`].
+    ///
+    /// # Errors
+    /// - `OllamaError::InvalidFormat`: The model information has no context length.
+    pub async fn for_model(ollama: &Ollama, model: &str) -> Result<Self, OllamaError> {
+        let info = ollama.show_model_information(model).await?;
+        let context_length = info.context_length().ok_or_else(|| {
+            OllamaError::InvalidFormat(format!("no context length in model info of {model}"))
+        })?;
+        Ok(Self::from_tokens(context_length as usize))
+    }
+
+    /// Number of characters repeated from the end of a chunk at the start of the next one.
+    #[inline]
+    pub fn overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Number of estimated tokens repeated from the end of a chunk at the start of the next one.
+    #[inline]
+    pub fn overlap_tokens(self, tokens: usize) -> Self {
+        self.overlap(tokens * CHARS_PER_TOKEN)
+    }
+
+    /// Estimated tokens to leave free in the budget, e.g. for a prompt or the answer.
+    #[inline]
+    pub fn reserve_tokens(mut self, tokens: usize) -> Self {
+        self.reserve = tokens * CHARS_PER_TOKEN;
+        self
+    }
+
+    #[inline]
+    pub fn format(mut self, format: TextFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Prefer Markdown boundaries, see [`TextFormat::Markdown`].
+    #[inline]
+    pub fn markdown(self) -> Self {
+        self.format(TextFormat::Markdown)
+    }
+
+    /// Prefer source code boundaries, see [`TextFormat::Code`].
+    #[inline]
+    pub fn code(self) -> Self {
+        self.format(TextFormat::Code)
+    }
+
+    /// The maximum number of characters of a chunk, once the reserve is taken out.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size.saturating_sub(self.reserve).max(1)
+    }
+
+    /// Split the text into chunks, in order. Whitespace around chunks is trimmed and
+    /// chunks made only of whitespace are dropped.
+    pub fn split<'t>(&self, text: &'t str) -> Vec<Chunk<'t>> {
+        let levels = match self.format {
+            TextFormat::Plain => PLAIN,
+            TextFormat::Markdown => MARKDOWN,
+            TextFormat::Code => CODE,
+        };
+        let fences = match self.format {
+            TextFormat::Markdown => fences(text),
+            _ => vec![],
+        };
+
+        let mut pieces = vec![];
+        let splitter = Splitter {
+            text,
+            fences: &fences,
+            max: self.chunk_size(),
+        };
+        splitter.split(0..text.len(), usize::MAX, levels, &mut pieces);
+        self.merge(text, &pieces)
+    }
+
+    /// Split a document into one document per chunk. Chunks get the id `<id>#<n>` and
+    /// the metadata of the document, plus `source_id`, `chunk`, `start` and `end`.
+    #[cfg(feature = "embeddings")]
+    pub fn split_document(&self, document: &Document) -> Vec<Document> {
+        self.split(&document.text)
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut split = Document::new(format!("{}#{i}", document.id), chunk.text)
+                    .metadata("source_id", document.id.as_str())
+                    .metadata("chunk", i)
+                    .metadata("start", chunk.start)
+                    .metadata("end", chunk.end);
+                for (key, value) in &document.metadata {
+                    split.metadata.entry(key).or_insert_with(|| value.clone());
+                }
+                split
+            })
+            .collect()
+    }
+
+    /// Merge consecutive pieces into chunks. A chunk takes as many pieces as fit, then gives
+    /// back the ones after its coarsest boundary, and the next chunk starts with as many
+    /// trailing pieces of the previous one as the overlap allows.
+    fn merge<'t>(&self, text: &'t str, pieces: &[Piece]) -> Vec<Chunk<'t>> {
+        let max = self.chunk_size();
+        let lengths: Vec<usize> = pieces
+            .iter()
+            .map(|p| char_len(&text[p.range.clone()]))
+            .collect();
+
+        let mut chunks = vec![];
+        let mut i = 0;
+        while i < pieces.len() {
+            let mut j = i + 1;
+            let mut len = lengths[i];
+            while j < pieces.len() && len + lengths[j] <= max {
+                len += lengths[j];
+                j += 1;
+            }
+
+            // The latest of the coarsest boundaries.
+            if j < pieces.len() {
+                j = (i + 1..=j).max_by_key(|&e| pieces[e].rank).unwrap();
+            }
+
+            if let Some(chunk) = trimmed(text, pieces[i].range.start..pieces[j - 1].range.end) {
+                chunks.push(chunk);
+            }
+            if j == pieces.len() {
+                break;
+            }
+
+            // Back up while the overlap and the next piece still fit in a chunk.
+            let mut k = j;
+            let mut overlap = 0;
+            while k > i + 1
+                && overlap + lengths[k - 1] <= self.overlap
+                && overlap + lengths[k - 1] + lengths[j] <= max
+            {
+                overlap += lengths[k - 1];
+                k -= 1;
+            }
+            i = k;
+        }
+        chunks
+    }
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn trimmed(text: &str, range: Range<usize>) -> Option<Chunk<'_>> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start().len());
+    let end = range.end - (slice.len() - slice.trim_end().len());
+    (start < end).then(|| Chunk {
+        text: &text[start..end],
+        start,
+        end,
+    })
+}
+
+/// A run of text that is never split further, along with the rank of the boundary it
+/// starts on: the number of levels left when it was produced, higher is coarser.
+struct Piece {
+    range: Range<usize>,
+    rank: usize,
+}
+
+struct Splitter<'t> {
+    text: &'t str,
+    fences: &'t [Range<usize>],
+    max: usize,
+}
+
+impl Splitter<'_> {
+    /// Split `range` into pieces of at most `max` characters, using the first level that
+    /// has a match and recursing into the finer levels for pieces that are still too long.
+    fn split(&self, range: Range<usize>, rank: usize, levels: &[Level], out: &mut Vec<Piece>) {
+        if char_len(&self.text[range.clone()]) <= self.max {
+            out.push(Piece { range, rank });
+            return;
+        }
+
+        let Some((level, finer)) = levels.split_first() else {
+            self.split_chars(range, rank, out);
+            return;
+        };
+
+        let points = self.split_points(&range, level);
+        if points.is_empty() {
+            self.split(range, rank, finer, out);
+            return;
+        }
+
+        let level_rank = levels.len();
+        let mut start = range.start;
+        let mut start_rank = rank;
+        for point in points.into_iter().chain([range.end]) {
+            self.split(start..point, start_rank, finer, out);
+            start = point;
+            start_rank = level_rank;
+        }
+    }
+
+    fn split_points(&self, range: &Range<usize>, level: &Level) -> Vec<usize> {
+        let mut points: Vec<usize> = level
+            .patterns
+            .iter()
+            .flat_map(|(pattern, offset)| {
+                self.text[range.clone()]
+                    .match_indices(pattern)
+                    .map(move |(i, _)| (range.start + i, range.start + i + offset))
+            })
+            .filter(|(at, _)| !level.respect_fences || !self.fences.iter().any(|f| f.contains(at)))
+            .map(|(_, point)| point)
+            .filter(|point| *point > range.start && *point < range.end)
+            .collect();
+        points.sort_unstable();
+        points.dedup();
+        points
+    }
+
+    fn split_chars(&self, range: Range<usize>, rank: usize, out: &mut Vec<Piece>) {
+        let mut start = range.start;
+        for (count, (i, _)) in self.text[range.clone()].char_indices().enumerate() {
+            if count > 0 && count % self.max == 0 {
+                out.push(Piece {
+                    range: start..range.start + i,
+                    rank,
+                });
+                start = range.start + i;
+            }
+        }
+        out.push(Piece {
+            range: start..range.end,
+            rank,
+        });
+    }
+}
+
+/// Byte ranges of the fenced code blocks (```` ``` ```` or `~~~`) of a Markdown text,
+/// from the opening fence to the closing one. An unclosed fence runs to the end.
+fn fences(text: &str) -> Vec<Range<usize>> {
+    let mut fences = vec![];
+    let mut open = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            match open.take() {
+                // Up to the closing fence, but not the newline after it.
+                Some(start) => fences.push(start..offset + line.trim_end().len()),
+                None => open = Some(offset),
+            }
+        }
+        offset += line.len();
+    }
+    if let Some(start) = open {
+        fences.push(start..text.len());
+    }
+    fences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextSplitter, char_len};
+
+    fn texts(splitter: &TextSplitter, text: &str) -> Vec<String> {
+        let chunks = splitter.split(text);
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+            assert!(char_len(chunk.text) <= splitter.chunk_size(), "{chunk:?}");
+        }
+        chunks.into_iter().map(|c| c.text.to_string()).collect()
+    }
+
+    #[test]
+    fn split_should_prefer_paragraphs() {
+        let text = "Sharks are fish.\n\nTigers are cats. They live in Asia.\n\nEagles fly.";
+
+        let splitter = TextSplitter::new(100);
+        assert_eq!(texts(&splitter, text), vec![text]);
+
+        let splitter = TextSplitter::new(40);
+        assert_eq!(
+            texts(&splitter, text),
+            vec![
+                "Sharks are fish.",
+                "Tigers are cats. They live in Asia.",
+                "Eagles fly."
+            ]
+        );
+
+        // Too long for a paragraph, the second one is split by sentence.
+        let splitter = TextSplitter::new(20);
+        assert_eq!(
+            texts(&splitter, text),
+            vec![
+                "Sharks are fish.",
+                "Tigers are cats.",
+                "They live in Asia.",
+                "Eagles fly."
+            ]
+        );
+    }
+
+    #[test]
+    fn split_should_overlap() {
+        let text = "one two three four five six seven";
+        let splitter = TextSplitter::new(14).overlap(6);
+        assert_eq!(
+            texts(&splitter, text),
+            vec!["one two three", "three four", "four five six", "six seven"]
+        );
+    }
+
+    #[test]
+    fn split_should_fall_back_to_characters() {
+        let text = "ééééééé";
+        let splitter = TextSplitter::new(3);
+        assert_eq!(texts(&splitter, text), vec!["ééé", "ééé", "é"]);
+    }
+
+    #[test]
+    fn split_markdown_should_keep_code_blocks() {
+        let text =
+            "# Title\n\nIntro.\n\n## Usage\n\n```rust\nlet a = 1;\n\nlet b = 2;\n```\n\nDone.";
+        let splitter = TextSplitter::new(50).markdown();
+        assert_eq!(
+            texts(&splitter, text),
+            vec![
+                "# Title\n\nIntro.",
+                "## Usage\n\n```rust\nlet a = 1;\n\nlet b = 2;\n```",
+                "Done."
+            ]
+        );
+
+        // The same text as plain text splits the code block at the blank line.
+        let splitter = TextSplitter::new(50);
+        let chunks = texts(&splitter, text);
+        assert!(chunks.iter().any(|c| c.ends_with("let a = 1;")));
+    }
+
+    #[test]
+    fn reserve_should_shrink_budget() {
+        let splitter = TextSplitter::from_tokens(100).reserve_tokens(25);
+        assert_eq!(splitter.chunk_size(), 300);
+        assert!(TextSplitter::new(10).split("  \n\n ").is_empty());
+    }
+
+    #[cfg(feature = "embeddings")]
+    #[test]
+    fn split_document_should_work() {
+        use crate::embeddings::Document;
+
+        let document = Document::new("doc", "Sharks are fish.\n\nTigers are cats.")
+            .metadata("topic", "animals");
+        let chunks = TextSplitter::new(20).split_document(&document);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].id, "doc#1");
+        assert_eq!(chunks[1].text, "Tigers are cats.");
+        assert_eq!(chunks[1].metadata["source_id"], "doc");
+        assert_eq!(chunks[1].metadata["chunk"], 1);
+        assert_eq!(chunks[1].metadata["start"], 18);
+        assert_eq!(chunks[1].metadata["topic"], "animals");
+    }
+
+    #[tokio::test]
+    async fn for_model_should_use_context_length() {
+        let mock = crate::mock::MockOllama::start().await;
+        let splitter = TextSplitter::for_model(&mock.ollama(), "llama3.1:8b")
+            .await
+            .unwrap();
+        assert_eq!(splitter.chunk_size(), 8192 * 4);
+    }
+}
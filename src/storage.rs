@@ -0,0 +1,85 @@
+//! Storage shared by the response and embedding caches.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A map evicting the least recently used entry once full.
+pub(crate) struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+    /// Keys ordered by last use, oldest first.
+    recency: BTreeMap<u64, K>,
+}
+
+impl<K: Clone + Eq + Hash, V> Lru<K, V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Looks up an entry, making it the most recently used.
+    pub(crate) fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let (value, used) = self.entries.get_mut(key)?;
+        self.recency.remove(used);
+        self.recency.insert(self.tick, key.clone());
+        *used = self.tick;
+        Some(value)
+    }
+
+    /// Inserts or replaces an entry, evicting the least recently used one if full. Returns
+    /// whether the entry was stored, which it is not with a capacity of 0.
+    pub(crate) fn insert(&mut self, key: K, value: V) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+
+        self.remove(&key);
+        if self.entries.len() >= self.capacity
+            && let Some((_, oldest)) = self.recency.pop_first()
+        {
+            self.entries.remove(&oldest);
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+        true
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) {
+        if let Some((_, used)) = self.entries.remove(key) {
+            self.recency.remove(&used);
+        }
+    }
+}
+
+/// Writes a file through a temporary file renamed into place, so a concurrent reader never
+/// sees a partial file. The temporary name is unique to the process and the write, so
+/// concurrent writes of the same file do not clobber each other.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+
+    let dir = path.parent().unwrap();
+    std::fs::create_dir_all(dir)?;
+
+    let name = path.file_name().unwrap().to_string_lossy();
+    let write = WRITES.fetch_add(1, Ordering::Relaxed);
+    let tmp = dir.join(format!("{name}.{}.{write}.tmp", std::process::id()));
+    let result = std::fs::write(&tmp, bytes).and_then(|_| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
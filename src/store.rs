@@ -0,0 +1,641 @@
+//! Reading the model store of a local Ollama installation directly from disk, e.g. while
+//! the server is down.
+//!
+//! The store holds one manifest per model under `manifests/<host>/<namespace>/<model>/<tag>`
+//! listing the digests of its layers, and the layers themselves as content-addressed blobs
+//! under `blobs/`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    abi::model::{ModelInfoDetail, list_local::ModelInfo, name::ModelName},
+    error::OllamaError,
+};
+
+/// The media type of the layer holding the model weights, a GGUF file.
+pub const MODEL_MEDIA_TYPE: &str = "application/vnd.ollama.image.model";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema_version: u32,
+
+    #[serde(default)]
+    pub media_type: String,
+
+    pub config: Layer,
+
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub media_type: String,
+
+    /// The digest of the blob, `sha256:<hex>`.
+    pub digest: String,
+
+    pub size: u64,
+
+    /// The model this layer was created from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+}
+
+impl Manifest {
+    /// The config followed by the layers.
+    pub fn blobs(&self) -> impl Iterator<Item = &Layer> {
+        std::iter::once(&self.config).chain(&self.layers)
+    }
+
+    /// The first layer of the given media type.
+    pub fn layer(&self, media_type: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.media_type == media_type)
+    }
+}
+
+/// The config blob of a model, the fields `/api/tags` reports as details.
+#[derive(Debug, Default, Deserialize)]
+struct ModelConfig {
+    #[serde(default)]
+    model_format: String,
+    #[serde(default)]
+    model_family: String,
+    #[serde(default)]
+    model_families: Option<Vec<String>>,
+    #[serde(default)]
+    model_type: String,
+    #[serde(default)]
+    file_type: String,
+}
+
+/// A model found in the store.
+#[derive(Debug, Clone)]
+pub struct LocalModel {
+    pub name: ModelName,
+
+    /// The path of the manifest file.
+    pub path: PathBuf,
+
+    pub manifest: Manifest,
+
+    /// The model as `/api/tags` would list it.
+    pub info: ModelInfo,
+}
+
+/// How much space a model takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelUsage {
+    pub name: ModelName,
+
+    /// The size of all its blobs.
+    pub size: u64,
+
+    /// The size of the blobs no other model uses, which deleting the model would free.
+    pub unique_size: u64,
+
+    /// Digests listed in the manifest without a blob on disk.
+    pub missing: Vec<String>,
+}
+
+/// A blob used by several models.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedLayer {
+    pub digest: String,
+    pub size: u64,
+    pub models: Vec<ModelName>,
+}
+
+/// A blob no manifest refers to, e.g. left behind by an interrupted pull.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrphanBlob {
+    pub digest: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// A manifest that could not be read, e.g. truncated by a full disk. The blobs only it
+/// refers to are reported as orphans.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidManifest {
+    pub name: ModelName,
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreUsage {
+    pub models: Vec<ModelUsage>,
+    pub shared: Vec<SharedLayer>,
+    pub orphans: Vec<OrphanBlob>,
+
+    /// Manifests left out of `models`, as they could not be read.
+    pub invalid: Vec<InvalidManifest>,
+
+    /// The size of all the blobs on disk, orphans included.
+    pub total_size: u64,
+}
+
+/// A local Ollama model store.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::store::ModelStore;
+///
+/// let store = ModelStore::open_default()?;
+/// for model in store.models()? {
+///     println!("{} {}", model.info.name, model.info.size);
+/// }
+///
+/// let usage = store.usage()?;
+/// for orphan in usage.orphans {
+///     println!("unused blob {} ({} bytes)", orphan.path.display(), orphan.size);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ModelStore {
+    root: PathBuf,
+}
+
+impl ModelStore {
+    /// Opens the store at `root`, the directory holding `manifests` and `blobs`.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, OllamaError> {
+        let root = root.into();
+        std::fs::metadata(root.join("manifests")).map_err(OllamaError::FileError)?;
+        Ok(Self { root })
+    }
+
+    /// Opens the store Ollama uses by default, see [`ModelStore::default_root`].
+    pub fn open_default() -> Result<Self, OllamaError> {
+        let root = Self::default_root().ok_or_else(|| {
+            OllamaError::InvalidFormat("neither OLLAMA_MODELS nor HOME is set".into())
+        })?;
+        Self::open(root)
+    }
+
+    /// `OLLAMA_MODELS` if set, `~/.ollama/models` otherwise.
+    pub fn default_root() -> Option<PathBuf> {
+        if let Some(models) = std::env::var_os("OLLAMA_MODELS").filter(|m| !m.is_empty()) {
+            return Some(PathBuf::from(models));
+        }
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".ollama").join("models"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The path of the manifest of a model, whether it exists or not.
+    pub fn manifest_path(&self, name: &ModelName) -> PathBuf {
+        self.root
+            .join("manifests")
+            .join(&name.host)
+            .join(&name.namespace)
+            .join(&name.model)
+            .join(&name.tag)
+    }
+
+    /// The path of a blob, `None` if it is not on disk. Blobs are stored as `sha256-<hex>`,
+    /// or `sha256:<hex>` by older versions.
+    pub fn blob_path(&self, digest: &str) -> Option<PathBuf> {
+        let blobs = self.root.join("blobs");
+        [digest.replacen(':', "-", 1), digest.to_string()]
+            .into_iter()
+            .map(|file| blobs.join(file))
+            .find(|path| path.is_file())
+    }
+
+    /// All the models in the store, sorted by name. Manifests that cannot be read are
+    /// skipped, see [`ModelStore::invalid_manifests`].
+    pub fn models(&self) -> Result<Vec<LocalModel>, OllamaError> {
+        Ok(self.scan()?.0)
+    }
+
+    /// The manifests [`ModelStore::models`] skips, as they could not be read.
+    pub fn invalid_manifests(&self) -> Result<Vec<InvalidManifest>, OllamaError> {
+        Ok(self.scan()?.1)
+    }
+
+    /// A model by name, e.g. `llama3.1:8b`.
+    pub fn model(&self, name: &str) -> Result<LocalModel, OllamaError> {
+        let name: ModelName = name.parse()?;
+        if !self.manifest_path(&name).is_file() {
+            return Err(OllamaError::ModelDoesNotExist);
+        }
+        self.read(name)
+    }
+
+    /// The path of the GGUF file holding the weights of a model.
+    pub fn model_file(&self, model: &LocalModel) -> Option<PathBuf> {
+        let layer = model.manifest.layer(MODEL_MEDIA_TYPE)?;
+        self.blob_path(&layer.digest)
+    }
+
+    /// Sizes per model, the blobs shared between models and the blobs no model uses.
+    pub fn usage(&self) -> Result<StoreUsage, OllamaError> {
+        let (models, invalid) = self.scan()?;
+
+        let mut users: BTreeMap<&str, (u64, Vec<&ModelName>)> = BTreeMap::new();
+        for model in &models {
+            for layer in model.manifest.blobs() {
+                let entry = users.entry(&layer.digest).or_insert((layer.size, vec![]));
+                if !entry.1.contains(&&model.name) {
+                    entry.1.push(&model.name);
+                }
+            }
+        }
+
+        let usage = models
+            .iter()
+            .map(|model| {
+                let mut seen = BTreeSet::new();
+                let (mut size, mut unique_size, mut missing) = (0, 0, vec![]);
+                for layer in model.manifest.blobs() {
+                    if !seen.insert(&layer.digest) {
+                        continue;
+                    }
+                    size += layer.size;
+                    if users[layer.digest.as_str()].1.len() == 1 {
+                        unique_size += layer.size;
+                    }
+                    if self.blob_path(&layer.digest).is_none() {
+                        missing.push(layer.digest.clone());
+                    }
+                }
+                ModelUsage {
+                    name: model.name.clone(),
+                    size,
+                    unique_size,
+                    missing,
+                }
+            })
+            .collect();
+
+        let shared = users
+            .iter()
+            .filter(|(_, (_, models))| models.len() > 1)
+            .map(|(digest, (size, models))| SharedLayer {
+                digest: digest.to_string(),
+                size: *size,
+                models: models.iter().map(|&m| m.clone()).collect(),
+            })
+            .collect();
+
+        let mut orphans = vec![];
+        let mut total_size = 0;
+        for (digest, path) in self.blobs()? {
+            let size = std::fs::metadata(&path)
+                .map_err(OllamaError::FileError)?
+                .len();
+            total_size += size;
+            if !users.contains_key(digest.as_str()) {
+                orphans.push(OrphanBlob { digest, path, size });
+            }
+        }
+
+        Ok(StoreUsage {
+            models: usage,
+            shared,
+            orphans,
+            invalid,
+            total_size,
+        })
+    }
+
+    /// Every model in the store, along with the manifests that could not be read.
+    fn scan(&self) -> Result<(Vec<LocalModel>, Vec<InvalidManifest>), OllamaError> {
+        let (mut models, mut invalid) = (vec![], vec![]);
+        for name in self.names()? {
+            match self.read(name.clone()) {
+                Ok(model) => models.push(model),
+                Err(e) => invalid.push(InvalidManifest {
+                    path: self.manifest_path(&name),
+                    name,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        Ok((models, invalid))
+    }
+
+    /// The names of all manifests, from their path under `manifests`.
+    fn names(&self) -> Result<Vec<ModelName>, OllamaError> {
+        let mut names = vec![];
+        let root = self.root.join("manifests");
+        for host in read_dirs(&root)? {
+            for namespace in read_dirs(&host)? {
+                for model in read_dirs(&namespace)? {
+                    for tag in read_dir(&model)? {
+                        if !tag.is_file() {
+                            continue;
+                        }
+                        let part = |p: &Path| p.file_name().unwrap().to_string_lossy().to_string();
+                        names.push(ModelName::new(
+                            &part(&host),
+                            &part(&namespace),
+                            &part(&model),
+                            &part(&tag),
+                        ));
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The digests and paths of the blobs on disk. Partial downloads are left out.
+    fn blobs(&self) -> Result<Vec<(String, PathBuf)>, OllamaError> {
+        let mut blobs = vec![];
+        let dir = self.root.join("blobs");
+        if !dir.is_dir() {
+            return Ok(blobs);
+        }
+        for path in read_dir(&dir)? {
+            let file = path.file_name().unwrap().to_string_lossy();
+            let Some(hex) = file
+                .strip_prefix("sha256-")
+                .or_else(|| file.strip_prefix("sha256:"))
+            else {
+                continue;
+            };
+            if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) && path.is_file() {
+                blobs.push((format!("sha256:{hex}"), path));
+            }
+        }
+        blobs.sort();
+        Ok(blobs)
+    }
+
+    fn read(&self, name: ModelName) -> Result<LocalModel, OllamaError> {
+        let path = self.manifest_path(&name);
+        let bytes = std::fs::read(&path).map_err(OllamaError::FileError)?;
+        let manifest: Manifest = serde_json::from_slice(&bytes).map_err(|e| {
+            OllamaError::InvalidFormat(format!("invalid manifest {}: {e}", path.display()))
+        })?;
+
+        let config = match self.blob_path(&manifest.config.digest) {
+            Some(config) => {
+                let bytes = std::fs::read(config).map_err(OllamaError::FileError)?;
+                serde_json::from_slice(&bytes).unwrap_or_default()
+            }
+            None => ModelConfig::default(),
+        };
+
+        let modified_at = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(OllamaError::FileError)?;
+        let digest: String = Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        let info = ModelInfo {
+            name: name.shortest(),
+            modified_at: DateTime::<Local>::from(modified_at).to_rfc3339(),
+            size: manifest.blobs().map(|l| l.size as i64).sum(),
+            digest,
+            details: ModelInfoDetail {
+                format: config.model_format,
+                family: config.model_family,
+                families: config.model_families,
+                parameter_size: config.model_type,
+                quantization_level: config.file_type,
+            },
+        };
+
+        Ok(LocalModel {
+            name,
+            path,
+            manifest,
+            info,
+        })
+    }
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, OllamaError> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir).map_err(OllamaError::FileError)? {
+        paths.push(entry.map_err(OllamaError::FileError)?.path());
+    }
+    Ok(paths)
+}
+
+fn read_dirs(dir: &Path) -> Result<Vec<PathBuf>, OllamaError> {
+    Ok(read_dir(dir)?.into_iter().filter(|p| p.is_dir()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+
+    use super::ModelStore;
+    use crate::error::OllamaError;
+
+    /// A store in a temporary directory, removed on drop.
+    struct Fixture(PathBuf);
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir()
+                .join(format!("ollama-native-store-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join("blobs")).unwrap();
+            std::fs::create_dir_all(root.join("manifests")).unwrap();
+            Self(root)
+        }
+
+        fn blob(&self, content: &[u8]) -> (String, u64) {
+            let hex: String = Sha256::digest(content)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect();
+            let path = self.0.join("blobs").join(format!("sha256-{hex}"));
+            std::fs::write(path, content).unwrap();
+            (format!("sha256:{hex}"), content.len() as u64)
+        }
+
+        fn manifest(&self, path: &str, config: (String, u64), layers: &[(&str, (String, u64))]) {
+            let layers: Vec<_> = layers
+                .iter()
+                .map(|(media_type, (digest, size))| {
+                    json!({ "mediaType": media_type, "digest": digest, "size": size })
+                })
+                .collect();
+            let manifest = json!({
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {
+                    "mediaType": "application/vnd.docker.container.image.v1+json",
+                    "digest": config.0,
+                    "size": config.1,
+                },
+                "layers": layers,
+            });
+            let path = self.0.join("manifests").join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+        }
+
+        fn root(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    /// Two tags of the same weights with their own params, a model from another
+    /// namespace, and an orphan left by a removed model.
+    fn fixture(name: &str) -> Fixture {
+        let fixture = Fixture::new(name);
+        let config = fixture.blob(
+            br#"{"model_format":"gguf","model_family":"llama","model_families":["llama"],"model_type":"8.0B","file_type":"Q4_0"}"#,
+        );
+        let weights = fixture.blob(b"GGUF weights");
+        let template = fixture.blob(b"{{ .Prompt }}");
+        let params = fixture.blob(br#"{"temperature":0.2}"#);
+        let other = fixture.blob(b"GGUF other weights");
+        fixture.blob(b"GGUF removed weights");
+        std::fs::write(
+            fixture.root().join("blobs").join("sha256-abc-partial"),
+            b"x",
+        )
+        .unwrap();
+
+        let model = "application/vnd.ollama.image.model";
+        fixture.manifest(
+            "registry.ollama.ai/library/llama3.1/8b",
+            config.clone(),
+            &[
+                (model, weights.clone()),
+                ("application/vnd.ollama.image.template", template.clone()),
+            ],
+        );
+        fixture.manifest(
+            "registry.ollama.ai/library/llama3.1/cold",
+            config.clone(),
+            &[
+                (model, weights),
+                ("application/vnd.ollama.image.template", template),
+                ("application/vnd.ollama.image.params", params),
+            ],
+        );
+        fixture.manifest(
+            "registry.ollama.ai/jmorgan/tiny/latest",
+            config,
+            &[
+                (model, other),
+                (
+                    "application/vnd.ollama.image.license",
+                    ("sha256:0000".into(), 4),
+                ),
+            ],
+        );
+        fixture
+    }
+
+    #[test]
+    fn models_should_read_manifests() {
+        let fixture = fixture("models");
+        let store = ModelStore::open(fixture.root()).unwrap();
+
+        let names: Vec<String> = store
+            .models()
+            .unwrap()
+            .into_iter()
+            .map(|m| m.info.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec!["jmorgan/tiny:latest", "llama3.1:8b", "llama3.1:cold"]
+        );
+
+        let model = store.model("llama3.1:8b").unwrap();
+        assert_eq!(model.info.size, 112 + 12 + 13);
+        assert_eq!(model.info.digest.len(), 64);
+        assert_eq!(model.info.details.family, "llama");
+        assert_eq!(model.info.details.parameter_size, "8.0B");
+        assert_eq!(model.info.details.quantization_level, "Q4_0");
+        assert_eq!(
+            std::fs::read(store.model_file(&model).unwrap()).unwrap(),
+            b"GGUF weights"
+        );
+
+        assert!(matches!(
+            store.model("llama3.1"),
+            Err(OllamaError::ModelDoesNotExist)
+        ));
+        assert!(ModelStore::open(fixture.root().join("missing")).is_err());
+    }
+
+    #[test]
+    fn usage_should_report_shared_and_orphaned_blobs() {
+        let fixture = fixture("usage");
+        let usage = ModelStore::open(fixture.root()).unwrap().usage().unwrap();
+
+        let sizes: Vec<(String, u64, u64)> = usage
+            .models
+            .iter()
+            .map(|m| (m.name.shortest(), m.size, m.unique_size))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                ("jmorgan/tiny:latest".to_string(), 112 + 18 + 4, 18 + 4),
+                ("llama3.1:8b".to_string(), 112 + 12 + 13, 0),
+                ("llama3.1:cold".to_string(), 112 + 12 + 13 + 19, 19),
+            ]
+        );
+        assert_eq!(usage.models[0].missing, vec!["sha256:0000"]);
+
+        // The config is shared by all three models, the weights and template by two.
+        assert_eq!(usage.shared.len(), 3);
+        assert!(
+            usage
+                .shared
+                .iter()
+                .any(|s| s.size == 112 && s.models.len() == 3)
+        );
+
+        assert_eq!(usage.orphans.len(), 1);
+        assert_eq!(usage.orphans[0].size, 20);
+        assert_eq!(usage.total_size, 112 + 12 + 13 + 19 + 18 + 20);
+    }
+
+    #[test]
+    fn invalid_manifests_should_be_skipped_and_reported() {
+        let fixture = fixture("invalid");
+        let path = fixture
+            .root()
+            .join("manifests/registry.ollama.ai/library/broken/latest");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"{\"schemaVersion\":").unwrap();
+        let store = ModelStore::open(fixture.root()).unwrap();
+
+        assert_eq!(store.models().unwrap().len(), 3);
+        let invalid = store.invalid_manifests().unwrap();
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].name.shortest(), "broken:latest");
+        assert_eq!(invalid[0].path, path);
+        assert!(invalid[0].error.contains("invalid manifest"));
+
+        let usage = store.usage().unwrap();
+        assert_eq!(usage.models.len(), 3);
+        assert_eq!(usage.invalid, invalid);
+    }
+}
@@ -0,0 +1,406 @@
+//! `tracing` spans for the requests sent to Ollama, named and attributed after the
+//! OpenTelemetry GenAI semantic conventions, so that `tracing-opentelemetry` exports them as
+//! GenAI client spans as they are.
+//!
+//! Every request gets a span named `{gen_ai.operation.name} {gen_ai.request.model}`, at the
+//! `INFO` level, which lasts until the response body has been read in full, so that it
+//! covers streams up to their last chunk. It carries:
+//! - `gen_ai.operation.name`: `chat`, `text_completion`, `embeddings`, or the name of the
+//!   endpoint for other requests, e.g. `pull` or `tags`.
+//! - `gen_ai.provider.name` and `gen_ai.system`: `ollama`.
+//! - `gen_ai.request.model`, and `gen_ai.request.temperature`, `gen_ai.request.top_p`,
+//!   `gen_ai.request.top_k`, `gen_ai.request.max_tokens` and `gen_ai.request.seed` if set.
+//! - `server.address`, `server.port`, `http.request.method`, `url.path` and
+//!   `http.response.status_code`. With a [`HostPool`][crate::pool::HostPool], the address
+//!   and port are those of the host that answered.
+//! - `gen_ai.response.model`, `gen_ai.response.id`, `gen_ai.response.finish_reasons`,
+//!   `gen_ai.usage.input_tokens` and `gen_ai.usage.output_tokens`, from the response.
+//! - `gen_ai.server.time_to_first_token`, in seconds, for streams. It is measured by the
+//!   client, from sending the request to receiving the first chunk.
+//! - `ollama.request.stream`, and `ollama.total_duration`, `ollama.load_duration`,
+//!   `ollama.prompt_eval_duration` and `ollama.eval_duration` in seconds, which have no
+//!   GenAI equivalent.
+//! - `error.type` and an `otel.status_code` of `ERROR` if the request fails. The error type
+//!   is the status code for error responses.
+
+use std::time::Instant;
+
+use bytes::Bytes;
+use reqwest::Method;
+use serde_json::Value;
+use tracing::{Instrument, Span, field::Empty, info_span};
+
+use crate::action::observe::{ResponseStats, error_type, observe};
+use crate::config::OllamaConfig;
+use crate::error::OllamaError;
+
+/// The span of a request, see the [module documentation](self).
+pub(crate) struct RequestSpan {
+    span: Span,
+    stream: bool,
+}
+
+impl RequestSpan {
+    pub(crate) fn new(
+        config: &OllamaConfig,
+        method: &Method,
+        path: &str,
+        body: Option<&Bytes>,
+    ) -> Self {
+        let body: Value = body
+            .and_then(|b| serde_json::from_slice(b).ok())
+            .unwrap_or_default();
+        let operation = operation_name(path);
+        let model = body["model"].as_str();
+        let stream = body["stream"] == true;
+        let url = reqwest::Url::parse(&config.url).ok();
+
+        // Native requests set sampling parameters in `options`, OpenAI-compatible ones at the top.
+        let param = |name: &str| match &body["options"][name] {
+            Value::Null => &body[name],
+            value => value,
+        };
+        let max_tokens = match param("num_predict") {
+            Value::Null => &body["max_tokens"],
+            value => value,
+        };
+
+        let span = info_span!(
+            "ollama",
+            otel.name = %model.map_or(operation.to_string(), |m| format!("{operation} {m}")),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            gen_ai.operation.name = operation,
+            gen_ai.provider.name = "ollama",
+            gen_ai.system = "ollama",
+            gen_ai.request.model = model,
+            gen_ai.request.temperature = param("temperature").as_f64(),
+            gen_ai.request.top_p = param("top_p").as_f64(),
+            gen_ai.request.top_k = param("top_k").as_i64(),
+            gen_ai.request.max_tokens = max_tokens.as_i64(),
+            gen_ai.request.seed = param("seed").as_i64(),
+            server.address = url.as_ref().and_then(|u| u.host_str()),
+            server.port = url.as_ref().and_then(|u| u.port_or_known_default()),
+            http.request.method = method.as_str(),
+            url.path = path,
+            http.response.status_code = Empty,
+            gen_ai.response.model = Empty,
+            gen_ai.response.id = Empty,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            gen_ai.server.time_to_first_token = Empty,
+            ollama.request.stream = stream,
+            ollama.total_duration = Empty,
+            ollama.load_duration = Empty,
+            ollama.prompt_eval_duration = Empty,
+            ollama.eval_duration = Empty,
+            error.type = Empty,
+        );
+        Self { span, stream }
+    }
+
+    /// Send the request in the span, which is kept open until the response body is read.
+    pub(crate) async fn instrument(
+        self,
+        send: impl Future<Output = Result<reqwest::Response, OllamaError>>,
+    ) -> Result<reqwest::Response, OllamaError> {
+        let started = Instant::now();
+        let response = match send.instrument(self.span.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.fail(error_type(&e));
+                return Err(e);
+            }
+        };
+
+        let status = response.status();
+        self.span
+            .record("http.response.status_code", status.as_u16());
+        if !status.is_success() {
+            self.fail(status.as_str());
+        }
+
+        if self.span.is_disabled() {
+            return Ok(response);
+        }
+        Ok(observe(response, started, move |stats| self.record(&stats)))
+    }
+
+    /// Records the host that answered instead of the configured one, for clients that pick
+    /// a host per request with a [`HostPool`][crate::pool::HostPool].
+    #[cfg(feature = "pool")]
+    pub(crate) fn server_recorder(&self) -> impl Fn(&str) + use<> {
+        let span = self.span.clone();
+        move |url| {
+            let url = reqwest::Url::parse(url).ok();
+            span.record("server.address", url.as_ref().and_then(|u| u.host_str()));
+            span.record(
+                "server.port",
+                url.as_ref().and_then(|u| u.port_or_known_default()),
+            );
+        }
+    }
+
+    fn fail(&self, error_type: &str) {
+        self.span.record("otel.status_code", "ERROR");
+        self.span.record("error.type", error_type);
+    }
+
+    fn record(&self, stats: &ResponseStats) {
+        let span = &self.span;
+        span.record("gen_ai.response.model", stats.model.as_deref());
+        span.record("gen_ai.response.id", stats.id.as_deref());
+        if !stats.finish_reasons.is_empty() {
+            let reasons = format!("{:?}", stats.finish_reasons);
+            span.record("gen_ai.response.finish_reasons", reasons);
+        }
+        span.record("gen_ai.usage.input_tokens", stats.input_tokens);
+        span.record("gen_ai.usage.output_tokens", stats.output_tokens);
+        if self.stream {
+            let first_chunk = stats.first_chunk.map(|d| d.as_secs_f64());
+            span.record("gen_ai.server.time_to_first_token", first_chunk);
+        }
+        span.record("ollama.total_duration", stats.total_duration);
+        span.record("ollama.load_duration", stats.load_duration);
+        span.record("ollama.prompt_eval_duration", stats.prompt_eval_duration);
+        span.record("ollama.eval_duration", stats.eval_duration);
+        if let Some(error_type) = stats.error {
+            self.fail(error_type);
+        }
+    }
+}
+
+/// The GenAI operation of an endpoint, or the name of the endpoint for other requests.
+fn operation_name(path: &str) -> &str {
+    match path {
+        "/api/chat" | "/v1/chat/completions" => "chat",
+        "/api/generate" | "/v1/completions" => "text_completion",
+        "/api/embed" | "/api/embeddings" | "/v1/embeddings" => "embeddings",
+        _ if path.starts_with("/api/blobs/") => "blobs",
+        _ => path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    use tokio_stream::StreamExt;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::Ollama;
+    use crate::action::IntoStream;
+    use crate::config::WireFormat;
+    use crate::mock::MockOllama;
+
+    type Fields = BTreeMap<&'static str, String>;
+
+    /// Keeps the fields of every span.
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<Fields>>,
+    }
+
+    impl Recorder {
+        /// The fields of the request spans, in the order they were opened.
+        fn requests(&self) -> Vec<Fields> {
+            let spans = self.spans.lock().unwrap();
+            let requests = spans.iter().filter(|s| s.contains_key("gen_ai.system"));
+            requests.cloned().collect()
+        }
+    }
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl Visit for Visitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields::new();
+            span.record(&mut Visitor(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(fields);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut Visitor(&mut spans[span.into_u64() as usize - 1]));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn field<'a>(span: &'a Fields, name: &str) -> Option<&'a str> {
+        span.get(name).map(String::as_str)
+    }
+
+    #[tokio::test]
+    async fn chat_span_should_carry_genai_attributes() {
+        let recorder = Arc::new(Recorder::default());
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama();
+
+        ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .temperature(0.2)
+            .seed(42)
+            .await
+            .unwrap();
+        let chunks: Vec<_> = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 4);
+
+        let spans = recorder.requests();
+        let [chat, stream] = spans.as_slice() else {
+            panic!("expected two request spans, got {spans:?}");
+        };
+        for span in [chat, stream] {
+            assert_eq!(field(span, "otel.name"), Some("chat llama3.1:8b"));
+            assert_eq!(field(span, "otel.kind"), Some("client"));
+            assert_eq!(field(span, "gen_ai.operation.name"), Some("chat"));
+            assert_eq!(field(span, "gen_ai.provider.name"), Some("ollama"));
+            assert_eq!(field(span, "gen_ai.request.model"), Some("llama3.1:8b"));
+            assert_eq!(field(span, "gen_ai.response.model"), Some("llama3.1:8b"));
+            assert_eq!(
+                field(span, "gen_ai.response.finish_reasons"),
+                Some("[\"stop\"]")
+            );
+            assert_eq!(field(span, "gen_ai.usage.input_tokens"), Some("10"));
+            assert_eq!(field(span, "gen_ai.usage.output_tokens"), Some("3"));
+            assert_eq!(field(span, "http.request.method"), Some("POST"));
+            assert_eq!(field(span, "http.response.status_code"), Some("200"));
+            assert_eq!(field(span, "url.path"), Some("/api/chat"));
+            assert_eq!(field(span, "server.address"), Some("127.0.0.1"));
+            assert!(field(span, "error.type").is_none());
+        }
+
+        assert_eq!(field(chat, "gen_ai.request.temperature"), Some("0.2"));
+        assert_eq!(field(chat, "gen_ai.request.seed"), Some("42"));
+        assert_eq!(field(chat, "ollama.request.stream"), Some("false"));
+        assert!(field(chat, "gen_ai.server.time_to_first_token").is_none());
+
+        assert!(field(stream, "gen_ai.request.temperature").is_none());
+        assert_eq!(field(stream, "ollama.request.stream"), Some("true"));
+        let ttft: f64 = field(stream, "gen_ai.server.time_to_first_token")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(ttft > 0.0);
+    }
+
+    #[tokio::test]
+    async fn openai_stream_span_should_record_usage() {
+        let recorder = Arc::new(Recorder::default());
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let mock = MockOllama::start().await;
+        let ollama = mock.ollama().wire_format(WireFormat::OpenAi);
+
+        let _: Vec<_> = ollama
+            .chat("llama3.1:8b")
+            .user_message("hello there")
+            .num_predict(64)
+            .stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let spans = recorder.requests();
+        let span = &spans[0];
+        assert_eq!(field(span, "url.path"), Some("/v1/chat/completions"));
+        assert_eq!(field(span, "gen_ai.operation.name"), Some("chat"));
+        assert_eq!(field(span, "gen_ai.request.max_tokens"), Some("64"));
+        assert_eq!(
+            field(span, "gen_ai.response.finish_reasons"),
+            Some("[\"stop\"]")
+        );
+        assert_eq!(field(span, "gen_ai.usage.input_tokens"), Some("10"));
+        assert_eq!(field(span, "gen_ai.usage.output_tokens"), Some("3"));
+        assert!(field(span, "gen_ai.response.id").is_some());
+    }
+
+    #[cfg(feature = "pool")]
+    #[tokio::test]
+    async fn pooled_spans_should_report_the_host_that_answered() {
+        let recorder = Arc::new(Recorder::default());
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let mock = MockOllama::start().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        // The dead host is tried first, then the request fails over to the mock.
+        let pool = crate::pool::HostPool::new([dead, mock.url.clone()]);
+        Ollama::with_pool(Arc::new(pool)).version().await.unwrap();
+
+        let spans = recorder.requests();
+        let port = reqwest::Url::parse(&mock.url).unwrap().port().unwrap();
+        assert_eq!(field(&spans[0], "server.port"), Some(&*port.to_string()));
+    }
+
+    #[tokio::test]
+    async fn failed_requests_should_mark_span_as_error() {
+        let recorder = Arc::new(Recorder::default());
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let mock = MockOllama::start().await;
+
+        assert!(
+            mock.ollama()
+                .show_model_information("missing")
+                .await
+                .is_err()
+        );
+        assert!(Ollama::new("http://127.0.0.1:1").version().await.is_err());
+
+        let spans = recorder.requests();
+        let [show, version] = spans.as_slice() else {
+            panic!("expected two request spans, got {spans:?}");
+        };
+        assert_eq!(field(show, "otel.name"), Some("show missing"));
+        assert_eq!(field(show, "http.response.status_code"), Some("404"));
+        assert_eq!(field(show, "error.type"), Some("404"));
+        assert_eq!(field(show, "otel.status_code"), Some("ERROR"));
+
+        assert_eq!(field(version, "otel.name"), Some("version"));
+        assert_eq!(field(version, "http.request.method"), Some("GET"));
+        assert_eq!(field(version, "error.type"), Some("connect"));
+        assert_eq!(field(version, "otel.status_code"), Some("ERROR"));
+        assert!(field(version, "gen_ai.request.model").is_none());
+    }
+}
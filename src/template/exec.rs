@@ -0,0 +1,468 @@
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use super::parse::{Arg, Node, Pipeline};
+use crate::error::OllamaError;
+
+fn error(message: impl std::fmt::Display) -> OllamaError {
+    OllamaError::TemplateError(message.to_string())
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+}
+
+pub(crate) struct Exec<'v> {
+    root: &'v Value,
+    vars: Vec<(String, Value)>,
+    out: String,
+}
+
+impl<'v> Exec<'v> {
+    pub(crate) fn run(nodes: &[Node], data: &'v Value) -> Result<String, OllamaError> {
+        let mut exec = Self {
+            root: data,
+            vars: vec![],
+            out: String::new(),
+        };
+        exec.list(nodes, data)?;
+        Ok(exec.out)
+    }
+
+    fn list(&mut self, nodes: &[Node], dot: &Value) -> Result<Flow, OllamaError> {
+        // Variables are scoped to the list declaring them.
+        let scope = self.vars.len();
+        let flow = self.nodes(nodes, dot);
+        self.vars.truncate(scope);
+        flow
+    }
+
+    fn nodes(&mut self, nodes: &[Node], dot: &Value) -> Result<Flow, OllamaError> {
+        for node in nodes {
+            let flow = match node {
+                Node::Text(text) => {
+                    self.out.push_str(text);
+                    Flow::Normal
+                }
+                Node::Action(pipeline) => {
+                    let value = self.pipeline(pipeline, dot)?;
+                    if pipeline.decl.is_empty() {
+                        self.out.push_str(&print(&value));
+                    }
+                    Flow::Normal
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                } => self.branches(branches, otherwise, dot, false)?,
+                Node::With {
+                    branches,
+                    otherwise,
+                } => self.branches(branches, otherwise, dot, true)?,
+                Node::Range {
+                    pipeline,
+                    body,
+                    otherwise,
+                } => self.range(pipeline, body, otherwise, dot)?,
+                Node::Break => Flow::Break,
+                Node::Continue => Flow::Continue,
+            };
+            if !matches!(flow, Flow::Normal) {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn branches(
+        &mut self,
+        branches: &[(Pipeline, Vec<Node>)],
+        otherwise: &[Node],
+        dot: &Value,
+        with: bool,
+    ) -> Result<Flow, OllamaError> {
+        let scope = self.vars.len();
+        for (pipeline, body) in branches {
+            let value = self.pipeline(pipeline, dot)?;
+            if truthy(&value) {
+                let dot = if with { &value } else { dot };
+                let flow = self.list(body, dot);
+                self.vars.truncate(scope);
+                return flow;
+            }
+        }
+        let flow = self.list(otherwise, dot);
+        self.vars.truncate(scope);
+        flow
+    }
+
+    fn range(
+        &mut self,
+        pipeline: &Pipeline,
+        body: &[Node],
+        otherwise: &[Node],
+        dot: &Value,
+    ) -> Result<Flow, OllamaError> {
+        let decl = Pipeline {
+            decl: vec![],
+            ..pipeline.clone()
+        };
+        let value = self.pipeline(&decl, dot)?;
+        let items: Vec<(Value, Value)> = match value {
+            Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| (Value::from(i), item))
+                .collect(),
+            Value::Object(map) => map
+                .into_iter()
+                .map(|(k, v)| (Value::String(k), v))
+                .collect(),
+            Value::Null => vec![],
+            Value::Number(n) if n.as_u64().is_some() => (0..n.as_u64().unwrap())
+                .map(|i| (Value::from(i), Value::from(i)))
+                .collect(),
+            other => return Err(error(format!("range can't iterate over {other}"))),
+        };
+        if items.is_empty() {
+            return self.list(otherwise, dot);
+        }
+
+        for (key, item) in items {
+            let scope = self.vars.len();
+            match pipeline.decl.as_slice() {
+                [] => {}
+                [value] => self.vars.push((value.clone(), item.clone())),
+                [index, value, ..] => {
+                    self.vars.push((index.clone(), key));
+                    self.vars.push((value.clone(), item.clone()));
+                }
+            }
+            let flow = self.list(body, &item);
+            self.vars.truncate(scope);
+            if let Flow::Break = flow? {
+                break;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn pipeline(&mut self, pipeline: &Pipeline, dot: &Value) -> Result<Value, OllamaError> {
+        let mut piped = None;
+        for command in &pipeline.commands {
+            piped = Some(self.command(command, dot, piped)?);
+        }
+        let value = piped.unwrap_or_default();
+
+        for name in &pipeline.decl {
+            if pipeline.assign {
+                let var = self
+                    .vars
+                    .iter_mut()
+                    .rev()
+                    .find(|(n, _)| n == name)
+                    .ok_or_else(|| error(format!("undefined variable ${name}")))?;
+                var.1 = value.clone();
+            } else {
+                self.vars.push((name.clone(), value.clone()));
+            }
+        }
+        Ok(value)
+    }
+
+    fn command(
+        &mut self,
+        args: &[Arg],
+        dot: &Value,
+        piped: Option<Value>,
+    ) -> Result<Value, OllamaError> {
+        let Arg::Function(name) = &args[0] else {
+            if args.len() > 1 || piped.is_some() {
+                return Err(error("can't give arguments to a non-function"));
+            }
+            return self.arg(&args[0], dot);
+        };
+
+        let mut values = args[1..]
+            .iter()
+            .map(|arg| self.arg(arg, dot))
+            .collect::<Result<Vec<_>, _>>()?;
+        values.extend(piped);
+        call(name, &values)
+    }
+
+    fn arg(&mut self, arg: &Arg, dot: &Value) -> Result<Value, OllamaError> {
+        match arg {
+            Arg::Field(fields) => Ok(lookup(dot, fields)),
+            Arg::Var(name, fields) => {
+                let value = if name.is_empty() {
+                    self.root
+                } else {
+                    self.vars
+                        .iter()
+                        .rev()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, v)| v)
+                        .ok_or_else(|| error(format!("undefined variable ${name}")))?
+                };
+                Ok(lookup(value, fields))
+            }
+            Arg::Literal(value) => Ok(value.clone()),
+            Arg::Function(name) => call(name, &[]),
+            Arg::Pipeline(pipeline) => self.pipeline(pipeline, dot),
+        }
+    }
+}
+
+/// Looks fields up the way Go does on structs, by their exported name, falling back to
+/// the snake case name used in JSON, e.g. `.ToolCalls` reads `tool_calls`.
+fn lookup(value: &Value, fields: &[String]) -> Value {
+    let mut value = value;
+    for field in fields {
+        let Value::Object(map) = value else {
+            return Value::Null;
+        };
+        match map.get(field).or_else(|| map.get(&snake_case(field))) {
+            Some(v) => value = v,
+            None => return Value::Null,
+        }
+    }
+    value.clone()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Go's notion of an empty value: false, 0, nil, or an empty string, array or map.
+pub(crate) fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Strings are printed as is and nil as nothing. Arrays and maps are printed as JSON, the
+/// way Ollama prints its tool types.
+fn print(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Result<Ordering, OllamaError> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .zip(b.as_f64())
+            .and_then(|(a, b)| a.partial_cmp(&b))
+            .ok_or_else(|| error("invalid number comparison")),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+        _ => Err(error(format!(
+            "incompatible types for comparison: {a} and {b}"
+        ))),
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => compare(a, b).is_ok_and(|o| o.is_eq()),
+        _ => a == b,
+    }
+}
+
+fn integer(value: &Value) -> Result<usize, OllamaError> {
+    value
+        .as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| error(format!("{value} is not a valid index")))
+}
+
+fn call(name: &str, args: &[Value]) -> Result<Value, OllamaError> {
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(error(format!(
+                "wrong number of args for {name}: want {n} got {}",
+                args.len()
+            )))
+        }
+    };
+
+    let value = match name {
+        "and" => args
+            .iter()
+            .find(|v| !truthy(v))
+            .or(args.last())
+            .cloned()
+            .ok_or_else(|| error("and needs at least one argument"))?,
+        "or" => args
+            .iter()
+            .find(|v| truthy(v))
+            .or(args.last())
+            .cloned()
+            .ok_or_else(|| error("or needs at least one argument"))?,
+        "not" => {
+            arity(1)?;
+            Value::Bool(!truthy(&args[0]))
+        }
+        "eq" => {
+            let (first, rest) = args
+                .split_first()
+                .filter(|(_, rest)| !rest.is_empty())
+                .ok_or_else(|| error("eq needs at least two arguments"))?;
+            Value::Bool(rest.iter().any(|v| equal(first, v)))
+        }
+        "ne" => {
+            arity(2)?;
+            Value::Bool(!equal(&args[0], &args[1]))
+        }
+        "lt" | "le" | "gt" | "ge" => {
+            arity(2)?;
+            let ordering = compare(&args[0], &args[1])?;
+            Value::Bool(match name {
+                "lt" => ordering.is_lt(),
+                "le" => ordering.is_le(),
+                "gt" => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        "len" => {
+            arity(1)?;
+            Value::from(match &args[0] {
+                Value::String(s) => s.len(),
+                Value::Array(a) => a.len(),
+                Value::Object(o) => o.len(),
+                Value::Null => 0,
+                other => return Err(error(format!("len of {other}"))),
+            })
+        }
+        "index" => {
+            let (item, keys) = args
+                .split_first()
+                .ok_or_else(|| error("index needs at least one argument"))?;
+            let mut item = item.clone();
+            for key in keys {
+                item = match (&item, key) {
+                    (Value::Array(a), _) => {
+                        let i = integer(key)?;
+                        a.get(i)
+                            .cloned()
+                            .ok_or_else(|| error(format!("index out of range: {i}")))?
+                    }
+                    (Value::Object(o), Value::String(k)) => o.get(k).cloned().unwrap_or_default(),
+                    (Value::Null, _) => Value::Null,
+                    _ => return Err(error(format!("can't index {item} with {key}"))),
+                };
+            }
+            item
+        }
+        "slice" => {
+            let (item, bounds) = args
+                .split_first()
+                .filter(|(_, bounds)| bounds.len() <= 2)
+                .ok_or_else(|| error("slice needs one to three arguments"))?;
+            let len = match item {
+                Value::String(s) => s.len(),
+                Value::Array(a) => a.len(),
+                other => return Err(error(format!("can't slice {other}"))),
+            };
+            let start = bounds.first().map(integer).transpose()?.unwrap_or(0);
+            let end = bounds.get(1).map(integer).transpose()?.unwrap_or(len);
+            if start > end || end > len {
+                return Err(error(format!("slice bounds out of range [{start}:{end}]")));
+            }
+            match item {
+                Value::String(s) => Value::from(
+                    s.get(start..end)
+                        .ok_or_else(|| error("slice is not on a character boundary"))?,
+                ),
+                Value::Array(a) => Value::from(a[start..end].to_vec()),
+                _ => unreachable!(),
+            }
+        }
+        "print" | "println" => {
+            // Like Go's fmt.Sprint, operands are separated by a space when neither is a string.
+            let mut printed = String::new();
+            for (i, arg) in args.iter().enumerate() {
+                let spaced = name == "println"
+                    || (!arg.is_string() && !args[i.saturating_sub(1)].is_string());
+                if i > 0 && spaced {
+                    printed.push(' ');
+                }
+                printed.push_str(&print(arg));
+            }
+            if name == "println" {
+                printed.push('\n');
+            }
+            Value::from(printed)
+        }
+        "printf" => {
+            let (format, args) = args
+                .split_first()
+                .ok_or_else(|| error("printf needs a format"))?;
+            Value::from(printf(&print(format), args)?)
+        }
+        "json" => {
+            arity(1)?;
+            Value::from(args[0].to_string())
+        }
+        "currentDate" => {
+            arity(0)?;
+            Value::from(chrono::Local::now().format("%Y-%m-%d").to_string())
+        }
+        _ => return Err(error(format!("function {name:?} not defined"))),
+    };
+    Ok(value)
+}
+
+/// The `%v`, `%s`, `%d`, `%q` and `%%` verbs of Go's fmt.Sprintf.
+fn printf(format: &str, args: &[Value]) -> Result<String, OllamaError> {
+    let mut printed = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            printed.push(c);
+            continue;
+        }
+        let verb = chars
+            .next()
+            .ok_or_else(|| error("printf format ends with %"))?;
+        if verb == '%' {
+            printed.push('%');
+            continue;
+        }
+        let arg = args
+            .next()
+            .ok_or_else(|| error(format!("printf is missing an argument for %{verb}")))?;
+        match verb {
+            'v' | 's' | 'd' => printed.push_str(&print(arg)),
+            'q' => printed.push_str(&Value::from(print(arg)).to_string()),
+            _ => return Err(error(format!("unsupported printf verb %{verb}"))),
+        }
+    }
+    Ok(printed)
+}
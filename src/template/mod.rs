@@ -0,0 +1,598 @@
+//! Local rendering of the Go `text/template` prompt templates Ollama models use, to see
+//! the exact prompt a list of messages turns into or to build prompts for `raw` mode.
+//!
+//! The subset of the template language used by Ollama templates is supported: fields,
+//! variables, `if`, `with`, `range` with `break` and `continue`, pipelines and the
+//! comparison, `len`, `index`, `slice`, `print`, `printf`, `json` and `currentDate`
+//! functions. `define`, `template` and `block` are not.
+
+mod exec;
+mod parse;
+
+use serde_json::{Value, json};
+
+use crate::{
+    abi::{Message, Role},
+    error::OllamaError,
+};
+
+use exec::Exec;
+use parse::{Node, Pipeline};
+
+/// A parsed prompt template.
+///
+/// # Example
+/// ```rust,ignore
+/// use ollama_native::{abi::Message, template::{Template, TemplateValues}};
+///
+/// let info = ollama.show_model_information("llama3.1:8b").await?;
+/// let template = Template::parse(&info.template)?;
+///
+/// let prompt = template.render(&TemplateValues::new(vec![
+///     Message::system("You are a pirate."),
+///     Message::user("Why is the sky blue?"),
+/// ]))?;
+///
+/// let resp = ollama.generate("llama3.1:8b").prompt(&prompt).raw().await?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+/// The values a template is rendered with.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateValues {
+    pub messages: Vec<Message>,
+
+    /// The tools as JSON, see [`TemplateValues::tools`].
+    pub tools: Vec<Value>,
+
+    /// For fill-in-the-middle templates, the text after the prompt.
+    pub suffix: Option<String>,
+
+    pub think: Option<bool>,
+}
+
+impl TemplateValues {
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Renders a single user prompt, the way `/api/generate` does.
+    pub fn prompt(prompt: &str) -> Self {
+        Self::new(vec![Message::user(prompt)])
+    }
+
+    /// The tools offered to the model in JSON, as passed to
+    /// [`ChatAction::tools`][`crate::action::completion::chat::ChatAction::tools`].
+    pub fn tools(mut self, tools: &[&str]) -> Result<Self, OllamaError> {
+        for tool in tools {
+            let tool = serde_json::from_str(tool)
+                .map_err(|e| OllamaError::InvalidFormat(format!("invalid tool format: {e}")))?;
+            self.tools.push(tool);
+        }
+        Ok(self)
+    }
+
+    #[inline]
+    pub fn suffix(mut self, suffix: &str) -> Self {
+        self.suffix = Some(suffix.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn think(mut self, think: bool) -> Self {
+        self.think = Some(think);
+        self
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, OllamaError> {
+        Ok(Self {
+            source: source.to_string(),
+            nodes: parse::parse(source)?,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the template reads `.Messages`. Templates that do not are rendered once per
+    /// exchange with `.System`, `.Prompt` and `.Response`.
+    pub fn uses_messages(&self) -> bool {
+        self.uses_field("Messages")
+    }
+
+    fn uses_field(&self, name: &str) -> bool {
+        let mut used = false;
+        for node in &self.nodes {
+            node.visit(&mut |p: &Pipeline| used |= p.uses_field(name));
+        }
+        used
+    }
+
+    /// Execute the template with `data` as dot, following Go's semantics. Fields are looked
+    /// up by name then by their snake case name, e.g. `.ToolCalls` reads `tool_calls`.
+    pub fn execute(&self, data: &Value) -> Result<String, OllamaError> {
+        Exec::run(&self.nodes, data)
+    }
+
+    /// Render the prompt Ollama builds from the values.
+    ///
+    /// Like Ollama, consecutive messages of the same role are merged, except tool results,
+    /// and system messages are also joined into `.System`. Templates without `.Messages` are
+    /// rendered once per exchange, the last one stopping right after `.Response`. Objects are
+    /// printed as JSON with their keys sorted, which may differ from the field order Ollama uses.
+    pub fn render(&self, values: &TemplateValues) -> Result<String, OllamaError> {
+        let (system, messages) = collate(&values.messages);
+
+        if let Some(suffix) = values.suffix.as_deref().filter(|s| !s.is_empty()) {
+            let prompt = messages
+                .iter()
+                .rev()
+                .find(|m| m.role == Role::User)
+                .map_or("", |m| m.content.as_str());
+            if !prompt.is_empty() {
+                return self.execute(&json!({
+                    "Prompt": prompt,
+                    "Suffix": suffix,
+                    "Response": "",
+                }));
+            }
+        }
+
+        if self.uses_messages() {
+            let messages = serde_json::to_value(&messages)
+                .map_err(|e| OllamaError::InvalidFormat(e.to_string()))?;
+            return self.execute(&json!({
+                "System": system,
+                "Messages": messages,
+                "Tools": values.tools,
+                "Response": "",
+                "Think": values.think.unwrap_or_default(),
+                "IsThinkSet": values.think.is_some(),
+            }));
+        }
+
+        self.render_legacy(&messages)
+    }
+
+    fn render_legacy(&self, messages: &[Message]) -> Result<String, OllamaError> {
+        let mut rendered = String::new();
+        let (mut system, mut prompt, mut response) = ("", "", "");
+        let vars = |system, prompt, response| json!({ "System": system, "Prompt": prompt, "Response": response });
+
+        for message in messages {
+            let flush = match message.role {
+                Role::System => !prompt.is_empty() || !response.is_empty(),
+                Role::User => !response.is_empty(),
+                _ => false,
+            };
+            if flush {
+                rendered.push_str(&self.execute(&vars(system, prompt, response))?);
+                (system, prompt, response) = ("", "", "");
+            }
+
+            match message.role {
+                Role::System => system = &message.content,
+                Role::User => prompt = &message.content,
+                Role::Assistant => response = &message.content,
+                Role::Tool => {}
+            }
+        }
+
+        // The last exchange ends where the reply starts.
+        let nodes = cut_after_response(&self.nodes, &mut false);
+        rendered.push_str(&Exec::run(&nodes, &vars(system, prompt, response))?);
+        Ok(rendered)
+    }
+}
+
+/// System messages joined, and the messages with consecutive ones of the same role merged.
+fn collate(messages: &[Message]) -> (String, Vec<Message>) {
+    let mut system = vec![];
+    let mut collated: Vec<Message> = vec![];
+    for message in messages {
+        if message.role == Role::System {
+            system.push(message.content.as_str());
+        }
+        match collated.last_mut() {
+            Some(last) if last.role == message.role && message.role != Role::Tool => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            }
+            _ => collated.push(message.clone()),
+        }
+    }
+    (system.join("\n\n"), collated)
+}
+
+/// The nodes up to the first one reading `.Response`, dropping everything after it.
+fn cut_after_response(nodes: &[Node], cut: &mut bool) -> Vec<Node> {
+    let mut kept = vec![];
+    for node in nodes {
+        if *cut {
+            break;
+        }
+        let node = match node {
+            Node::Action(pipeline) => {
+                *cut = pipeline.uses_field("Response");
+                node.clone()
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => Node::If {
+                branches: cut_branches(branches, cut),
+                otherwise: cut_after_response(otherwise, cut),
+            },
+            Node::With {
+                branches,
+                otherwise,
+            } => Node::With {
+                branches: cut_branches(branches, cut),
+                otherwise: cut_after_response(otherwise, cut),
+            },
+            Node::Range {
+                pipeline,
+                body,
+                otherwise,
+            } => Node::Range {
+                pipeline: pipeline.clone(),
+                body: cut_after_response(body, cut),
+                otherwise: cut_after_response(otherwise, cut),
+            },
+            other => other.clone(),
+        };
+        kept.push(node);
+    }
+    kept
+}
+
+fn cut_branches(branches: &[(Pipeline, Vec<Node>)], cut: &mut bool) -> Vec<(Pipeline, Vec<Node>)> {
+    branches
+        .iter()
+        .map(|(pipeline, body)| (pipeline.clone(), cut_after_response(body, cut)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Template, TemplateValues};
+    use crate::abi::Message;
+
+    const LLAMA_3_1: &str = r#"{{- if or .System .Tools }}<|start_header_id|>system<|end_header_id|>
+{{- if .System }}
+
+{{ .System }}
+{{- end }}
+{{- if .Tools }}
+
+You are a helpful assistant with tool calling capabilities.
+{{- end }}<|eot_id|>
+{{- end }}
+{{- range $i, $_ := .Messages }}
+{{- $last := eq (len (slice $.Messages $i)) 1 }}
+{{- if eq .Role "user" }}<|start_header_id|>user<|end_header_id|>
+{{- if and $.Tools $last }}
+
+Given the following functions, please respond with a JSON for a function call.
+
+{{ range $.Tools }}
+{{- . }}
+{{ end }}
+Question: {{ .Content }}<|eot_id|>
+{{- else }}
+
+{{ .Content }}<|eot_id|>
+{{- end }}{{ if $last }}<|start_header_id|>assistant<|end_header_id|>
+
+{{ end }}
+{{- else if eq .Role "assistant" }}<|start_header_id|>assistant<|end_header_id|>
+{{- if .ToolCalls }}
+{{ range .ToolCalls }}
+{"name": "{{ .Function.Name }}", "parameters": {{ .Function.Arguments }}}{{ end }}
+{{- else }}
+
+{{ .Content }}
+{{- end }}{{ if not $last }}<|eot_id|>{{ end }}
+{{- else if eq .Role "tool" }}<|start_header_id|>ipython<|end_header_id|>
+
+{{ .Content }}<|eot_id|>{{ if $last }}<|start_header_id|>assistant<|end_header_id|>
+
+{{ end }}
+{{- end }}
+{{- end }}"#;
+
+    const QWEN_2_5: &str = r#"{{- if .Messages }}
+{{- if or .System .Tools }}<|im_start|>system
+{{- if .System }}
+{{ .System }}
+{{- end }}
+{{- if .Tools }}
+
+<tools>
+{{- range .Tools }}
+{"type": "function", "function": {{ .Function }}}
+{{- end }}
+</tools>
+{{- end }}<|im_end|>
+{{ end }}
+{{- range $i, $_ := .Messages }}
+{{- $last := eq (len (slice $.Messages $i)) 1 -}}
+{{- if eq .Role "user" }}<|im_start|>user
+{{ .Content }}<|im_end|>
+{{ else if eq .Role "assistant" }}<|im_start|>assistant
+{{ if .Content }}{{ .Content }}
+{{- else if .ToolCalls }}<tool_call>
+{{ range .ToolCalls }}{"name": "{{ .Function.Name }}", "arguments": {{ .Function.Arguments }}}
+{{ end }}</tool_call>
+{{- end }}{{ if not $last }}<|im_end|>
+{{ end }}
+{{- else if eq .Role "tool" }}<|im_start|>user
+<tool_response>
+{{ .Content }}
+</tool_response><|im_end|>
+{{ end }}
+{{- if and (ne .Role "assistant") $last }}<|im_start|>assistant
+{{ end }}
+{{- end }}
+{{- else }}
+{{- if .System }}<|im_start|>system
+{{ .System }}<|im_end|>
+{{ end }}{{ if .Prompt }}<|im_start|>user
+{{ .Prompt }}<|im_end|>
+{{ end }}<|im_start|>assistant
+{{ end }}{{ .Response }}{{ if .Response }}<|im_end|>{{ end }}"#;
+
+    const MISTRAL: &str = r#"{{- range $i, $_ := .Messages }}
+{{- if eq .Role "user" }}
+{{- if and (le (len (slice $.Messages $i)) 2) $.Tools }}[AVAILABLE_TOOLS] {{ json $.Tools }}[/AVAILABLE_TOOLS]
+{{- end }}[INST] {{ if and (eq (len (slice $.Messages $i)) 1) $.System }}{{ $.System }}
+
+{{ end }}{{ .Content }}[/INST]
+{{- else if eq .Role "assistant" }}
+{{- if .Content }} {{ .Content }}</s>
+{{- else if .ToolCalls }}[TOOL_CALLS] [
+{{- range .ToolCalls }}{"name": "{{ .Function.Name }}", "arguments": {{ json .Function.Arguments }}}
+{{- end }}]</s>
+{{- end }}
+{{- else if eq .Role "tool" }}[TOOL_RESULTS] {"content": {{ .Content }}}[/TOOL_RESULTS]
+{{- end }}
+{{- end }}"#;
+
+    const GEMMA_2: &str = r#"{{- range $i, $_ := .Messages }}
+{{- $last := eq (len (slice $.Messages $i)) 1 }}
+{{- if or (eq .Role "user") (eq .Role "system") }}<start_of_turn>user
+{{ .Content }}<end_of_turn>
+{{ if $last }}<start_of_turn>model
+{{ end }}
+{{- else if eq .Role "assistant" }}<start_of_turn>model
+{{ .Content }}{{ if not $last }}<end_of_turn>
+{{ end }}
+{{- end }}
+{{- end }}"#;
+
+    const PHI_3: &str = r#"{{ if .System }}<|system|>
+{{ .System }}<|end|>
+{{ end }}{{ if .Prompt }}<|user|>
+{{ .Prompt }}<|end|>
+{{ end }}<|assistant|>
+{{ .Response }}<|end|>
+"#;
+
+    const WEATHER_TOOL: &str =
+        r#"{"type":"function","function":{"name":"get_weather","parameters":{"type":"object"}}}"#;
+
+    fn render(template: &str, values: &TemplateValues) -> String {
+        Template::parse(template).unwrap().render(values).unwrap()
+    }
+
+    fn tool_call() -> Message {
+        Message::assistant("").tool_call(json!({
+            "function": { "name": "get_weather", "arguments": { "city": "Paris" } }
+        }))
+    }
+
+    #[test]
+    fn llama_should_render_tools_and_calls() {
+        let values = TemplateValues::new(vec![
+            Message::system("Be brief."),
+            Message::user("Hi"),
+            Message::assistant("Hello!"),
+            Message::user("Weather in Paris?"),
+        ]);
+        assert_eq!(
+            render(LLAMA_3_1, &values),
+            "<|start_header_id|>system<|end_header_id|>\n\nBe brief.<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\nHello!<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nWeather in Paris?<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+
+        let values = TemplateValues::new(vec![
+            Message::user("Weather in Paris?"),
+            tool_call(),
+            Message::tool("22C"),
+        ])
+        .tools(&[WEATHER_TOOL])
+        .unwrap();
+        assert_eq!(
+            render(LLAMA_3_1, &values),
+            "<|start_header_id|>system<|end_header_id|>\n\n\
+             You are a helpful assistant with tool calling capabilities.<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nWeather in Paris?<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n\
+             {\"name\": \"get_weather\", \"parameters\": {\"city\":\"Paris\"}}<|eot_id|>\
+             <|start_header_id|>ipython<|end_header_id|>\n\n22C<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+
+        // The tools are listed with the last user message.
+        let values = TemplateValues::prompt("Weather in Paris?")
+            .tools(&[WEATHER_TOOL])
+            .unwrap();
+        assert!(render(LLAMA_3_1, &values).contains(&format!(
+            "a function call.\n\n{}\n\nQuestion: Weather in Paris?<|eot_id|>",
+            serde_json::from_str::<serde_json::Value>(WEATHER_TOOL).unwrap()
+        )));
+    }
+
+    #[test]
+    fn qwen_should_render_tools_and_calls() {
+        let values = TemplateValues::new(vec![
+            Message::system("Be brief."),
+            Message::user("Weather in Paris?"),
+            tool_call(),
+            Message::tool("22C"),
+        ])
+        .tools(&[WEATHER_TOOL])
+        .unwrap();
+        assert_eq!(
+            render(QWEN_2_5, &values),
+            "<|im_start|>system\nBe brief.\n\n<tools>\n\
+             {\"type\": \"function\", \"function\": {\"name\":\"get_weather\",\"parameters\":{\"type\":\"object\"}}}\n\
+             </tools><|im_end|>\n\
+             <|im_start|>user\nWeather in Paris?<|im_end|>\n\
+             <|im_start|>assistant\n<tool_call>\n\
+             {\"name\": \"get_weather\", \"arguments\": {\"city\":\"Paris\"}}\n\
+             </tool_call><|im_end|>\n\
+             <|im_start|>user\n<tool_response>\n22C\n</tool_response><|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn mistral_should_render_json_tools() {
+        let values = TemplateValues::new(vec![
+            Message::system("Be brief."),
+            Message::user("Weather in Paris?"),
+            tool_call(),
+            Message::tool("22"),
+            Message::assistant("It is 22C."),
+            Message::user("Thanks"),
+        ])
+        .tools(&[WEATHER_TOOL])
+        .unwrap();
+        assert_eq!(
+            render(MISTRAL, &values),
+            "[INST] Weather in Paris?[/INST]\
+             [TOOL_CALLS] [{\"name\": \"get_weather\", \"arguments\": {\"city\":\"Paris\"}}]</s>\
+             [TOOL_RESULTS] {\"content\": 22}[/TOOL_RESULTS] It is 22C.</s>\
+             [AVAILABLE_TOOLS] [{\"function\":{\"name\":\"get_weather\",\"parameters\":{\"type\":\"object\"}},\"type\":\"function\"}][/AVAILABLE_TOOLS]\
+             [INST] Be brief.\n\nThanks[/INST]"
+        );
+    }
+
+    #[test]
+    fn gemma_should_merge_consecutive_messages() {
+        let values = TemplateValues::new(vec![
+            Message::user("Hi"),
+            Message::user("Anyone there?"),
+            Message::assistant("Yes"),
+            Message::user("Good"),
+        ]);
+        assert_eq!(
+            render(GEMMA_2, &values),
+            "<start_of_turn>user\nHi\n\nAnyone there?<end_of_turn>\n\
+             <start_of_turn>model\nYes<end_of_turn>\n\
+             <start_of_turn>user\nGood<end_of_turn>\n<start_of_turn>model\n"
+        );
+    }
+
+    #[test]
+    fn legacy_template_should_render_each_exchange() {
+        let template = Template::parse(PHI_3).unwrap();
+        assert!(!template.uses_messages());
+
+        let values = TemplateValues::new(vec![
+            Message::system("Be brief."),
+            Message::user("Hi"),
+            Message::assistant("Hello!"),
+            Message::user("Bye"),
+        ]);
+        assert_eq!(
+            template.render(&values).unwrap(),
+            "<|system|>\nBe brief.<|end|>\n<|user|>\nHi<|end|>\n<|assistant|>\nHello!<|end|>\n\
+             <|user|>\nBye<|end|>\n<|assistant|>\n"
+        );
+    }
+
+    #[test]
+    fn execute_should_follow_go_semantics() {
+        let execute = |source: &str, data| Template::parse(source).unwrap().execute(&data).unwrap();
+
+        assert_eq!(
+            execute("a  {{- 1 -}}  b {{/* comment */}}c", json!({})),
+            "a1b c"
+        );
+        assert_eq!(
+            execute(
+                "{{ if .A }}a{{ else if .B }}b{{ else }}c{{ end }}",
+                json!({ "B": [1] })
+            ),
+            "b"
+        );
+        assert_eq!(
+            execute(
+                "{{ range $i, $n := .N }}{{ if eq $n 3 }}{{ break }}{{ end }}{{ $i }}:{{ . }} {{ end }}",
+                json!({ "N": [1, 2, 3, 4] })
+            ),
+            "0:1 1:2 "
+        );
+        assert_eq!(
+            execute(
+                "{{ range .N }}x{{ else }}empty{{ end }}",
+                json!({ "N": [] })
+            ),
+            "empty"
+        );
+        assert_eq!(
+            execute(
+                "{{ $x := 1 }}{{ if true }}{{ $x = 2 }}{{ end }}{{ $x }}",
+                json!({})
+            ),
+            "2"
+        );
+        assert_eq!(
+            execute(
+                "{{ with .User }}{{ .Name }}{{ end }}",
+                json!({ "User": { "name": "Ada" } })
+            ),
+            "Ada"
+        );
+        assert_eq!(
+            execute(
+                r#"{{ printf "%s=%d" .K .V }} {{ .K | len }} {{ index .M "k" }}"#,
+                json!({ "K": "ab", "V": 3, "M": { "k": "v" } })
+            ),
+            "ab=3 2 v"
+        );
+        assert_eq!(
+            execute("{{ or .Missing \"default\" }}", json!({})),
+            "default"
+        );
+
+        for source in [
+            "{{ if .A }}",
+            "{{ end }}",
+            "{{ .A",
+            "{{ unknown .A }}",
+            "{{ template \"x\" }}",
+        ] {
+            assert!(Template::parse(source).is_err(), "{source}");
+        }
+        let template = Template::parse("{{ lt .A 1 }}").unwrap();
+        assert!(template.execute(&json!({ "A": "a" })).is_err());
+    }
+}
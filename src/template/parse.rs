@@ -0,0 +1,535 @@
+use serde_json::Value;
+
+use crate::error::OllamaError;
+
+/// Functions available in templates, the Go builtins Ollama templates use along with
+/// Ollama's own `json` and `currentDate`.
+pub(crate) const FUNCTIONS: &[&str] = &[
+    "and",
+    "or",
+    "not",
+    "eq",
+    "ne",
+    "lt",
+    "le",
+    "gt",
+    "ge",
+    "len",
+    "index",
+    "slice",
+    "print",
+    "printf",
+    "println",
+    "json",
+    "currentDate",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    Text(String),
+
+    /// Prints the value of the pipeline, unless it declares or assigns variables.
+    Action(Pipeline),
+
+    /// `if` and its `else if` branches.
+    If {
+        branches: Vec<(Pipeline, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+
+    /// `with` and its `else with` branches, setting dot to the value of the pipeline.
+    With {
+        branches: Vec<(Pipeline, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+
+    Range {
+        pipeline: Pipeline,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Pipeline {
+    /// Variables declared (`:=`) or assigned (`=`) by the pipeline.
+    pub decl: Vec<String>,
+    pub assign: bool,
+    /// Commands separated by `|`, each result being passed as the last argument of the next.
+    pub commands: Vec<Vec<Arg>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Arg {
+    /// `.Field.Field`, or dot itself when empty.
+    Field(Vec<String>),
+    /// `$name.Field.Field`, `$` being the data the template was executed with.
+    Var(String, Vec<String>),
+    Literal(Value),
+    Function(String),
+    Pipeline(Box<Pipeline>),
+}
+
+impl Node {
+    /// Calls `f` on every pipeline of the node and of its children, in order.
+    pub(crate) fn visit(&self, f: &mut impl FnMut(&Pipeline)) {
+        match self {
+            Node::Text(_) | Node::Break | Node::Continue => {}
+            Node::Action(pipeline) => f(pipeline),
+            Node::If {
+                branches,
+                otherwise,
+            }
+            | Node::With {
+                branches,
+                otherwise,
+            } => {
+                for (pipeline, body) in branches {
+                    f(pipeline);
+                    body.iter().for_each(|n| n.visit(f));
+                }
+                otherwise.iter().for_each(|n| n.visit(f));
+            }
+            Node::Range {
+                pipeline,
+                body,
+                otherwise,
+            } => {
+                f(pipeline);
+                body.iter().chain(otherwise).for_each(|n| n.visit(f));
+            }
+        }
+    }
+}
+
+impl Pipeline {
+    /// Whether the pipeline reads the field `name` anywhere, on dot or on a variable.
+    pub(crate) fn uses_field(&self, name: &str) -> bool {
+        self.commands.iter().flatten().any(|arg| match arg {
+            Arg::Field(fields) | Arg::Var(_, fields) => fields.iter().any(|f| f == name),
+            Arg::Pipeline(pipeline) => pipeline.uses_field(name),
+            Arg::Literal(_) | Arg::Function(_) => false,
+        })
+    }
+}
+
+fn error(message: impl std::fmt::Display) -> OllamaError {
+    OllamaError::TemplateError(message.to_string())
+}
+
+/// Text and actions, with the `{{-` and `-}}` trim markers applied and comments removed.
+enum Item {
+    Text(String),
+    Action(String),
+}
+
+fn lex(source: &str) -> Result<Vec<Item>, OllamaError> {
+    let mut items = vec![];
+    let mut rest = source;
+    let mut trim_next = false;
+
+    while let Some(open) = rest.find("{{") {
+        let mut text = &rest[..open];
+        if trim_next {
+            text = text.trim_start();
+        }
+        let after = &rest[open + 2..];
+        let trim_left = after.starts_with('-') && after[1..].starts_with(char::is_whitespace);
+        if trim_left {
+            text = text.trim_end();
+        }
+        if !text.is_empty() {
+            items.push(Item::Text(text.to_string()));
+        }
+
+        let inner = if trim_left { &after[1..] } else { after };
+        let close = find_close(inner).ok_or_else(|| error("unclosed action"))?;
+        let mut action = &inner[..close];
+        trim_next =
+            action.ends_with('-') && action[..action.len() - 1].ends_with(char::is_whitespace);
+        if trim_next {
+            action = &action[..action.len() - 1];
+        }
+
+        let action = action.trim();
+        if action.starts_with("/*") {
+            if !action.ends_with("*/") {
+                return Err(error("unclosed comment"));
+            }
+        } else {
+            items.push(Item::Action(action.to_string()));
+        }
+        rest = &inner[close + 2..];
+    }
+
+    let text = if trim_next { rest.trim_start() } else { rest };
+    if !text.is_empty() {
+        items.push(Item::Text(text.to_string()));
+    }
+    Ok(items)
+}
+
+/// The position of the `}}` closing an action, skipping those inside quotes and comments.
+fn find_close(inner: &str) -> Option<usize> {
+    if inner.trim_start().starts_with("/*") {
+        let end = inner.find("*/")?;
+        return inner[end..].find("}}").map(|i| end + i);
+    }
+
+    let bytes = inner.as_bytes();
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (quote, bytes[i]) {
+            (Some(b'"'), b'\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (None, c @ (b'"' | b'`')) => quote = Some(c),
+            (None, b'}') if bytes.get(i + 1) == Some(&b'}') => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Field(Vec<String>),
+    Var(String, Vec<String>),
+    Literal(Value),
+    Ident(String),
+    LParen,
+    RParen,
+    Pipe,
+    Declare,
+    Assign,
+    Comma,
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Reads `.Field.Field` at the start of `chars`, returning the fields and the bytes read.
+fn fields(s: &str) -> (Vec<String>, usize) {
+    let mut fields = vec![];
+    let mut read = 0;
+    while s[read..].starts_with('.') {
+        let name: String = s[read + 1..].chars().take_while(|c| is_ident(*c)).collect();
+        if name.is_empty() {
+            break;
+        }
+        read += 1 + name.len();
+        fields.push(name);
+    }
+    (fields, read)
+}
+
+fn tokenize(action: &str) -> Result<Vec<Token>, OllamaError> {
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < action.len() {
+        let rest = &action[i..];
+        let c = rest.chars().next().unwrap();
+
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if c == '.' {
+            let (fields, read) = fields(rest);
+            tokens.push(Token::Field(fields));
+            i += read.max(1);
+        } else if c == '$' {
+            let name: String = rest[1..].chars().take_while(|c| is_ident(*c)).collect();
+            let (fields, read) = fields(&rest[1 + name.len()..]);
+            tokens.push(Token::Var(name.clone(), fields));
+            i += 1 + name.len() + read;
+        } else if c == '"' {
+            let end = find_quote_end(rest).ok_or_else(|| error("unterminated string"))?;
+            let value: String = serde_json::from_str(&rest[..=end])
+                .map_err(|e| error(format!("invalid string {}: {e}", &rest[..=end])))?;
+            tokens.push(Token::Literal(Value::String(value)));
+            i += end + 1;
+        } else if c == '`' {
+            let end = rest[1..]
+                .find('`')
+                .ok_or_else(|| error("unterminated raw string"))?;
+            tokens.push(Token::Literal(Value::String(rest[1..=end].to_string())));
+            i += end + 2;
+        } else if c.is_ascii_digit()
+            || (c == '-' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let len = 1 + rest[1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or(rest.len() - 1);
+            let number = &rest[..len];
+            let value = number
+                .parse::<i64>()
+                .map(Value::from)
+                .or_else(|_| number.parse::<f64>().map(Value::from))
+                .map_err(|_| error(format!("invalid number {number}")))?;
+            tokens.push(Token::Literal(value));
+            i += len;
+        } else if is_ident(c) {
+            let word: String = rest.chars().take_while(|c| is_ident(*c)).collect();
+            i += word.len();
+            tokens.push(match word.as_str() {
+                "true" => Token::Literal(Value::Bool(true)),
+                "false" => Token::Literal(Value::Bool(false)),
+                "nil" => Token::Literal(Value::Null),
+                _ => Token::Ident(word),
+            });
+        } else {
+            let (token, len) = match c {
+                '(' => (Token::LParen, 1),
+                ')' => (Token::RParen, 1),
+                '|' => (Token::Pipe, 1),
+                ',' => (Token::Comma, 1),
+                '=' => (Token::Assign, 1),
+                ':' if rest.starts_with(":=") => (Token::Declare, 2),
+                _ => return Err(error(format!("unexpected {c:?} in {{{{ {action} }}}}"))),
+            };
+            tokens.push(token);
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
+
+fn find_quote_end(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'"' => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Parses the tokens of an action into a pipeline.
+struct PipelineParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl PipelineParser {
+    fn parse(tokens: Vec<Token>) -> Result<Pipeline, OllamaError> {
+        let mut parser = Self { tokens, pos: 0 };
+        let decl = parser.declarations();
+        let pipeline = parser.pipeline(decl)?;
+        if parser.pos < parser.tokens.len() {
+            return Err(error(format!(
+                "unexpected {:?} in pipeline",
+                parser.tokens[parser.pos]
+            )));
+        }
+        Ok(pipeline)
+    }
+
+    /// `$a :=`, `$a, $b :=` or `$a =` at the start of a pipeline.
+    fn declarations(&mut self) -> Option<(Vec<String>, bool)> {
+        let mut names = vec![];
+        let mut pos = self.pos;
+        loop {
+            match self.tokens.get(pos) {
+                Some(Token::Var(name, fields)) if fields.is_empty() => names.push(name.clone()),
+                _ => return None,
+            }
+            match self.tokens.get(pos + 1) {
+                Some(Token::Comma) if names.len() == 1 => pos += 2,
+                Some(Token::Declare) => {
+                    self.pos = pos + 2;
+                    return Some((names, false));
+                }
+                Some(Token::Assign) => {
+                    self.pos = pos + 2;
+                    return Some((names, true));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn pipeline(&mut self, decl: Option<(Vec<String>, bool)>) -> Result<Pipeline, OllamaError> {
+        let (decl, assign) = decl.unwrap_or_default();
+        let mut commands = vec![self.command()?];
+        while self.tokens.get(self.pos) == Some(&Token::Pipe) {
+            self.pos += 1;
+            let command = self.command()?;
+            if !matches!(command[0], Arg::Function(_)) {
+                return Err(error("only functions can be piped into"));
+            }
+            commands.push(command);
+        }
+        Ok(Pipeline {
+            decl,
+            assign,
+            commands,
+        })
+    }
+
+    fn command(&mut self) -> Result<Vec<Arg>, OllamaError> {
+        let mut args = vec![];
+        while let Some(token) = self.tokens.get(self.pos).cloned() {
+            let arg = match token {
+                Token::Pipe | Token::RParen => break,
+                Token::Field(fields) => Arg::Field(fields),
+                Token::Var(name, fields) => Arg::Var(name, fields),
+                Token::Literal(value) => Arg::Literal(value),
+                Token::Ident(name) => {
+                    if !FUNCTIONS.contains(&name.as_str()) {
+                        return Err(error(format!("function {name:?} not defined")));
+                    }
+                    Arg::Function(name)
+                }
+                Token::LParen => {
+                    self.pos += 1;
+                    let decl = self.declarations();
+                    let pipeline = self.pipeline(decl)?;
+                    if self.tokens.get(self.pos) != Some(&Token::RParen) {
+                        return Err(error("unclosed left paren"));
+                    }
+                    Arg::Pipeline(Box::new(pipeline))
+                }
+                token => return Err(error(format!("unexpected {token:?} in command"))),
+            };
+            self.pos += 1;
+            args.push(arg);
+        }
+
+        if args.is_empty() {
+            return Err(error("missing value for command"));
+        }
+        Ok(args)
+    }
+}
+
+/// How a list of nodes ended.
+enum End {
+    Eof,
+    /// `{{end}}`.
+    Closed,
+    /// `else`, with the tokens following it, e.g. `if .Tools` for `else if .Tools`.
+    Else(Vec<Token>),
+}
+
+pub(crate) fn parse(source: &str) -> Result<Vec<Node>, OllamaError> {
+    let items = lex(source)?;
+    let mut items = items.into_iter();
+    match list(&mut items)? {
+        (nodes, End::Eof) => Ok(nodes),
+        (_, End::Closed) => Err(error("unexpected {{end}}")),
+        (_, End::Else(_)) => Err(error("unexpected {{else}}")),
+    }
+}
+
+fn list(items: &mut impl Iterator<Item = Item>) -> Result<(Vec<Node>, End), OllamaError> {
+    let mut nodes = vec![];
+    while let Some(item) = items.next() {
+        let action = match item {
+            Item::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Item::Action(action) => action,
+        };
+
+        let mut tokens = tokenize(&action)?;
+        let keyword = match tokens.first() {
+            Some(Token::Ident(word)) => word.clone(),
+            Some(_) => String::new(),
+            None => return Err(error("missing value for command")),
+        };
+        if matches!(
+            keyword.as_str(),
+            "if" | "with" | "range" | "else" | "end" | "break" | "continue"
+        ) {
+            tokens.remove(0);
+        }
+
+        match keyword.as_str() {
+            "end" => return Ok((nodes, End::Closed)),
+            "else" => return Ok((nodes, End::Else(tokens))),
+            "break" => nodes.push(Node::Break),
+            "continue" => nodes.push(Node::Continue),
+            "if" | "with" => {
+                let (branches, otherwise) = branches(items, &keyword, tokens)?;
+                nodes.push(if keyword == "if" {
+                    Node::If {
+                        branches,
+                        otherwise,
+                    }
+                } else {
+                    Node::With {
+                        branches,
+                        otherwise,
+                    }
+                });
+            }
+            "range" => {
+                let pipeline = PipelineParser::parse(tokens)?;
+                let (body, end) = list(items)?;
+                let otherwise = match end {
+                    End::Closed => vec![],
+                    End::Else(tokens) if tokens.is_empty() => closed(items)?,
+                    End::Else(_) => return Err(error("unexpected tokens after {{else}} in range")),
+                    End::Eof => return Err(error("unclosed {{range}}")),
+                };
+                nodes.push(Node::Range {
+                    pipeline,
+                    body,
+                    otherwise,
+                });
+            }
+            "define" | "template" | "block" => {
+                return Err(error(format!("{{{{{keyword}}}}} is not supported")));
+            }
+            _ => nodes.push(Node::Action(PipelineParser::parse(tokens)?)),
+        }
+    }
+    Ok((nodes, End::Eof))
+}
+
+type Branches = (Vec<(Pipeline, Vec<Node>)>, Vec<Node>);
+
+/// The branches of an `if` or `with`, following `else if` or `else with` chains.
+fn branches(
+    items: &mut impl Iterator<Item = Item>,
+    keyword: &str,
+    tokens: Vec<Token>,
+) -> Result<Branches, OllamaError> {
+    let mut branches = vec![];
+    let mut pipeline = PipelineParser::parse(tokens)?;
+    loop {
+        let (body, end) = list(items)?;
+        branches.push((pipeline, body));
+        match end {
+            End::Closed => return Ok((branches, vec![])),
+            End::Eof => return Err(error(format!("unclosed {{{{{keyword}}}}}"))),
+            End::Else(tokens) if tokens.is_empty() => return Ok((branches, closed(items)?)),
+            End::Else(mut tokens) => {
+                if tokens.first() != Some(&Token::Ident(keyword.to_string())) {
+                    return Err(error(format!("expected {{{{else {keyword}}}}}")));
+                }
+                tokens.remove(0);
+                pipeline = PipelineParser::parse(tokens)?;
+            }
+        }
+    }
+}
+
+/// The nodes of an `else` branch, which must end with `{{end}}`.
+fn closed(items: &mut impl Iterator<Item = Item>) -> Result<Vec<Node>, OllamaError> {
+    match list(items)? {
+        (nodes, End::Closed) => Ok(nodes),
+        (_, End::Eof) => Err(error("unclosed {{else}}")),
+        (_, End::Else(_)) => Err(error("unexpected {{else}} after {{else}}")),
+    }
+}
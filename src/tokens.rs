@@ -0,0 +1,9 @@
+//! Rough token counts, for budgeting context windows and requests without a tokenizer.
+
+/// Characters per token assumed when estimating, about the average for English text.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimated number of tokens in a text, assuming [`CHARS_PER_TOKEN`] characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}